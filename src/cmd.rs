@@ -1,4 +1,16 @@
+mod archive;
 mod common;
+mod crc_footer;
 pub mod dump_fmap;
+pub mod error;
 pub mod extract_fmap;
+pub mod fwid;
+mod ifd;
+mod image;
+pub mod info;
 pub mod load_fmap;
+pub mod modify;
+mod ops;
+pub mod probe;
+pub mod repack;
+pub mod summary;
@@ -0,0 +1,115 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+
+use camino::Utf8Path;
+
+/* Archive formats extracted areas can be stored in, detected by file extension. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Tar,
+    Zip,
+}
+
+impl Format {
+    pub fn from_path(path: &Utf8Path) -> Option<Format> {
+        match path.extension().map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("tar") => Some(Format::Tar),
+            Some("zip") => Some(Format::Zip),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `entries` given as (name, content) pairs into archive at `path`.
+pub fn write(path: &Utf8Path, entries: &[(String, Vec<u8>)]) -> Result<(), Box<dyn Error>> {
+    let format = match Format::from_path(path) {
+        Some(v) => v,
+        None => return Err(format!("Unsupported archive format of '{path}'").into()),
+    };
+    let file = File::create(path)?;
+
+    match format {
+        Format::Tar => {
+            let mut builder = tar::Builder::new(file);
+            for (name, data) in entries.iter() {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                builder.append_data(&mut header, name, data.as_slice())?;
+            }
+            builder.into_inner()?.flush()?;
+        }
+        Format::Zip => {
+            let mut writer = zip::ZipWriter::new(file);
+            let options = zip::write::SimpleFileOptions::default();
+            for (name, data) in entries.iter() {
+                writer.start_file(name.as_str(), options)?;
+                writer.write_all(data)?;
+            }
+            writer.finish()?.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    use camino::Utf8PathBuf;
+
+    fn entries() -> Vec<(String, Vec<u8>)> {
+        vec![
+            ("FIRST".to_string(), vec![1u8; 16]),
+            ("SECOND".to_string(), vec![2u8; 32]),
+        ]
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path("a.tar".into()), Some(Format::Tar));
+        assert_eq!(Format::from_path("a/b.ZIP".into()), Some(Format::Zip));
+        assert_eq!(Format::from_path("a.tar.gz".into()), None);
+        assert_eq!(Format::from_path("a".into()), None);
+    }
+
+    #[test]
+    fn test_write_tar() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = Utf8PathBuf::from_path_buf(dir.path().join("areas.tar")).unwrap();
+        write(&path, &entries())?;
+
+        let mut archive = tar::Archive::new(File::open(&path)?);
+        let mut result = Vec::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            result.push((entry.path()?.to_string_lossy().to_string(), data));
+        }
+        assert_eq!(result, entries());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_zip() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = Utf8PathBuf::from_path_buf(dir.path().join("areas.zip")).unwrap();
+        write(&path, &entries())?;
+
+        let mut archive = zip::ZipArchive::new(File::open(&path)?)?;
+        let mut result = Vec::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            result.push((entry.name().to_string(), data));
+        }
+        assert_eq!(result, entries());
+
+        Ok(())
+    }
+}
@@ -1,27 +1,145 @@
 use camino::Utf8PathBuf;
+use log::warn;
+use std::convert::TryFrom;
 use std::str::FromStr;
 
+use crate::{cmd::archive, fmap};
+
 pub fn file_exists_validator(s: &str) -> Result<Utf8PathBuf, String> {
     let p = Utf8PathBuf::from(s);
-    if p.exists() {
-        Ok(p)
-    } else {
-        Err(format!("File '{}' does not exist", s).to_string())
+    if !p.exists() {
+        return Err(format!("File '{}' does not exist", s).to_string());
+    }
+    if p.is_dir() {
+        return Err(format!("'{s}' is a directory, not a file"));
+    }
+    if let Err(e) = std::fs::File::open(&p) {
+        return Err(format!("File '{s}' is not readable: {e}"));
     }
+    Ok(p)
 }
 
+pub fn archive_path_validator(s: &str) -> Result<Utf8PathBuf, String> {
+    let p = Utf8PathBuf::from(s);
+    match archive::Format::from_path(&p) {
+        Some(_) => Ok(p),
+        None => Err(format!("Archive '{s}' should have .tar or .zip extension")),
+    }
+}
+
+/// Parses mapping in format SECTION:PATH. Section name ends at the first colon,
+/// so the path may contain colons, e.g. FW_MAIN_A:C:\fw.bin.
 pub fn area_to_file_mapping_param_valid(s: &str) -> Result<(String, Utf8PathBuf), String> {
-    let parts: Vec<&str> = s.split(':').collect();
-    if parts.len() != 2 {
-        return Err(String::from(
-            "The argument should be in the format 'SECTION:PATH'",
-        ));
+    let (section, path) = s.split_once(':').ok_or(String::from(
+        "The argument should be in the format 'SECTION:PATH'",
+    ))?;
+    if let Some(range) = section.strip_prefix('@') {
+        offset_range_validator(range)?;
     }
-    Ok((String::from(parts[0]), Utf8PathBuf::from(parts[1])))
+    split_offset_qualifier(section)?;
+    split_digest(path)?;
+    Ok((String::from(section), Utf8PathBuf::from(path)))
 }
 
-pub fn decimal_or_hex_validator_u8(s: &str) -> Result<u8, String> {
-    if let Ok(decimal) = u8::from_str(s) {
+/// Pseudo-section selecting FlashMap header and area entries found in the image, whether
+/// or not FlashMap declares an area covering them.
+pub const FMAP_SECTION: &str = "@fmap";
+
+/// Parses mapping in format SECTION:PATH like `area_to_file_mapping_param_valid`, or bare
+/// SECTION, which is returned with empty path. SECTION may also be `FMAP_SECTION`.
+pub fn area_mapping_or_name_param_valid(s: &str) -> Result<(String, Utf8PathBuf), String> {
+    let section = s.split_once(':').map_or(s, |(section, _)| section);
+    if section == FMAP_SECTION {
+        let path = s.get(section.len() + 1..).unwrap_or("");
+        split_digest(path)?;
+        return Ok((section.to_string(), Utf8PathBuf::from(path)));
+    }
+    if s.contains(':') {
+        return area_to_file_mapping_param_valid(s);
+    }
+    if let Some(range) = s.strip_prefix('@') {
+        offset_range_validator(range)?;
+    }
+    split_offset_qualifier(s)?;
+    Ok((String::from(s), Utf8PathBuf::new()))
+}
+
+/// Splits optional `@OFFSET` qualifier off SECTION, e.g. `FW_MAIN_A@0x510000`, selecting
+/// one of areas sharing the name. Raw byte ranges starting with `@` are returned whole.
+pub fn split_offset_qualifier(section: &str) -> Result<(&str, Option<u32>), String> {
+    match section.rsplit_once('@') {
+        Some((name, offset)) if !name.is_empty() => {
+            let offset = decimal_or_hex_validator_u32(offset)
+                .map_err(|e| format!("Invalid offset of section '{name}': {e}"))?;
+            Ok((name, Some(offset)))
+        }
+        _ => Ok((section, None)),
+    }
+}
+
+/// Splits optional `=ALGO:HEX` digest suffix off `path`, e.g. `out.bin=sha256:ab12...`.
+/// Suffix is recognized only if ALGO is alphanumeric and HEX consists of hex digits,
+/// otherwise whole `path` is returned. Only `sha256` is supported. Digest is lowercased.
+pub fn split_digest(path: &str) -> Result<(&str, Option<String>), String> {
+    let digest = path
+        .rsplit_once('=')
+        .and_then(|(file, suffix)| Some((file, suffix.split_once(':')?)))
+        .filter(|(_, (algo, hex))| {
+            !algo.is_empty()
+                && algo.chars().all(|c| c.is_ascii_alphanumeric())
+                && !hex.is_empty()
+                && hex.chars().all(|c| c.is_ascii_hexdigit())
+        });
+    match digest {
+        None => Ok((path, None)),
+        Some((file, ("sha256", hex))) if hex.len() == 64 => {
+            Ok((file, Some(hex.to_ascii_lowercase())))
+        }
+        Some((_, ("sha256", hex))) => Err(format!(
+            "SHA-256 digest should have 64 hex digits, got {}",
+            hex.len()
+        )),
+        Some((_, (algo, _))) => Err(format!(
+            "Unsupported digest algorithm '{algo}'. Supported: sha256"
+        )),
+    }
+}
+
+/// Parses area flag assignment in format SECTION:FLAG, e.g. RW_NVRAM:PRESERVE.
+/// Flag names are case-insensitive. Several flags may be given, e.g. RO_SECTION:RO|STATIC.
+pub fn area_flag_param_valid(s: &str) -> Result<(String, fmap::FMapFlags), String> {
+    let (area, flag) = s.rsplit_once(':').ok_or(format!(
+        "The argument '{s}' should be in the format 'SECTION:FLAG'"
+    ))?;
+    Ok((area.to_string(), fmap::FMapFlags::from_symbolic(flag)?))
+}
+
+/// Parses byte range given as `OFFSET+SIZE` or `START-END` (END exclusive).
+/// Returns (offset, size).
+pub fn offset_range_validator(s: &str) -> Result<(u32, u32), String> {
+    if let Some((offset, size)) = s.split_once('+') {
+        let offset = decimal_or_hex_validator_u32(offset)?;
+        let size = decimal_or_hex_validator_u32(size)?;
+        if offset.checked_add(size).is_none() {
+            return Err(format!("Range '{s}' exceeds 32-bit address space"));
+        }
+        Ok((offset, size))
+    } else if let Some((start, end)) = s.split_once('-') {
+        let start = decimal_or_hex_validator_u32(start)?;
+        let end = decimal_or_hex_validator_u32(end)?;
+        match end.checked_sub(start) {
+            Some(size) => Ok((start, size)),
+            None => Err(format!("Range '{s}' ends before it starts")),
+        }
+    } else {
+        Err(format!(
+            "Range '{s}' should be in the format 'OFFSET+SIZE' or 'START-END'"
+        ))
+    }
+}
+
+pub fn decimal_or_hex_validator_u64(s: &str) -> Result<u64, String> {
+    if let Ok(decimal) = u64::from_str(s) {
         return Ok(decimal);
     }
     let s1 = if s.starts_with("0x") {
@@ -31,10 +149,400 @@ pub fn decimal_or_hex_validator_u8(s: &str) -> Result<u8, String> {
     } else {
         s
     };
-    if let Ok(hex) = u8::from_str_radix(s1, 16) {
+    if let Ok(hex) = u64::from_str_radix(s1, 16) {
         return Ok(hex);
     }
     Err(format!(
         "Value '{s}' is not a correctr integer nor hex value matching the argument type"
     ))
 }
+
+/// Accepts decimal or hex power of two, e.g. alignment of FlashMap.
+pub fn alignment_validator(s: &str) -> Result<usize, String> {
+    match usize::try_from(decimal_or_hex_validator_u64(s)?) {
+        Ok(v) if v.is_power_of_two() => Ok(v),
+        _ => Err(format!("Value '{s}' is not a power of two")),
+    }
+}
+
+/// Accepts decimal or hex value with optional sign, e.g. -0x1000.
+pub fn signed_decimal_or_hex_validator_i64(s: &str) -> Result<i64, String> {
+    let (negative, magnitude) = match s.strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let magnitude = i128::from(decimal_or_hex_validator_u64(magnitude)?);
+    i64::try_from(if negative { -magnitude } else { magnitude }).map_err(|_| {
+        format!("Value '{s}' is not a correctr integer nor hex value matching the argument type")
+    })
+}
+
+pub fn decimal_or_hex_validator_u32(s: &str) -> Result<u32, String> {
+    u32::try_from(decimal_or_hex_validator_u64(s)?).map_err(|_| {
+        format!("Value '{s}' is not a correctr integer nor hex value matching the argument type")
+    })
+}
+
+/// Accepts byte value in decimal, hex, binary with `0b` or octal with `0o` prefix.
+/// Underscores separating digits are ignored, e.g. 0b1010_0101.
+pub fn decimal_or_hex_validator_u8(s: &str) -> Result<u8, String> {
+    let value = s.replace('_', "");
+    let radix = [("0b", 2), ("0B", 2), ("0o", 8), ("0O", 8)]
+        .iter()
+        .find_map(|&(prefix, radix)| Some((value.strip_prefix(prefix)?, radix)));
+    let parsed = match radix {
+        Some((digits, radix)) => u64::from_str_radix(digits, radix).ok(),
+        None => decimal_or_hex_validator_u64(&value).ok(),
+    };
+    parsed.and_then(|v| u8::try_from(v).ok()).ok_or_else(|| {
+        format!("Value '{s}' is not a correctr integer nor hex value matching the argument type")
+    })
+}
+
+/// Formats `bytes` as lowercase hex string, e.g. for printing digests.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Translates flash `offset` of data of given `size` into offset within image file
+/// starting at flash offset `base` and verifies that the data fits in the image.
+pub fn offset_in_image(offset: u32, size: u32, base: u32, image_size: u64) -> Result<u64, String> {
+    let image_offset = match offset.checked_sub(base) {
+        Some(v) => v as u64,
+        None => {
+            return Err(format!(
+                "Offset {offset:#x} is placed before image base {base:#x}"
+            ))
+        }
+    };
+    if image_offset + size as u64 > image_size {
+        return Err(format!(
+            "Data at offset {offset:#x} of size {size:#x} does not fit in image of size {image_size:#x} with base {base:#x}"
+        ));
+    }
+    Ok(image_offset)
+}
+
+/// Warns about area names used more than once in `fmap`. Lookups by such name use the
+/// first area. Returns error instead if `strict` is set.
+pub fn check_duplicate_names(fmap: &fmap::FMap, strict: bool) -> Result<(), String> {
+    let names = fmap.duplicate_names();
+    if names.is_empty() {
+        return Ok(());
+    }
+    let message = format!("Duplicated FlashMap area names: {}", names.join(", "));
+    if strict {
+        return Err(message);
+    }
+    warn!("{message}. The first area of each name is used");
+    Ok(())
+}
+
+/// Matches `name` against shell-like `pattern` where `*` matches any sequence of characters
+/// and `?` matches any single character.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Position in the pattern of last `*` and position in the name it was matched at.
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            // Let the last `*` consume one more character.
+            backtrack = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+pub fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+/// Accepts single byte value, see `decimal_or_hex_validator_u8`, or hex pattern of multiple
+/// bytes, e.g. 0xDEADBEEF. Underscores separating digits are ignored.
+pub fn fill_pattern_validator(s: &str) -> Result<Vec<u8>, String> {
+    if let Ok(byte) = decimal_or_hex_validator_u8(s) {
+        return Ok(vec![byte]);
+    }
+    let value = s.replace('_', "");
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .ok_or(format!(
+            "Value '{s}' is not a correct byte value nor hex pattern starting with 0x"
+        ))?;
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return Err(format!(
+            "Hex pattern '{s}' must contain non-zero, even number of digits"
+        ));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            digits
+                .get(i..i + 2)
+                .and_then(|d| u8::from_str_radix(d, 16).ok())
+                .ok_or(format!("Hex pattern '{s}' contains invalid digits"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_exists_validator() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let file_path = workdir.join("file.bin");
+        std::fs::write(&file_path, [0u8; 4])?;
+
+        assert_eq!(file_exists_validator(file_path.as_str()), Ok(file_path));
+        assert!(file_exists_validator(workdir.as_str())
+            .unwrap_err()
+            .contains("is a directory"));
+        assert!(file_exists_validator(workdir.join("missing.bin").as_str())
+            .unwrap_err()
+            .contains("does not exist"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_area_to_file_mapping_param_valid() {
+        assert_eq!(
+            area_to_file_mapping_param_valid("FW_MAIN_A:fw.bin"),
+            Ok(("FW_MAIN_A".to_string(), Utf8PathBuf::from("fw.bin")))
+        );
+        assert_eq!(
+            area_to_file_mapping_param_valid("FW_MAIN_A:C:\\fw.bin"),
+            Ok(("FW_MAIN_A".to_string(), Utf8PathBuf::from("C:\\fw.bin")))
+        );
+        assert_eq!(
+            area_to_file_mapping_param_valid("GBB:out:a:b.bin"),
+            Ok(("GBB".to_string(), Utf8PathBuf::from("out:a:b.bin")))
+        );
+        assert!(area_to_file_mapping_param_valid("FW_MAIN_A").is_err());
+        assert!(area_to_file_mapping_param_valid("FW_MAIN_A:fw.bin=md5:abcd").is_err());
+    }
+
+    #[test]
+    fn test_area_mapping_or_name_param_valid() {
+        assert_eq!(
+            area_mapping_or_name_param_valid("FW_MAIN_A"),
+            Ok(("FW_MAIN_A".to_string(), Utf8PathBuf::new()))
+        );
+        assert_eq!(
+            area_mapping_or_name_param_valid("FW_MAIN_A:fw.bin"),
+            Ok(("FW_MAIN_A".to_string(), Utf8PathBuf::from("fw.bin")))
+        );
+        assert!(area_mapping_or_name_param_valid("@0x10").is_err());
+        assert_eq!(
+            area_mapping_or_name_param_valid("@fmap:fmap.bin"),
+            Ok((FMAP_SECTION.to_string(), Utf8PathBuf::from("fmap.bin")))
+        );
+        assert_eq!(
+            area_mapping_or_name_param_valid("FW_MAIN_A@0x510000:out.bin"),
+            Ok((
+                "FW_MAIN_A@0x510000".to_string(),
+                Utf8PathBuf::from("out.bin")
+            ))
+        );
+        assert!(area_mapping_or_name_param_valid("FW_MAIN_A@zz:out.bin").is_err());
+        assert!(area_mapping_or_name_param_valid("FW_MAIN_A@").is_err());
+        assert_eq!(
+            area_mapping_or_name_param_valid("@fmap"),
+            Ok((FMAP_SECTION.to_string(), Utf8PathBuf::new()))
+        );
+    }
+
+    #[test]
+    fn test_split_offset_qualifier() {
+        assert_eq!(split_offset_qualifier("FW_MAIN_A"), Ok(("FW_MAIN_A", None)));
+        assert_eq!(
+            split_offset_qualifier("FW_MAIN_A@0x510000"),
+            Ok(("FW_MAIN_A", Some(0x510000)))
+        );
+        assert_eq!(split_offset_qualifier("A@16"), Ok(("A", Some(16))));
+        assert_eq!(
+            split_offset_qualifier("@0x10+0x10"),
+            Ok(("@0x10+0x10", None))
+        );
+        assert!(split_offset_qualifier("A@0x100000000").is_err());
+    }
+
+    #[test]
+    fn test_split_digest() {
+        let hex = "ab".repeat(32);
+        assert_eq!(split_digest("fw.bin"), Ok(("fw.bin", None)));
+        assert_eq!(
+            split_digest(&format!("fw.bin=sha256:{}", hex.to_uppercase())),
+            Ok(("fw.bin", Some(hex.clone())))
+        );
+        assert_eq!(
+            split_digest(&format!("a=b.bin=sha256:{hex}")),
+            Ok(("a=b.bin", Some(hex.clone())))
+        );
+        // Not a digest suffix.
+        assert_eq!(split_digest("a=C:\\fw.bin"), Ok(("a=C:\\fw.bin", None)));
+        assert!(split_digest("fw.bin=sha256:abcd").is_err());
+        assert!(split_digest(&format!("fw.bin=md5:{hex}")).is_err());
+    }
+
+    #[test]
+    fn test_fill_pattern_validator() {
+        assert_eq!(fill_pattern_validator("0xff"), Ok(vec![0xff]));
+        assert_eq!(fill_pattern_validator("255"), Ok(vec![0xff]));
+        assert_eq!(fill_pattern_validator("7"), Ok(vec![0x07]));
+        assert_eq!(
+            fill_pattern_validator("0xDEADBEEF"),
+            Ok(vec![0xde, 0xad, 0xbe, 0xef])
+        );
+        assert_eq!(fill_pattern_validator("0X0102"), Ok(vec![0x01, 0x02]));
+        assert!(fill_pattern_validator("0xabc").is_err());
+        assert!(fill_pattern_validator("0x").is_err());
+        assert!(fill_pattern_validator("0xzz00").is_err());
+        assert!(fill_pattern_validator("256").is_err());
+
+        assert_eq!(fill_pattern_validator("0b11111111"), Ok(vec![0xff]));
+        assert_eq!(fill_pattern_validator("0o377"), Ok(vec![0xff]));
+        assert_eq!(fill_pattern_validator("0xf_f"), Ok(vec![0xff]));
+        assert_eq!(fill_pattern_validator("0b1010_0101"), Ok(vec![0xa5]));
+        assert_eq!(
+            fill_pattern_validator("0xDEAD_BEEF"),
+            Ok(vec![0xde, 0xad, 0xbe, 0xef])
+        );
+        assert_eq!(
+            fill_pattern_validator("0XDEAD_BEEF"),
+            Ok(vec![0xde, 0xad, 0xbe, 0xef])
+        );
+        assert!(fill_pattern_validator("0b100000000").is_err());
+        assert!(fill_pattern_validator("0o400").is_err());
+        assert!(fill_pattern_validator("0b2").is_err());
+    }
+
+    #[test]
+    fn test_decimal_or_hex_validator_u8() {
+        assert_eq!(decimal_or_hex_validator_u8("255"), Ok(0xff));
+        assert_eq!(decimal_or_hex_validator_u8("0xff"), Ok(0xff));
+        assert_eq!(decimal_or_hex_validator_u8("0XFF"), Ok(0xff));
+        assert_eq!(decimal_or_hex_validator_u8("0b11111111"), Ok(0xff));
+        assert_eq!(decimal_or_hex_validator_u8("0o377"), Ok(0xff));
+        assert_eq!(decimal_or_hex_validator_u8("0xf_f"), Ok(0xff));
+        assert_eq!(decimal_or_hex_validator_u8("0b1010_0101"), Ok(0xa5));
+        assert_eq!(decimal_or_hex_validator_u8("1_0"), Ok(10));
+        assert!(decimal_or_hex_validator_u8("256").is_err());
+        assert!(decimal_or_hex_validator_u8("0b100000000").is_err());
+        assert!(decimal_or_hex_validator_u8("0o400").is_err());
+        assert!(decimal_or_hex_validator_u8("0b2").is_err());
+        assert!(decimal_or_hex_validator_u8("0o8").is_err());
+        assert!(decimal_or_hex_validator_u8("0xDEADBEEF").is_err());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("RW_SECTION_*", "RW_SECTION_A"));
+        assert!(glob_match("RW_SECTION_*", "RW_SECTION_"));
+        assert!(glob_match("RW_FWID_?", "RW_FWID_B"));
+        assert!(glob_match("*MRC*", "RECOVERY_MRC_CACHE"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("RW_FWID_?", "RW_FWID_"));
+        assert!(!glob_match("RW_SECTION_*", "RO_SECTION"));
+        assert!(!glob_match("*_A", "VBLOCK_B"));
+    }
+
+    #[test]
+    fn test_decimal_or_hex_validator_u32() {
+        assert_eq!(decimal_or_hex_validator_u32("4096"), Ok(4096));
+        assert_eq!(decimal_or_hex_validator_u32("0x1000"), Ok(0x1000));
+        assert_eq!(decimal_or_hex_validator_u32("0xffffffff"), Ok(u32::MAX));
+        assert!(decimal_or_hex_validator_u32("0x100000000").is_err());
+        assert!(decimal_or_hex_validator_u32("zz").is_err());
+    }
+
+    #[test]
+    fn test_alignment_validator() {
+        assert_eq!(alignment_validator("0x1000"), Ok(0x1000));
+        assert_eq!(alignment_validator("1"), Ok(1));
+        assert!(alignment_validator("0").is_err());
+        assert!(alignment_validator("0x1800").is_err());
+    }
+
+    #[test]
+    fn test_signed_decimal_or_hex_validator_i64() {
+        assert_eq!(signed_decimal_or_hex_validator_i64("4096"), Ok(4096));
+        assert_eq!(signed_decimal_or_hex_validator_i64("+0x1000"), Ok(0x1000));
+        assert_eq!(signed_decimal_or_hex_validator_i64("-0x1000"), Ok(-0x1000));
+        assert_eq!(
+            signed_decimal_or_hex_validator_i64("-0x8000000000000000"),
+            Ok(i64::MIN)
+        );
+        assert!(signed_decimal_or_hex_validator_i64("0x8000000000000000").is_err());
+        assert!(signed_decimal_or_hex_validator_i64("--1").is_err());
+    }
+
+    #[test]
+    fn test_offset_range_validator() {
+        assert_eq!(offset_range_validator("0x1000+0x200"), Ok((0x1000, 0x200)));
+        assert_eq!(offset_range_validator("0x1000-0x1200"), Ok((0x1000, 0x200)));
+        assert_eq!(offset_range_validator("16+16"), Ok((16, 16)));
+        assert!(offset_range_validator("0x1200-0x1000").is_err());
+        assert!(offset_range_validator("0xffffffff+2").is_err());
+        assert!(offset_range_validator("0x1000").is_err());
+        assert!(offset_range_validator("0x1000+zz").is_err());
+        assert!(area_to_file_mapping_param_valid("@0x10+0x10:out.bin").is_ok());
+        assert!(area_to_file_mapping_param_valid("@0x10:out.bin").is_err());
+    }
+
+    #[test]
+    fn test_offset_in_image() {
+        assert_eq!(offset_in_image(0x200, 0x100, 0, 0x400), Ok(0x200));
+        assert_eq!(offset_in_image(0x200, 0x100, 0x100, 0x200), Ok(0x100));
+        assert!(offset_in_image(0x80, 0x80, 0x100, 0x400).is_err());
+        assert!(offset_in_image(0x200, 0x200, 0x100, 0x200).is_err());
+    }
+
+    #[test]
+    fn test_check_duplicate_names() {
+        let area = |name: &str, offset| fmap::FMapArea::new(name, offset, 0x10);
+        let mut fmap = fmap::FMap {
+            size: 0x40,
+            areas: vec![area("A", 0), area("B", 0x10)],
+            ..Default::default()
+        };
+        assert_eq!(check_duplicate_names(&fmap, true), Ok(()));
+
+        fmap.areas.push(area("A", 0x20));
+        assert_eq!(check_duplicate_names(&fmap, false), Ok(()));
+        assert_eq!(
+            check_duplicate_names(&fmap, true),
+            Err("Duplicated FlashMap area names: A".to_string())
+        );
+    }
+
+    #[test]
+    fn test_area_flag_param_valid() {
+        assert_eq!(
+            area_flag_param_valid("RW_NVRAM:PRESERVE"),
+            Ok(("RW_NVRAM".to_string(), fmap::FMapFlags::Preserve))
+        );
+        assert_eq!(
+            area_flag_param_valid("RO_SECTION:ro"),
+            Ok(("RO_SECTION".to_string(), fmap::FMapFlags::RO))
+        );
+        assert!(area_flag_param_valid("RW_NVRAM").is_err());
+        assert!(area_flag_param_valid("RW_NVRAM:WRITABLE").is_err());
+    }
+}
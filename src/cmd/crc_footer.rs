@@ -0,0 +1,90 @@
+use std::convert::TryInto;
+use std::io::{self, Write};
+
+/* Optional integrity footer appended to extracted area files.
+ * Layout: <content> <magic: 4 bytes> <CRC32 of content: u32 little-endian> */
+pub const MAGIC: &[u8; 4] = b"FCRC";
+pub const FOOTER_SIZE: usize = MAGIC.len() + 4;
+
+/// Writer passing data through to `inner` writer while computing its CRC32.
+/// Footer is appended by `finish()`.
+pub struct Writer<W: Write> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Writer {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+
+    /// Appends footer with CRC32 of all written data and returns inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let crc = self.hasher.finalize();
+        self.inner.write_all(MAGIC)?;
+        self.inner.write_all(&crc.to_le_bytes())?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Verifies footer at the end of `data` and returns content without it.
+pub fn verify_and_strip(data: &[u8]) -> Result<&[u8], String> {
+    if data.len() < FOOTER_SIZE {
+        return Err("Data too short to contain CRC footer".to_string());
+    }
+    let (content, footer) = data.split_at(data.len() - FOOTER_SIZE);
+    let (magic, crc) = footer.split_at(MAGIC.len());
+    if magic != MAGIC {
+        return Err("CRC footer magic not found".to_string());
+    }
+    let expected = u32::from_le_bytes(crc.try_into().unwrap());
+    let actual = crc32fast::hash(content);
+    if expected != actual {
+        return Err(format!(
+            "CRC mismatch: expected {expected:#010x}, calculated {actual:#010x}"
+        ));
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_verify() -> Result<(), String> {
+        let mut writer = Writer::new(Vec::new());
+        writer.write_all(b"some area ").unwrap();
+        writer.write_all(b"content").unwrap();
+        let mut data = writer.finish().unwrap();
+        assert_eq!(data.len(), 17 + FOOTER_SIZE);
+        assert_eq!(verify_and_strip(&data)?, b"some area content");
+
+        data[0] ^= 0xff;
+        if verify_and_strip(&data).is_ok() {
+            return Err("Tampered data expected to fail verification".to_string());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_missing_footer() {
+        assert!(verify_and_strip(b"short").is_err());
+        assert!(verify_and_strip(b"no footer in here").is_err());
+    }
+}
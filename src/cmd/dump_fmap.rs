@@ -1,21 +1,26 @@
 use std::cell::RefCell;
 use std::error::Error;
-use std::fs::File;
-use std::io::{stdout, Write};
+use std::io::{stdout, Cursor, IsTerminal, Read, Seek, SeekFrom, Write};
 use std::rc::Rc;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
 
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use clap::builder::ArgPredicate;
-use clap::{ArgAction, Args, ValueHint};
+use clap::{ArgAction, Args, ValueEnum, ValueHint};
 use itertools::Itertools;
-use log::{error, warn};
+use log::{error, info, warn};
+use sha2::{Digest, Sha256};
 
 use crate::{
-    cmd::{common, extract_fmap},
-    fmap,
+    cmd::{common, extract_fmap, ifd, image},
+    fmap::{
+        self,
+        tree::{self, Node, NodeRef},
+    },
 };
 
-#[derive(Args)]
+#[derive(Args, Default)]
 pub struct DumpFmapArgs {
     #[arg(index = 1, value_hint = ValueHint::FilePath)]
     /// Firmware image path.
@@ -37,19 +42,64 @@ pub struct DumpFmapArgs {
     /// Include gaps in human-readable format. Implies human-readable format.
     human_readable_with_gaps: bool,
 
+    #[arg(long, value_name = "SIZE", requires = "human_readable_with_gaps", value_parser = common::decimal_or_hex_validator_u64)]
+    /// Do not show gaps smaller than SIZE bytes in human-readable format. Their number is
+    /// reported in a warning.
+    min_gap: Option<u64>,
+
     #[arg(long, action, requires = "human_readable")]
     /// Do not report nor terminate on encountering overlapping sections.
     ignore_overlapping_sections: bool,
 
+    #[arg(long, visible_alias = "no-tree", action, requires = "human_readable")]
+    /// Show nesting level of areas as a column instead of indenting names in human-readable format.
+    flat: bool,
+
+    #[arg(long, value_name = "N", requires = "human_readable")]
+    /// Hide areas nested deeper than N levels below the entire flash in human-readable format.
+    max_depth: Option<usize>,
+
+    #[arg(long, action, requires = "human_readable", conflicts_with_all = ["top_level_only", "tree_style"])]
+    /// Show only areas not containing other areas in human-readable format.
+    leaves_only: bool,
+
+    #[arg(long, action, requires = "human_readable")]
+    /// Show only areas placed directly in the entire flash in human-readable format.
+    top_level_only: bool,
+
+    #[arg(long, action, requires = "human_readable")]
+    /// Show areas with the same offset and size as separate rows instead of marking all
+    /// but the first one as duplicates.
+    no_dedup: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STYLE",
+        requires = "human_readable",
+        conflicts_with = "flat"
+    )]
+    /// Draw connectors between nested areas in human-readable format instead of indenting
+    /// names with spaces only.
+    tree_style: Option<TreeStyle>,
+
     #[arg(long, short, action,
           conflicts_with_all = ["extract", "human_readable", "flashrom_parsable", "ec_parsable"])]
     /// Use format easy to parse by scripts.
     /// <area> <offset> <size>
     parsable: bool,
 
+    #[arg(long, action,
+          conflicts_with_all = ["extract", "human_readable", "parsable", "flashrom_parsable", "ec_parsable", "full_parsable", "csv", "fmd", "py", "hexdump"])]
+    /// Use format easy to parse by scripts including inclusive end of area, as in flashrom format.
+    /// End of area of zero size, which has no bytes, is `-`.
+    /// <area> <offset> <size> <end|->
+    parsable2: bool,
+
     #[arg(long, short = 'F', action,
           conflicts_with_all = ["extract", "human_readable", "parsable", "ec_parsable"])]
-    /// Use format expected by flashrom.
+    /// Use format expected by flashrom. Areas of zero size cannot be described in it
+    /// and are skipped with a warning.
     flashrom_parsable: bool,
 
     #[arg(long, short, action,
@@ -57,6 +107,155 @@ pub struct DumpFmapArgs {
     /// Use format expected by flash_ec.
     ec_parsable: bool,
 
+    #[arg(long, action,
+          conflicts_with_all = ["extract", "human_readable", "parsable", "flashrom_parsable", "ec_parsable"])]
+    /// Use format easy to parse by scripts including all area flags.
+    /// <area> <offset> <size> <FLAG,FLAG,...|NONE>
+    full_parsable: bool,
+
+    #[arg(long, action,
+          conflicts_with_all = ["extract", "human_readable", "parsable", "flashrom_parsable", "ec_parsable", "full_parsable"])]
+    /// Use comma-separated values format with header row.
+    /// name,start,end,size,flags
+    csv: bool,
+
+    #[arg(long, action,
+          conflicts_with_all = ["extract", "human_readable", "parsable", "parsable2", "flashrom_parsable", "ec_parsable", "full_parsable", "csv", "fmd", "py", "hexdump"])]
+    /// Print one JSON object per line: FlashMap header followed by each area.
+    ndjson: bool,
+
+    #[arg(long, action,
+          conflicts_with_all = ["extract", "human_readable", "parsable", "flashrom_parsable", "ec_parsable", "full_parsable", "csv", "hexdump"])]
+    /// Reconstruct coreboot flash map descriptor (.fmd) from nesting of areas.
+    fmd: bool,
+
+    #[arg(long, action,
+          conflicts_with_all = ["extract", "human_readable", "parsable", "flashrom_parsable", "ec_parsable", "full_parsable", "csv", "fmd", "hexdump"])]
+    /// Print FlashMap as dictionary in format of the Python `flashmap` module.
+    py: bool,
+
+    #[arg(long, value_name = "SECTION",
+          conflicts_with_all = ["extract", "human_readable", "parsable", "flashrom_parsable", "ec_parsable"])]
+    /// Print hex and ASCII dump of the section content.
+    hexdump: Option<String>,
+
+    #[arg(long, value_name = "NAME", conflicts_with = "extract")]
+    /// Do not show area with given name. Can be repeated.
+    exclude: Vec<String>,
+
+    #[arg(long, value_name = "FLAG", conflicts_with = "extract", value_parser = fmap::FMapFlags::from_symbolic)]
+    /// Show only areas having given flag, e.g. PRESERVE. Can be repeated, in which case
+    /// areas must have all of the flags.
+    only_flag: Vec<fmap::FMapFlags>,
+
+    #[arg(long, value_enum, conflicts_with_all = ["extract", "human_readable", "hexdump"])]
+    /// Sort areas of flat output formats. Areas are printed in FlashMap order by default.
+    sort: Option<SortKey>,
+
+    #[arg(long, value_name = "N", conflicts_with_all = ["extract", "human_readable", "hexdump"])]
+    /// Skip first N areas of flat output formats. Applied after sorting.
+    skip: Option<usize>,
+
+    #[arg(long, short = 'n', value_name = "N", conflicts_with_all = ["extract", "human_readable", "hexdump"])]
+    /// Print at most N areas in flat output formats. Applied after sorting and skipping.
+    count: Option<usize>,
+
+    #[arg(long, visible_alias = "absolute", action, conflicts_with = "extract")]
+    /// Add FlashMap base to every displayed start and end to show absolute flash addresses.
+    flash_addresses: bool,
+
+    #[arg(long, value_name = "OFFSET", default_value = "0", conflicts_with = "extract",
+          value_parser = common::decimal_or_hex_validator_u64)]
+    /// Search for FlashMap starting at given image offset, skipping everything before it.
+    search_from: u64,
+
+    #[arg(long, value_name = "OFFSET", conflicts_with_all = ["extract", "search_from", "ifd", "max_scan", "align", "stats"],
+          value_parser = common::decimal_or_hex_validator_u64)]
+    /// Read FlashMap at given image offset instead of searching for it.
+    fmap_offset: Option<u64>,
+
+    #[arg(long, action, conflicts_with = "extract")]
+    /// Look for FlashMap in BIOS region described by Intel Flash Descriptor first.
+    /// Whole image is searched if there is no descriptor or FlashMap is not in BIOS region.
+    ifd: bool,
+
+    #[arg(long, action, conflicts_with = "extract")]
+    /// Dump again whenever the image changes, clearing the screen in between if output is
+    /// a terminal. Stop with Ctrl-C.
+    watch: bool,
+
+    #[arg(long, action,
+          conflicts_with_all = ["extract", "human_readable", "parsable", "flashrom_parsable", "ec_parsable", "full_parsable", "csv", "hexdump"])]
+    /// Print SHA-256 of FlashMap header and area entries as stored in the image.
+    self_hash: bool,
+
+    #[arg(long, action,
+          conflicts_with_all = ["extract", "human_readable", "parsable", "flashrom_parsable", "ec_parsable", "full_parsable", "csv", "hexdump", "self_hash", "watch"])]
+    /// Write FlashMap header and area entries exactly as stored in the image to stdout.
+    raw: bool,
+
+    #[arg(long, action, requires = "raw")]
+    /// Write raw FlashMap to stdout even if it is a terminal.
+    force: bool,
+
+    #[arg(long, action,
+          conflicts_with_all = ["extract", "human_readable", "parsable", "flashrom_parsable", "ec_parsable", "full_parsable", "csv", "hexdump", "self_hash", "raw"])]
+    /// Report areas with bounds differing by few bytes from bounds of another area,
+    /// which are likely off by one.
+    lint: bool,
+
+    #[arg(long, action,
+          conflicts_with_all = ["extract", "human_readable", "parsable", "flashrom_parsable", "ec_parsable", "full_parsable", "csv", "hexdump", "self_hash", "raw", "lint"])]
+    /// Print byte ranges shared by overlapping areas and fail if there are any.
+    overlaps: bool,
+
+    #[arg(long, action, requires_all = ["lint", "output"])]
+    /// Snap bounds of areas reported by --lint to the other area and write fixed image to --output.
+    fix: bool,
+
+    #[arg(long, value_hint = ValueHint::FilePath, requires = "fix")]
+    /// Output file path for image fixed with --fix.
+    output: Option<Utf8PathBuf>,
+
+    #[arg(long, action)]
+    /// Print offset of found FlashMap as `# hit at OFFSET` comment before output of
+    /// non-default formats.
+    show_hit: bool,
+
+    #[arg(long, action)]
+    /// Warn if `FMAP` area does not match location and size of the found FlashMap.
+    check_self: bool,
+
+    #[arg(long, action)]
+    /// Warn about area names containing non-printable or non-ASCII bytes, e.g. in corrupted
+    /// images, and print the bytes in hex.
+    check_names: bool,
+
+    #[arg(long, action)]
+    /// Show non-printable and non-ASCII bytes of area names as `\xNN` escapes.
+    ascii_names: bool,
+
+    #[arg(long, action)]
+    /// Fail if more than one FlashMap area has the same name, instead of warning.
+    strict_names: bool,
+
+    #[arg(long, action, conflicts_with = "extract")]
+    /// Fail if any area has flag bits other than STATIC, COMPRESSED, RO and PRESERVE set,
+    /// instead of ignoring its flags.
+    strict_flags: bool,
+
+    #[arg(long, value_name = "SIZE", value_parser = common::decimal_or_hex_validator_u64)]
+    /// Search for FlashMap only within first SIZE bytes of the image.
+    max_scan: Option<u64>,
+
+    #[arg(long, value_name = "N", value_parser = common::alignment_validator)]
+    /// Check only offsets aligned to N bytes, a power of two, when searching for FlashMap.
+    align: Option<usize>,
+
+    #[arg(long, action, conflicts_with = "extract")]
+    /// Print statistics of FlashMap search to stderr.
+    stats: bool,
+
     #[arg(long, action = ArgAction::Help)]
     /// Print help.
     help: Option<bool>,
@@ -65,284 +264,373 @@ pub struct DumpFmapArgs {
     params: Vec<(String, Utf8PathBuf)>,
 }
 
-#[derive(Debug)]
-struct Node {
-    pub name: String,
-    pub offset: usize,
-    pub size: usize,
-    pub aliases: Vec<String>,
-    pub parent: Option<Rc<RefCell<Node>>>,
-    pub children: Vec<Rc<RefCell<Node>>>,
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum SortKey {
+    /// Ascending by offset.
+    Offset,
+    /// Alphabetically by name.
+    Name,
+    /// Ascending by size.
+    Size,
 }
 
-impl Node {
-    pub fn is_duplicate(&self, node: &Node) -> bool {
-        self.offset == node.offset && self.size == node.size
-    }
-
-    pub fn end(&self) -> usize {
-        self.offset + self.size
-    }
-
-    pub fn overlaps(&self, node: &Node) -> bool {
-        (self.offset < node.offset && node.offset < self.end() && self.end() < node.end())
-            || (node.offset < self.offset && self.offset < node.end() && node.end() < self.end())
-    }
+/// Line-oriented output formats described by --dump-schema.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum SchemaFormat {
+    /// Format of --parsable.
+    Parsable,
+    /// Format of --parsable2.
+    Parsable2,
+    /// Format of --flashrom-parsable.
+    Flashrom,
+    /// Format of --ec-parsable.
+    Ec,
+    /// Format of --full-parsable.
+    FullParsable,
+    /// Format of --csv.
+    Csv,
+    /// Format of --ndjson.
+    Ndjson,
+}
 
-    pub fn fits_in(&self, node: &Node) -> bool {
-        self.offset >= node.offset && self.end() <= node.end()
-    }
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum TreeStyle {
+    /// Names indented with spaces.
+    #[default]
+    None,
+    /// Connectors drawn with `|`, `-` and `\``.
+    Ascii,
+    /// Connectors drawn with box-drawing characters.
+    Unicode,
+}
 
-    pub fn parents_number(&self) -> usize {
-        match &self.parent {
-            None => 0,
-            Some(p) => p.borrow().parents_number() + 1,
+impl TreeStyle {
+    /// Strings drawn in front of the name of a node: continuation of an ancestor having
+    /// further children, blank space, connector of a node followed by its siblings and
+    /// connector of the last child.
+    fn connectors(self) -> [&'static str; 4] {
+        match self {
+            TreeStyle::None => ["  ", "  ", "  ", "  "],
+            TreeStyle::Ascii => ["|   ", "    ", "|-- ", "`-- "],
+            TreeStyle::Unicode => ["│   ", "    ", "├── ", "└── "],
         }
     }
 }
 
-impl PartialEq for Node {
-    fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.is_duplicate(other)
-    }
+/// Options of human-readable format.
+#[derive(Default)]
+struct TreeOptions {
+    /// Show space not covered by areas as `[UNUSED]` areas.
+    show_gaps: bool,
+    /// Hide gaps smaller than given size.
+    min_gap: u64,
+    /// Report overlapping areas instead of failing.
+    ignore_overlap: bool,
+    /// Show nesting level as a column instead of indenting names.
+    flat: bool,
+    /// Show areas of the same offset and size as separate rows instead of aliases.
+    no_dedup: bool,
+    /// Hide areas nested deeper than given level. Root is at level 0.
+    max_depth: Option<usize>,
+    /// Show only areas without children.
+    leaves_only: bool,
+    /// Show only the root and its children.
+    top_level_only: bool,
+    /// Characters used to show nesting of areas.
+    tree_style: TreeStyle,
 }
 
 fn dump_human_readable(
     fmap: &fmap::FMap,
-    show_gaps: bool,
-    ignore_overlap: bool,
+    base: u64,
+    opts: &TreeOptions,
     writer: impl Write,
 ) -> Result<(), Box<dyn Error>> {
-    // Convert into nodes.
-    let mut nodes = fmap
-        .areas
+    let issues = fmap.validate();
+    check_overlaps(&issues, fmap.size as u64, opts.ignore_overlap)?;
+
+    let tree = tree::build_tree(fmap, !opts.no_dedup);
+    let gaps = issues
         .iter()
-        .map(|ar| {
-            Rc::new(RefCell::new(Node {
-                name: ar.name.clone(),
-                offset: ar.offset as usize,
-                size: ar.size as usize,
-                aliases: vec![],
-                parent: None,
-                children: vec![],
-            }))
+        .filter_map(|issue| match issue {
+            fmap::LayoutIssue::Gap { start, size } => Some((*start, *size)),
+            _ => None,
         })
-        .collect::<Vec<_>>();
-    nodes.push(Rc::new(RefCell::new(Node {
-        name: String::from("-entire flash-"),
-        offset: fmap.base as usize,
-        size: fmap.size as usize,
-        aliases: vec![],
-        parent: None,
-        children: vec![],
-    })));
-
-    // Sort ascending by offset and descending by size to push larger areas first.
-    nodes.sort_unstable_by_key(|a| {
-        let v = a.borrow();
-        (v.offset, usize::MAX - v.size, v.name.clone())
-    });
+        .collect_vec();
+    let (shown_gaps, small_gaps): (Vec<_>, Vec<_>) = gaps
+        .iter()
+        .partition(|(_, size)| opts.show_gaps && *size >= opts.min_gap);
+    let all_nodes = flatten_tree(&tree, &shown_gaps);
+    drop(tree);
+
+    show(&all_nodes, base, opts, writer)?;
+
+    if !opts.show_gaps && !gaps.is_empty() {
+        warn!("WARNING: Gaps in FlashMap found. Use -H to show them.");
+    } else if opts.show_gaps && !small_gaps.is_empty() {
+        warn!(
+            "WARNING: {} gaps smaller than {:#x} bytes, {:#x} bytes in total, not shown.",
+            small_gaps.len(),
+            opts.min_gap,
+            small_gaps.iter().map(|(_, size)| size).sum::<u64>()
+        );
+    }
+    Ok(())
+}
 
-    // Remove duplicates and find overlaps
-    let mut deduplicated = vec![nodes[0].clone()];
+/// Reports overlapping and out of bounds areas found in layout `issues` of flash of
+/// `flash_size` bytes. Areas crossing the end of flash overlap it, so they are counted as
+/// overlaps. Areas lying wholly beyond flash are only reported, as they do not overlap any
+/// area within flash. Returns error if there are any overlaps, unless `ignore_overlap` is set.
+pub(in crate::cmd) fn check_overlaps(
+    issues: &[fmap::LayoutIssue],
+    flash_size: u64,
+    ignore_overlap: bool,
+) -> Result<(), Box<dyn Error>> {
     let mut overlaps = 0;
-    'dedup_outer: for node in nodes.iter().skip(1) {
-        for d in deduplicated.iter() {
-            let node = node.borrow();
-            let mut d = d.borrow_mut();
-            if node.is_duplicate(&d) {
-                d.aliases.push(node.name.clone());
-                continue 'dedup_outer;
-            } else if node.overlaps(&d) {
+    for issue in issues.iter() {
+        match issue {
+            fmap::LayoutIssue::Overlap { a, b } => error!(
+                r#"Areas "{}" ({:#x} - {:#x}) and "{}" ({:#x} - {:#x}) overlap!"#,
+                a.name,
+                a.offset,
+                a.end(),
+                b.name,
+                b.offset,
+                b.end()
+            ),
+            fmap::LayoutIssue::OutOfBounds { area } if (area.offset as u64) < flash_size => {
                 error!(
-                    r#"Areas "{}" ({:#x} - {:#x}) and "{}" ({:#x} - {:#x}) overlap!"#,
-                    d.name, d.offset, d.end(),
-                    node.name, node.offset, node.end()
+                    r#"Area "{}" ({:#x} - {:#x}) stretches beyond flash!"#,
+                    area.name,
+                    area.offset,
+                    area.end()
+                )
+            }
+            fmap::LayoutIssue::OutOfBounds { area } => {
+                warn!(
+                    r#"Area "{}" ({:#x} - {:#x}) lies beyond flash."#,
+                    area.name,
+                    area.offset,
+                    area.end()
                 );
-                if !ignore_overlap {
-                    overlaps += 1;
-                }
-                continue 'dedup_outer;
+                continue;
             }
+            _ => continue,
+        }
+        if !ignore_overlap {
+            overlaps += 1;
         }
-        // Add first occurrence of entry.
-        deduplicated.push(node.clone());
     }
-    drop(nodes);
 
     if overlaps != 0 {
         return Err(format!("{overlaps} overlapping areas detected. Terminating.").into());
     }
+    Ok(())
+}
 
-    // Skip first as it will (or at leas should) be the root node.
-    for i in 1..deduplicated.len() {
-        let mut node_a = deduplicated[i].borrow_mut();
-        for k in (0..i).rev() {
-            let mut node_b = deduplicated[k].borrow_mut();
-            if node_a.fits_in(&node_b) {
-                node_a.parent = Some(deduplicated[k].clone());
-                node_b.children.push(deduplicated[i].clone());
-                break;
-            }
-        }
+/// Inserts `[UNUSED]` nodes for `gaps` given as (offset, size) pairs into the tree and returns
+/// all nodes to display sorted by offset.
+pub(in crate::cmd) fn flatten_tree(tree: &[NodeRef], gaps: &[(u64, u64)]) -> Vec<NodeRef> {
+    for &(offset, size) in gaps.iter() {
+        let gap = Node {
+            name: "[UNUSED]".to_string(),
+            offset,
+            size,
+            aliases: vec![],
+            parent: None,
+            children: vec![],
+        };
+        // Gap belongs to the deepest node containing it, which is the last one in the tree.
+        let parent = match tree.iter().rev().find(|n| gap.fits_in(&n.borrow())) {
+            Some(v) => v,
+            None => continue,
+        };
+        let gap = Rc::new(RefCell::new(Node {
+            parent: Some(parent.clone()),
+            ..gap
+        }));
+        let mut parent = parent.borrow_mut();
+        let index = parent
+            .children
+            .iter()
+            .position(|c| c.borrow().offset >= offset)
+            .unwrap_or(parent.children.len());
+        parent.children.insert(index, gap);
     }
 
-    // Check for gaps.
-    let mut gap_count = 0;
-    let mut all_nodes = Vec::<Rc<RefCell<Node>>>::new();
-    for node in deduplicated.iter() {
-        let node_ref = node;
-        let mut node = node.borrow_mut();
-        if node.children.is_empty() {
-            // Node with no children should have been already processed.
-            continue;
-        }
-        // Special case for orphan nodes.
-        if node.parent.is_none() {
-            all_nodes.push(node_ref.clone());
-        }
-
-        let node_offset = node.offset;
-        let node_end = node.end();
-
-        // Create new list of children to easily insert gap entries if necessary.
-        let mut new_children = Vec::<Rc<RefCell<Node>>>::new();
-        for i in 0..node.children.len() {
-            let child_offset = node.children[i].borrow().offset;
-            let child_end = node.children[i].borrow().end();
-
-            // First child. Check with parent.
-            if i == 0 && node_offset < child_offset {
-                gap_count += 1;
-                if show_gaps {
-                    new_children.push(Rc::new(RefCell::new(Node {
-                        name: "[UNUSED]".to_string(),
-                        offset: node_offset,
-                        size: child_offset - node_offset,
-                        aliases: vec![],
-                        parent: Some(node_ref.clone()),
-                        children: vec![],
-                    })));
-                }
-            } else if i != 0 {
-                // Non-first child. Check with previous child.
-                let left_child_end = node.children[i - 1].borrow().end();
-
-                if left_child_end < child_offset {
-                    gap_count += 1;
-                    if show_gaps {
-                        new_children.push(Rc::new(RefCell::new(Node {
-                            name: "[UNUSED]".to_string(),
-                            offset: left_child_end,
-                            size: child_offset - left_child_end,
-                            aliases: vec![],
-                            parent: Some(node_ref.clone()),
-                            children: vec![],
-                        })));
-                    }
-                }
-            }
-
-            // Move current child.
-            new_children.push(node.children[i].clone());
-
-            // Handle last child in similar manner as first child.
-            if i == node.children.len() && node_end > child_end {
-                gap_count += 1;
-                if show_gaps {
-                    new_children.push(Rc::new(RefCell::new(Node {
-                        name: "[UNUSED]".to_string(),
-                        offset: node_end,
-                        size: node_end - child_end,
-                        aliases: vec![],
-                        parent: Some(node_ref.clone()),
-                        children: vec![],
-                    })));
-                }
-            }
+    let mut all_nodes = Vec::<NodeRef>::new();
+    for node in tree.iter() {
+        let n = node.borrow();
+        // Orphan nodes are displayed only if they have children. Root is an orphan too.
+        if n.parent.is_none() && !n.children.is_empty() {
+            all_nodes.push(node.clone());
         }
-
-        // Replace node children with another one.
-        // NOTE: Maybe unnecessary at this point, but let's keep structure valid from both ends.
-        node.children.clear();
-        node.children.append(&mut new_children);
-
-        all_nodes.append(&mut node.children.iter().cloned().collect_vec());
+        all_nodes.append(&mut n.children.iter().cloned().collect_vec());
     }
 
-    drop(deduplicated);
-
+    // Depth keeps parents before children of the same geometry (e.g. the root node).
     all_nodes.sort_unstable_by_key(|a| {
         let v = a.borrow();
-        (v.offset, usize::MAX - v.size, v.name.clone())
+        (
+            v.offset,
+            u64::MAX - v.size,
+            v.parents_number(),
+            v.name.clone(),
+        )
     });
 
-    show(&all_nodes, writer)?;
+    all_nodes
+}
 
-    if !show_gaps && gap_count > 0 {
-        warn!("WARNING: Gaps in FlashMap found. Use -H to show them.");
+/// Number of nodes nested in `node` at any depth.
+fn descendants_number(node: &Node) -> usize {
+    node.children
+        .iter()
+        .map(|c| 1 + descendants_number(&c.borrow()))
+        .sum()
+}
+
+/// Computes prefixes drawn in front of names of nodes at nesting `levels` listed in display
+/// order, so that each node is connected to the following siblings and to its parent.
+/// Returns indentation of each node and whether it is the last child of its parent.
+fn tree_prefixes(levels: &[usize], style: TreeStyle) -> Vec<(String, bool)> {
+    let [pipe, blank, _, _] = style.connectors();
+    // Levels at which a sibling of the node or of its ancestor is still to be displayed.
+    let mut open = Vec::<bool>::new();
+    let mut prefixes = vec![(String::new(), true); levels.len()];
+    for (i, &level) in levels.iter().enumerate().rev() {
+        let indent = (1..level)
+            .map(|d| match open.get(d) {
+                Some(true) => pipe,
+                _ => blank,
+            })
+            .collect::<String>();
+        prefixes[i] = (indent, !open.get(level).copied().unwrap_or(false));
+        open.resize(level + 1, false);
+        open[level] = true;
     }
-    Ok(())
+    prefixes
 }
 
-fn show(nodes: &[Rc<RefCell<Node>>], mut writer: impl Write) -> Result<(), Box<dyn Error>> {
+/// Prints table of `nodes` with addresses in flash placed at `base`. Nodes nested deeper than
+/// `max_depth` are hidden and their number is shown next to their ancestor at `max_depth`
+/// level as `(+K more)`.
+fn show(
+    nodes: &[Rc<RefCell<Node>>],
+    base: u64,
+    opts: &TreeOptions,
+    mut writer: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let (flat, max_depth) = (opts.flat, opts.max_depth);
+    let nodes = nodes
+        .iter()
+        .filter(|node| {
+            let n = node.borrow();
+            let level = n.parents_number();
+            max_depth.is_none_or(|depth| level <= depth)
+                && (!opts.leaves_only || n.children.is_empty())
+                && (!opts.top_level_only || level <= 1)
+        })
+        .collect_vec();
+    let levels = nodes
+        .iter()
+        .map(|node| node.borrow().parents_number())
+        .collect_vec();
+    let prefixes = tree_prefixes(&levels, opts.tree_style);
+    let [_, _, tee, corner] = opts.tree_style.connectors();
+    // Level is shown either as a column or as indentation of the name. Aliases are connected
+    // as siblings following the node, the last of them taking its place in the tree.
+    let label = |i: usize, name: &str, last: bool| {
+        let (level, (indent, _)) = (levels[i], &prefixes[i]);
+        if flat {
+            format!("{level: <8}{name}")
+        } else if level == 0 || opts.leaves_only {
+            // Leaves are listed without their ancestors, so nesting is not shown.
+            name.to_string()
+        } else {
+            format!("{indent}{}{name}", if last { corner } else { tee })
+        }
+    };
+    let header = if flat { "# level name" } else { "# name" };
+    // Name column is as wide as the longest label, so that following columns line up.
+    let width = nodes
+        .iter()
+        .enumerate()
+        .flat_map(|(i, node)| {
+            let n = node.borrow();
+            std::iter::once(label(i, &n.name, true).chars().count())
+                .chain(
+                    n.aliases
+                        .iter()
+                        .map(|alias| label(i, alias, true).chars().count()),
+                )
+                .collect_vec()
+        })
+        .fold(header.len(), usize::max);
+    // Hex columns have at least 8 digits, more if addresses do not fit in them.
+    let digits = nodes
+        .iter()
+        .map(|node| {
+            let n = node.borrow();
+            let (start, end) = (base.wrapping_add(n.offset), base.wrapping_add(n.end()));
+            format!("{:x}", start.max(end)).len()
+        })
+        .fold(8, usize::max);
+    let widths = (width, digits);
+
     writeln!(
         writer,
-        "# name                     start       end         size"
+        "{header: <width$}  {:<digits$}    {:<digits$}    size",
+        "start", "end"
     )?;
-    for node in nodes.iter() {
-        let (node_level, node_name, node_offset, node_end, node_size) = {
-            let n = node.borrow();
-            (
-                n.parents_number(),
-                n.name.clone(),
-                n.offset,
-                n.end(),
-                n.size,
-            )
+    for (i, node) in nodes.iter().enumerate() {
+        let n = node.borrow();
+        let last = prefixes[i].1;
+        let hidden = match max_depth {
+            Some(depth) if levels[i] == depth => descendants_number(&n),
+            _ => 0,
         };
         show_line(
-            node_level,
-            &node_name,
-            node_offset,
-            node_end,
-            node_size,
+            &label(i, &n.name, last && n.aliases.is_empty()),
+            widths,
+            base.wrapping_add(n.offset),
+            base.wrapping_add(n.end()),
+            n.size,
             &mut writer,
-            &"".to_string(),
+            &match hidden {
+                0 => String::new(),
+                k => format!("  (+{k} more)"),
+            },
         )?;
-        for alias in node.borrow().aliases.iter() {
+        for (k, alias) in n.aliases.iter().enumerate() {
             show_line(
-                node_level,
-                alias,
-                node_offset,
-                node_end,
-                node_size,
+                &label(i, alias, last && k + 1 == n.aliases.len()),
+                widths,
+                base.wrapping_add(n.offset),
+                base.wrapping_add(n.end()),
+                n.size,
                 &mut writer,
-                &"  // DUPLICATE".to_string(),
+                "  // DUPLICATE",
             )?;
         }
     }
     Ok(())
 }
 
+/// Prints single row of the table. `widths` are widths of the name column and of hex columns.
 fn show_line(
-    level: usize,
-    name: &String,
-    offset: usize,
-    end: usize,
-    size: usize,
+    label: &str,
+    (width, digits): (usize, usize),
+    offset: u64,
+    end: u64,
+    size: u64,
     mut writer: impl Write,
-    suffix: &String,
+    suffix: &str,
 ) -> Result<(), Box<dyn Error>> {
     match writeln!(
         writer,
-        "{}{: <25}  {:08x}    {:08x}    {:08x}{}",
-        "  ".repeat(level),
-        name,
-        offset,
-        end,
-        size,
-        suffix
+        "{label: <width$}  {offset:0digits$x}    {end:0digits$x}    {size:0digits$x}{suffix}"
     ) {
         Ok(()) => Ok(()),
         Err(e) => Err(e.into()),
@@ -351,52 +639,98 @@ fn show_line(
 
 fn dump_default(fmap: &fmap::FMap, offset: usize, mut writer: impl Write) -> std::io::Result<()> {
     writeln!(writer, "hit at {offset:#x}")?;
-    writeln!(writer, "fmap_signature:  __FMAP__")?; // Original futility has no colon here
-    writeln!(
-        writer,
-        "fmap_version:    {}.{}",
-        fmap.version_major, fmap.version_minor
-    )?;
-    writeln!(writer, "fmap_base:       {:#x}", fmap.base)?;
-    writeln!(writer, "fmap_size:       {0:#x} ({0})", fmap.size)?;
-    writeln!(writer, "fmap_name:       {}", fmap.name)?;
-    writeln!(writer, "fmap_nareas:     {}", fmap.areas.len())?;
-    for (i, area) in fmap.areas.iter().enumerate() {
-        writeln!(writer, "area:            {}", i + 1)?;
-        writeln!(writer, "area_offset:     {:#x}", area.offset)?;
-        writeln!(writer, "area_size:       {0:#x} ({0})", area.size)?;
-        writeln!(writer, "area_name:       {}", area.name)?;
+    write!(writer, "{fmap}")
+}
+
+/// Keeps only areas having all of the `flags`.
+fn only_flag_areas(fmap: &mut fmap::FMap, flags: &[fmap::FMapFlags]) {
+    if flags.is_empty() {
+        return;
+    }
+    let flags = flags
+        .iter()
+        .fold(fmap::FMapFlags::empty(), |acc, f| acc | *f);
+    fmap.areas = fmap.areas_with_flag(flags).into_iter().cloned().collect();
+}
+
+/// Removes areas with names listed in `exclude`.
+fn exclude_areas(areas: &mut Vec<fmap::FMapArea>, exclude: &[String]) {
+    areas.retain(|a| !exclude.contains(&a.name));
+}
+
+/// Sorts `areas` by `key`. Order of areas with equal keys is preserved.
+fn sort_areas(areas: &mut [fmap::FMapArea], key: SortKey) {
+    match key {
+        SortKey::Offset => areas.sort_by_key(|a| a.offset),
+        SortKey::Name => areas.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortKey::Size => areas.sort_by_key(|a| a.size),
+    }
+}
+
+/// Leaves only `count` areas following first `skip` ones.
+fn window_areas(areas: &mut Vec<fmap::FMapArea>, skip: Option<usize>, count: Option<usize>) {
+    areas.drain(..skip.unwrap_or(0).min(areas.len()));
+    if let Some(count) = count {
+        areas.truncate(count);
+    }
+}
+
+fn dump_parsable(fmap: &fmap::FMap, base: u64, mut writer: impl Write) -> std::io::Result<()> {
+    for area in fmap.areas.iter() {
+        writeln!(writer, "{} {} {}", area.name, area.address(base), area.size)?;
     }
     Ok(())
 }
 
-fn dump_parsable(fmap: &fmap::FMap, mut writer: impl Write) -> std::io::Result<()> {
+fn dump_parsable2(fmap: &fmap::FMap, base: u64, mut writer: impl Write) -> std::io::Result<()> {
     for area in fmap.areas.iter() {
-        writeln!(writer, "{} {} {}", area.name, area.offset, area.size)?;
+        let last = match area.last_address(base) {
+            Some(v) => v.to_string(),
+            None => String::from("-"),
+        };
+        writeln!(
+            writer,
+            "{} {} {} {}",
+            area.name,
+            area.address(base),
+            area.size,
+            last
+        )?;
     }
     Ok(())
 }
 
-fn dump_flashrom_parsable(fmap: &fmap::FMap, mut writer: impl Write) -> std::io::Result<()> {
+fn dump_flashrom_parsable(
+    fmap: &fmap::FMap,
+    base: u64,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
     for area in fmap.areas.iter() {
+        let last = match area.last_address(base) {
+            Some(v) => v,
+            None => {
+                warn!("Area '{}' has zero size, skipping", area.name);
+                continue;
+            }
+        };
         writeln!(
             writer,
             "{:#08x}:{:#08x} {}",
-            area.offset,
-            (area.offset + area.size - 1),
+            area.address(base),
+            last,
             area.name
         )?;
     }
     Ok(())
 }
 
-fn dump_ec_parsable(fmap: &fmap::FMap, mut writer: impl Write) -> std::io::Result<()> {
+fn dump_ec_parsable(fmap: &fmap::FMap, base: u64, mut writer: impl Write) -> std::io::Result<()> {
     for area in fmap.areas.iter() {
         writeln!(
             writer,
             "{} {} {} {}",
             area.name,
-            area.offset,
+            area.address(base),
             area.size,
             if area.flags.contains(fmap::FMapFlags::Preserve) {
                 "preserve"
@@ -408,44 +742,776 @@ fn dump_ec_parsable(fmap: &fmap::FMap, mut writer: impl Write) -> std::io::Resul
     Ok(())
 }
 
-pub fn run_command(args: &DumpFmapArgs) -> Result<(), Box<dyn Error>> {
-    if args.extract {
-        let extract_args = extract_fmap::ExtractFmapArgs {
-            image: args.image.clone(),
-            params: args.params.clone(),
+fn dump_full_parsable(fmap: &fmap::FMap, base: u64, mut writer: impl Write) -> std::io::Result<()> {
+    for area in fmap.areas.iter() {
+        let flags = area.flags.names();
+        writeln!(
+            writer,
+            "{} {} {} {}",
+            area.name,
+            area.address(base),
+            area.size,
+            if flags.is_empty() {
+                String::from("NONE")
+            } else {
+                flags.join(",")
+            }
+        )?;
+    }
+    Ok(())
+}
+
+fn dump_csv(fmap: &fmap::FMap, base: u64, mut writer: impl Write) -> std::io::Result<()> {
+    writeln!(writer, "name,start,end,size,flags")?;
+    for area in fmap.areas.iter() {
+        let name = if area.name.contains(&[',', '"', '\n', '\r'][..]) {
+            format!("\"{}\"", area.name.replace('"', "\"\""))
+        } else {
+            area.name.clone()
         };
-        return extract_fmap::run_command(&extract_args);
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            name,
+            area.address(base),
+            area.end_address(base),
+            area.size,
+            area.flags.names().join(";")
+        )?;
     }
+    Ok(())
+}
 
-    let mut input_file = File::open(&args.image)?;
-    let (fmap, fmap_offset) = fmap::FMap::find_fmap(&mut input_file)?;
+/// Prints FlashMap header and then each area as JSON object in separate line, with keys
+/// sorted, e.g.
+///
+/// `{"base":0,"name":"FMAP","nareas":1,"size":1024,"type":"fmap","version_major":1,"version_minor":1}`
+/// `{"flags":["STATIC","RO"],"name":"RO","offset":0,"size":128,"type":"area"}`
+fn dump_ndjson(fmap: &fmap::FMap, base: u64, mut writer: impl Write) -> std::io::Result<()> {
+    let header = serde_json::json!({
+        "type": "fmap",
+        "name": fmap.name,
+        "version_major": fmap.version_major,
+        "version_minor": fmap.version_minor,
+        "base": fmap.base,
+        "size": fmap.size,
+        "nareas": fmap.areas.len(),
+    });
+    writeln!(writer, "{header}")?;
+    for area in fmap.areas.iter() {
+        let area = serde_json::json!({
+            "type": "area",
+            "name": area.name,
+            "offset": area.address(base),
+            "size": area.size,
+            "flags": area.flags.names(),
+        });
+        writeln!(writer, "{area}")?;
+    }
+    Ok(())
+}
 
-    if args.human_readable {
-        dump_human_readable(
-            &fmap,
-            args.human_readable_with_gaps,
-            args.ignore_overlapping_sections,
-            &mut stdout(),
+/// Describes lines of output `format`: template of a line, whether there is a header row
+/// and names and types of columns. Integers are given with their radix. Lines of
+/// `SchemaFormat::Ndjson` are JSON objects, described with their fields instead of columns.
+pub fn output_schema(format: SchemaFormat) -> serde_json::Value {
+    let string = |name: &str| serde_json::json!({ "name": name, "type": "string" });
+    let integer = |name: &str, radix: u32| serde_json::json!({ "name": name, "type": "integer", "radix": radix });
+    let flags = |separator: &str, empty: &str| {
+        serde_json::json!({
+            "name": "flags",
+            "type": "flags",
+            "separator": separator,
+            "empty": empty,
+            "values": fmap::FMapFlags::all().names(),
+        })
+    };
+    let (line, header, columns) = match format {
+        SchemaFormat::Parsable => (
+            "<name> <offset> <size>",
+            false,
+            vec![string("name"), integer("offset", 10), integer("size", 10)],
+        ),
+        SchemaFormat::Parsable2 => (
+            "<name> <offset> <size> <end>",
+            false,
+            vec![
+                string("name"),
+                integer("offset", 10),
+                integer("size", 10),
+                serde_json::json!({ "name": "end", "type": "integer", "radix": 10, "empty": "-" }),
+            ],
+        ),
+        SchemaFormat::Flashrom => (
+            "<start>:<end> <name>",
+            false,
+            vec![integer("start", 16), integer("end", 16), string("name")],
+        ),
+        SchemaFormat::Ec => (
+            "<name> <offset> <size> <preserve>",
+            false,
+            vec![
+                string("name"),
+                integer("offset", 10),
+                integer("size", 10),
+                serde_json::json!({
+                    "name": "preserve",
+                    "type": "enum",
+                    "values": ["preserve", "not-preserve"],
+                }),
+            ],
+        ),
+        SchemaFormat::FullParsable => (
+            "<name> <offset> <size> <flags>",
+            false,
+            vec![
+                string("name"),
+                integer("offset", 10),
+                integer("size", 10),
+                flags(",", "NONE"),
+            ],
+        ),
+        SchemaFormat::Csv => (
+            "<name>,<start>,<end>,<size>,<flags>",
+            true,
+            vec![
+                string("name"),
+                integer("start", 10),
+                integer("end", 10),
+                integer("size", 10),
+                flags(";", ""),
+            ],
+        ),
+        SchemaFormat::Ndjson => return ndjson_schema(),
+    };
+    serde_json::json!({
+        "format": format.to_possible_value().map(|v| v.get_name().to_string()),
+        "line": line,
+        "header": header,
+        "columns": columns,
+    })
+}
+
+/// Describes objects printed by `dump_ndjson`: header object of type `fmap` in the first line
+/// followed by object of type `area` for each area.
+fn ndjson_schema() -> serde_json::Value {
+    let field = |name: &str, kind: &str| serde_json::json!({ "name": name, "type": kind });
+    let kind =
+        |value: &str| serde_json::json!({ "name": "type", "type": "string", "value": value });
+    serde_json::json!({
+        "format": "ndjson",
+        "line": "<JSON object>",
+        "header": true,
+        "objects": [
+            {
+                "type": "fmap",
+                "fields": [
+                    kind("fmap"),
+                    field("name", "string"),
+                    field("version_major", "integer"),
+                    field("version_minor", "integer"),
+                    field("base", "integer"),
+                    field("size", "integer"),
+                    field("nareas", "integer"),
+                ],
+            },
+            {
+                "type": "area",
+                "fields": [
+                    kind("area"),
+                    field("name", "string"),
+                    field("offset", "integer"),
+                    field("size", "integer"),
+                    {
+                        "name": "flags",
+                        "type": "array",
+                        "items": "string",
+                        "values": fmap::FMapFlags::all().names(),
+                    },
+                ],
+            },
+        ],
+    })
+}
+
+/// Quotes `s` as Python string literal, as `repr()` of `str` does for printable strings.
+/// Double quotes are used only if `s` contains single quotes and no double quotes.
+fn py_str(s: &str) -> String {
+    let s = s.replace('\\', "\\\\");
+    if s.contains('\'') && !s.contains('"') {
+        format!("\"{s}\"")
+    } else {
+        format!("'{}'", s.replace('\'', "\\'"))
+    }
+}
+
+/// Prints FlashMap as `repr()` of the dictionary returned by `fmap_decode()` of the Python
+/// `flashmap` module, in a single line. Keys keep the order of that module:
+///
+/// `{'signature': b'__FMAP__', 'ver_major': 1, 'ver_minor': 1, 'base': 0, 'size': 1024,
+/// 'name': 'FMAP', 'nareas': 1, 'areas': [{'offset': 0, 'size': 128, 'name': 'RO',
+/// 'flags': 5, 'FLAGS': ('FMAP_AREA_STATIC', 'FMAP_AREA_RO')}]}`
+///
+/// Numbers are decimal, offsets are relative to the flash start and `FLAGS` is a tuple of
+/// names of set flags, with trailing comma for a single flag, as Python prints it.
+fn dump_py(fmap: &fmap::FMap, mut writer: impl Write) -> std::io::Result<()> {
+    let areas = fmap
+        .areas
+        .iter()
+        .map(|area| {
+            let flags = area
+                .flags
+                .names()
+                .iter()
+                .map(|flag| py_str(&format!("FMAP_AREA_{flag}")))
+                .collect_vec();
+            let flags = match flags.len() {
+                1 => format!("({},)", flags[0]),
+                _ => format!("({})", flags.join(", ")),
+            };
+            format!(
+                "{{'offset': {}, 'size': {}, 'name': {}, 'flags': {}, 'FLAGS': {}}}",
+                area.offset,
+                area.size,
+                py_str(&area.name),
+                area.flags.bits(),
+                flags
+            )
+        })
+        .join(", ");
+    writeln!(
+        writer,
+        "{{'signature': b'__FMAP__', 'ver_major': {}, 'ver_minor': {}, 'base': {}, 'size': {}, \
+         'name': {}, 'nareas': {}, 'areas': [{}]}}",
+        fmap.version_major,
+        fmap.version_minor,
+        fmap.base,
+        fmap.size,
+        py_str(&fmap.name),
+        fmap.areas.len(),
+        areas
+    )
+}
+
+/// Bytes of names of `nareas` areas of FlashMap stored as `raw`, up to the first NUL.
+fn raw_area_names(raw: &[u8], nareas: usize) -> Vec<&[u8]> {
+    (0..nareas)
+        .map(|i| {
+            let start = fmap::HEADER_SIZE + i * fmap::AREA_SIZE + 8;
+            let name = &raw[start..start + fmap::NAME_LEN];
+            let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+            &name[..len]
+        })
+        .collect()
+}
+
+fn is_printable_name(name: &[u8]) -> bool {
+    name.iter().all(|b| (0x20..=0x7e).contains(b))
+}
+
+/// Escapes non-printable and non-ASCII bytes of `name` as `\xNN`.
+fn escape_name(name: &[u8]) -> String {
+    name.iter()
+        .map(|&b| match b {
+            b'\\' => String::from("\\\\"),
+            0x20..=0x7e => char::from(b).to_string(),
+            _ => format!("\\x{b:02x}"),
+        })
+        .collect()
+}
+
+/// Verifies that areas named `FMAP` point at FlashMap found at `fmap_offset`
+/// and are large enough to hold it. Returns list of found problems.
+fn check_self(fmap: &fmap::FMap, fmap_offset: usize) -> Vec<String> {
+    let required_size = fmap::HEADER_SIZE + fmap.areas.len() * fmap::AREA_SIZE;
+    let mut problems = Vec::new();
+    for area in fmap.areas.iter().filter(|a| a.name == "FMAP") {
+        if area.offset as usize != fmap_offset {
+            problems.push(format!(
+                "Area 'FMAP' offset {:#x} does not match FlashMap found at {:#x}",
+                area.offset, fmap_offset
+            ));
+        }
+        if (area.size as usize) < required_size {
+            problems.push(format!(
+                "Area 'FMAP' size {:#x} is smaller than FlashMap size {:#x}",
+                area.size, required_size
+            ));
+        }
+    }
+    problems
+}
+
+/// Prints content of area `area_name` in canonical hex+ASCII format, 16 bytes per row.
+fn dump_hexdump(
+    reader: &mut (impl Read + Seek),
+    fmap: &fmap::FMap,
+    area_name: &str,
+    base: u64,
+    mut writer: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let area = match fmap.get(area_name) {
+        Some(v) => v,
+        None => return Err(fmap::FMapError::AreaNotFound(area_name.to_string()).into()),
+    };
+    if area.size == 0 {
+        warn!("Area '{}' has zero size", area_name);
+        return Ok(());
+    }
+
+    let mut data = vec![0u8; area.size as usize];
+    reader.seek(SeekFrom::Start(area.offset as u64))?;
+    reader.read_exact(&mut data)?;
+
+    for (i, row) in data.chunks(16).enumerate() {
+        let hex = (0..16)
+            .map(|k| match row.get(k) {
+                Some(b) => format!("{b:02x}"),
+                None => String::from("  "),
+            })
+            .collect_vec();
+        let ascii: String = row
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        writeln!(
+            writer,
+            "{:08x}  {}  {}  |{}|",
+            area.address(base).wrapping_add(i as u64 * 16),
+            hex[..8].join(" "),
+            hex[8..].join(" "),
+            ascii
         )?;
-    } else if args.parsable {
-        dump_parsable(&fmap, &mut stdout())?;
-    } else if args.flashrom_parsable {
-        dump_flashrom_parsable(&fmap, &mut stdout())?;
-    } else if args.ec_parsable {
-        dump_ec_parsable(&fmap, &mut stdout())?;
+    }
+    Ok(())
+}
+
+/// Writes `node` and its children in .fmd syntax. Offsets are relative to the parent.
+fn write_fmd_node(
+    fmap: &fmap::FMap,
+    node: &Node,
+    parent_offset: u64,
+    level: usize,
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    let indent = "\t".repeat(level);
+    let flags = match fmap.get(&node.name) {
+        Some(ar) if ar.flags.contains(fmap::FMapFlags::Preserve) => "(PRESERVE)",
+        _ => "",
+    };
+    write!(
+        writer,
+        "{indent}{}{flags}@{:#x} {:#x}",
+        node.name,
+        node.offset - parent_offset,
+        node.size
+    )?;
+    if node.children.is_empty() {
+        writeln!(writer)?;
     } else {
-        dump_default(&fmap, fmap_offset, &mut stdout())?;
+        writeln!(writer, " {{")?;
+        for child in node.children.iter() {
+            write_fmd_node(fmap, &child.borrow(), node.offset, level + 1, writer)?;
+        }
+        writeln!(writer, "{indent}}}")?;
     }
+    // Descriptor cannot contain two areas with the same placement.
+    for alias in node.aliases.iter() {
+        writeln!(writer, "{indent}# {alias} duplicates {}", node.name)?;
+    }
+    Ok(())
+}
 
+/// Reconstructs coreboot .fmd descriptor. Fails on overlapping areas, which cannot be nested.
+fn dump_fmd(fmap: &fmap::FMap, mut writer: impl Write) -> Result<(), Box<dyn Error>> {
+    check_overlaps(&fmap.validate(), fmap.size as u64, false)?;
+    let tree = tree::build_tree(fmap, true);
+    let root = tree[0].borrow();
+    writeln!(writer, "FLASH@{:#x} {:#x} {{", fmap.base, root.size)?;
+    for child in root.children.iter() {
+        write_fmd_node(fmap, &child.borrow(), root.offset, 1, &mut writer)?;
+    }
+    writeln!(writer, "}}")?;
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Computes SHA-256 over FMap header and all area entries found at `fmap_offset`.
+fn self_hash(
+    reader: &mut (impl Read + Seek),
+    fmap: &fmap::FMap,
+    fmap_offset: usize,
+) -> std::io::Result<String> {
+    let data = raw_fmap(reader, fmap, fmap_offset)?;
+    Ok(common::hex(&Sha256::digest(data)))
+}
 
-    fn init() {
-        let _ = env_logger::builder().is_test(true).try_init();
+/// FlashMap header and area entries as stored in the image at `fmap_offset`.
+fn raw_fmap(
+    reader: &mut (impl Read + Seek),
+    fmap: &fmap::FMap,
+    fmap_offset: usize,
+) -> std::io::Result<Vec<u8>> {
+    let size = fmap::HEADER_SIZE + fmap.areas.len() * fmap::AREA_SIZE;
+    let mut data = vec![0u8; size];
+    reader.seek(SeekFrom::Start(fmap_offset as u64))?;
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Fails if any area of FlashMap stored as `raw` bytes has unknown flag bits set.
+fn check_flags(raw: &[u8]) -> Result<(), fmap::FMapError> {
+    let opts = fmap::ParseOptions {
+        strict_flags: true,
+        ..Default::default()
+    };
+    fmap::FMap::parse_fmap_with_opts(&mut Cursor::new(raw), &opts)?;
+    Ok(())
+}
+
+/// Largest difference between bounds of two areas considered a mistake rather than intent.
+const LINT_MAX_DELTA: u64 = 8;
+
+/// Finds areas whose start and end differ from those of another area by at most
+/// LINT_MAX_DELTA bytes, but not both by zero. Returns pairs of indices of the suspicious area
+/// and the area it most likely should match, which is the one coming first in sorted order.
+fn lint_areas(fmap: &fmap::FMap) -> Vec<(usize, usize)> {
+    let index = |area: &fmap::FMapArea| {
+        fmap.areas
+            .iter()
+            .position(|a| std::ptr::eq(a, area))
+            .expect("sorted area should belong to FlashMap")
+    };
+    let sorted = fmap.sorted_areas();
+    let mut findings: Vec<(usize, usize)> = Vec::new();
+    for (i, reference) in sorted.iter().enumerate() {
+        for area in sorted[i + 1..].iter() {
+            if area.offset as u64 > reference.offset as u64 + LINT_MAX_DELTA {
+                break;
+            }
+            let same = area.offset == reference.offset && area.size == reference.size;
+            if same || area.end().abs_diff(reference.end()) > LINT_MAX_DELTA {
+                continue;
+            }
+            let (suspect, reference) = (index(area), index(reference));
+            if !findings
+                .iter()
+                .any(|(s, _)| [suspect, reference].contains(s))
+            {
+                findings.push((suspect, reference));
+            }
+        }
+    }
+    findings
+}
+
+/// Reports findings of `lint_areas` and, if `output` is given, writes copy of the image with
+/// FlashMap having suspicious areas snapped to their references. Only offsets and sizes of
+/// snapped area entries are rewritten, so names and flags are kept as stored in the image.
+fn lint(
+    reader: &mut (impl Read + Seek),
+    fmap: &fmap::FMap,
+    fmap_offset: usize,
+    output: Option<&Utf8Path>,
+) -> Result<(), Box<dyn Error>> {
+    let findings = lint_areas(fmap);
+    for &(suspect, reference) in findings.iter() {
+        let (a, b) = (&fmap.areas[suspect], &fmap.areas[reference]);
+        warn!(
+            r#"Area "{}" ({:#x} - {:#x}) almost matches area "{}" ({:#x} - {:#x}), likely off by one"#,
+            a.name,
+            a.offset,
+            a.end(),
+            b.name,
+            b.offset,
+            b.end()
+        );
+    }
+    if findings.is_empty() {
+        info!("No suspicious areas found");
+    }
+
+    let path = match output {
+        None => return Ok(()),
+        Some(v) => v,
+    };
+    let mut data = Vec::new();
+    reader.seek(SeekFrom::Start(0))?;
+    reader.read_to_end(&mut data)?;
+    for &(suspect, reference) in findings.iter() {
+        let reference = &fmap.areas[reference];
+        let entry = fmap_offset + fmap::HEADER_SIZE + suspect * fmap::AREA_SIZE;
+        data[entry..entry + 4].copy_from_slice(&reference.offset.to_le_bytes());
+        data[entry + 4..entry + 8].copy_from_slice(&reference.size.to_le_bytes());
+    }
+    std::fs::write(path, data)?;
+    info!("Fixed {} areas, written to `{path}'", findings.len());
+    Ok(())
+}
+
+/// Byte ranges shared by partially overlapping areas reported in layout `issues`.
+/// Returns the areas with start and exclusive end of the shared range.
+fn overlap_ranges(
+    issues: &[fmap::LayoutIssue],
+) -> Vec<(&fmap::FMapArea, &fmap::FMapArea, u64, u64)> {
+    issues
+        .iter()
+        .filter_map(|issue| match issue {
+            fmap::LayoutIssue::Overlap { a, b } => {
+                Some((a, b, a.offset.max(b.offset) as u64, a.end().min(b.end())))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Prints ranges shared by overlapping areas. Fails if there are any.
+fn dump_overlaps(
+    fmap: &fmap::FMap,
+    base: u64,
+    mut writer: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let issues = fmap.validate();
+    let overlaps = overlap_ranges(&issues);
+    for &(a, b, start, end) in overlaps.iter() {
+        writeln!(
+            writer,
+            r#""{}" and "{}" overlap at {:#x} - {:#x} ({:#x} bytes)"#,
+            a.name,
+            b.name,
+            base.wrapping_add(start),
+            base.wrapping_add(end),
+            end - start
+        )?;
+    }
+    if !overlaps.is_empty() {
+        return Err(format!("{} overlapping areas detected", overlaps.len()).into());
+    }
+    Ok(())
+}
+
+/// Interval of checking whether watched image changed.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Modification time and size of file at `path`, used to detect its changes.
+fn file_stamp(path: &Utf8Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Waits until stamp of file at `path` differs from `last` and then stays the same for
+/// one more interval, so the image is not read while still being written. Returns new stamp.
+fn wait_for_change(path: &Utf8Path, last: Option<(SystemTime, u64)>) -> Option<(SystemTime, u64)> {
+    let mut current = last;
+    loop {
+        sleep(WATCH_INTERVAL);
+        let next = file_stamp(path);
+        if next == current && current != last {
+            return current;
+        }
+        current = next;
+    }
+}
+
+fn watch(args: &DumpFmapArgs) -> Result<(), Box<dyn Error>> {
+    let mut stamp = file_stamp(&args.image);
+    // Escape codes would only clutter output redirected to a file or pipe.
+    let clear_screen = stdout().is_terminal();
+    loop {
+        if clear_screen {
+            // Clear screen and move cursor to the top left corner.
+            print!("\x1b[2J\x1b[H");
+        }
+        if let Err(e) = dump(args, stdout()) {
+            error!("{e}");
+        }
+        stdout().flush()?;
+        stamp = wait_for_change(&args.image, stamp);
+    }
+}
+
+pub fn run_command(args: &DumpFmapArgs) -> Result<(), Box<dyn Error>> {
+    if args.extract {
+        let extract_args = extract_fmap::ExtractFmapArgs {
+            image: args.image.clone(),
+            params: args.params.clone(),
+            strict_names: args.strict_names,
+            max_scan: args.max_scan,
+            align: args.align,
+            ..Default::default()
+        };
+        return extract_fmap::run_command(&extract_args);
+    }
+    if args.watch {
+        return watch(args);
+    }
+    if args.raw && !args.force && stdout().is_terminal() {
+        return Err("Refusing to write binary data to terminal. Use --force to override".into());
+    }
+    dump(args, stdout())
+}
+
+fn dump(args: &DumpFmapArgs, mut writer: impl Write) -> Result<(), Box<dyn Error>> {
+    let mut input_file = image::open(&args.image)?;
+    let search_opts = fmap::SearchOptions {
+        start: args.search_from as usize,
+        max_scan: args.max_scan,
+        min_align: args.align,
+    };
+    let found = if let Some(offset) = args.fmap_offset {
+        let offset = offset as usize;
+        Some((fmap::FMap::parse_fmap_at(&mut input_file, offset)?, offset))
+    } else if args.ifd {
+        ifd::find_fmap_in_bios_region(&mut input_file, &search_opts)?
+    } else {
+        None
+    };
+    let (mut fmap, fmap_offset) = match found {
+        Some(v) => {
+            if args.stats {
+                eprintln!("FlashMap found in BIOS region, image not scanned");
+            }
+            v
+        }
+        None => {
+            let (fmap, offset, stats) =
+                fmap::FMap::find_fmap_with_stats(&mut input_file, &search_opts)?;
+            if args.stats {
+                eprintln!(
+                    "Scanned {} bytes, checked {} candidates, found at alignment {:#x}",
+                    stats.bytes_scanned, stats.candidates, stats.alignment
+                );
+            }
+            (fmap, offset)
+        }
+    };
+    common::check_duplicate_names(&fmap, args.strict_names)?;
+    if args.strict_flags {
+        check_flags(&raw_fmap(&mut input_file, &fmap, fmap_offset)?)?;
+    }
+    let base = if args.flash_addresses { fmap.base } else { 0 };
+
+    if args.self_hash {
+        writeln!(
+            writer,
+            "{}",
+            self_hash(&mut input_file, &fmap, fmap_offset)?
+        )?;
+        return Ok(());
+    }
+    if args.raw {
+        writer.write_all(&raw_fmap(&mut input_file, &fmap, fmap_offset)?)?;
+        return Ok(());
+    }
+    if args.overlaps {
+        return dump_overlaps(&fmap, base, &mut writer);
+    }
+    if args.lint {
+        return lint(&mut input_file, &fmap, fmap_offset, args.output.as_deref());
+    }
+
+    if args.check_self {
+        for problem in check_self(&fmap, fmap_offset).iter() {
+            warn!("{problem}");
+        }
+    }
+    if args.check_names || args.ascii_names {
+        let raw = raw_fmap(&mut input_file, &fmap, fmap_offset)?;
+        let names = raw_area_names(&raw, fmap.areas.len());
+        for (area, name) in fmap.areas.iter_mut().zip(names) {
+            if is_printable_name(name) {
+                continue;
+            }
+            if args.check_names {
+                warn!(
+                    "Area at {:#x} has name with non-printable bytes: {}",
+                    area.offset,
+                    common::hex(name)
+                );
+            }
+            if args.ascii_names {
+                area.name = escape_name(name);
+            }
+        }
+    }
+    exclude_areas(&mut fmap.areas, &args.exclude);
+    only_flag_areas(&mut fmap, &args.only_flag);
+    if let Some(key) = args.sort {
+        sort_areas(&mut fmap.areas, key);
+    }
+    window_areas(&mut fmap.areas, args.skip, args.count);
+
+    // Default format reports the offset anyway.
+    let default_format = !(args.hexdump.is_some()
+        || args.human_readable
+        || args.parsable
+        || args.parsable2
+        || args.flashrom_parsable
+        || args.ec_parsable
+        || args.full_parsable
+        || args.csv
+        || args.ndjson
+        || args.fmd
+        || args.py);
+    if args.show_hit && !default_format {
+        writeln!(writer, "# hit at {fmap_offset:#x}")?;
+    }
+
+    if let Some(area_name) = &args.hexdump {
+        dump_hexdump(&mut input_file, &fmap, area_name, base, &mut writer)?;
+    } else if args.human_readable {
+        dump_human_readable(
+            &fmap,
+            base,
+            &TreeOptions {
+                show_gaps: args.human_readable_with_gaps,
+                min_gap: args.min_gap.unwrap_or(0),
+                ignore_overlap: args.ignore_overlapping_sections,
+                flat: args.flat,
+                no_dedup: args.no_dedup,
+                max_depth: args.max_depth,
+                leaves_only: args.leaves_only,
+                top_level_only: args.top_level_only,
+                tree_style: args.tree_style.unwrap_or_default(),
+            },
+            &mut writer,
+        )?;
+    } else if args.parsable {
+        dump_parsable(&fmap, base, &mut writer)?;
+    } else if args.parsable2 {
+        dump_parsable2(&fmap, base, &mut writer)?;
+    } else if args.flashrom_parsable {
+        dump_flashrom_parsable(&fmap, base, &mut writer)?;
+    } else if args.ec_parsable {
+        dump_ec_parsable(&fmap, base, &mut writer)?;
+    } else if args.full_parsable {
+        dump_full_parsable(&fmap, base, &mut writer)?;
+    } else if args.csv {
+        dump_csv(&fmap, base, &mut writer)?;
+    } else if args.ndjson {
+        dump_ndjson(&fmap, base, &mut writer)?;
+    } else if args.fmd {
+        dump_fmd(&fmap, &mut writer)?;
+    } else if args.py {
+        dump_py(&fmap, &mut writer)?;
+    } else {
+        dump_default(&fmap, fmap_offset, &mut writer)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
     }
 
     fn example_complex_fmap() -> fmap::FMap {
@@ -686,99 +1752,374 @@ mod tests {
     fn test_dump_human_readable() -> Result<(), String> {
         init();
         let mut result = Vec::new();
-        if let Err(e) = dump_human_readable(&example_complex_fmap(), false, true, &mut result) {
+        if let Err(e) = dump_human_readable(
+            &example_complex_fmap(),
+            0,
+            &TreeOptions {
+                ignore_overlap: true,
+                ..Default::default()
+            },
+            &mut result,
+        ) {
             return Err(format!("dump_human_readable() failed with error: {e}"));
         }
-        let expected = r#"# name                     start       end         size
--entire flash-             00000000    02000000    02000000
-  SI_ALL                     00000000    00500000    00500000
+        let expected = r#"# name                         start       end         size
+-entire flash-                 00000000    02000000    02000000
+  SI_ALL                       00000000    00500000    00500000
     SI_DESC                    00000000    00001000    00001000
     SI_ME                      00001000    00500000    004ff000
-      CSE_LAYOUT                 00001000    00003000    00002000
-      CSE_RO                     00003000    0019d000    0019a000
-      CSE_DATA                   0019d000    00206000    00069000
-      CSE_RW                     00206000    004f6000    002f0000
-  SI_BIOS                    00500000    02000000    01b00000
+      CSE_LAYOUT               00001000    00003000    00002000
+      CSE_RO                   00003000    0019d000    0019a000
+      CSE_DATA                 0019d000    00206000    00069000
+      CSE_RW                   00206000    004f6000    002f0000
+  SI_BIOS                      00500000    02000000    01b00000
     RW_SECTION_A               00500000    00d00000    00800000
-      VBLOCK_A                   00500000    00510000    00010000
-      FW_MAIN_A                  00510000    00cfffc0    007effc0
-      RW_FWID_A                  00cfffc0    00d00000    00000040
+      VBLOCK_A                 00500000    00510000    00010000
+      FW_MAIN_A                00510000    00cfffc0    007effc0
+      RW_FWID_A                00cfffc0    00d00000    00000040
     RW_LEGACY                  00d00000    00f00000    00200000
     RW_MISC                    00f00000    01000000    00100000
-      UNIFIED_MRC_CACHE          00f00000    00f20000    00020000
-        RECOVERY_MRC_CACHE         00f00000    00f10000    00010000
-        RW_MRC_CACHE               00f10000    00f20000    00010000
-      RW_ELOG                    00f20000    00f24000    00004000
-      RW_SHARED                  00f24000    00f28000    00004000
-        SHARED_DATA                00f24000    00f26000    00002000
-        SHARED_DATA_DUPLICATE      00f24000    00f26000    00002000  // DUPLICATE
-        VBLOCK_DEV                 00f26000    00f28000    00002000
-      RW_SPD_CACHE               00f28000    00f29000    00001000
-      RW_VPD                     00f29000    00f2b000    00002000
-      RW_NVRAM                   00f2b000    00f31000    00006000
+      UNIFIED_MRC_CACHE        00f00000    00f20000    00020000
+        RECOVERY_MRC_CACHE     00f00000    00f10000    00010000
+        RW_MRC_CACHE           00f10000    00f20000    00010000
+      RW_ELOG                  00f20000    00f24000    00004000
+      RW_SHARED                00f24000    00f28000    00004000
+        SHARED_DATA            00f24000    00f26000    00002000
+        SHARED_DATA_DUPLICATE  00f24000    00f26000    00002000  // DUPLICATE
+        VBLOCK_DEV             00f26000    00f28000    00002000
+      RW_SPD_CACHE             00f28000    00f29000    00001000
+      RW_VPD                   00f29000    00f2b000    00002000
+      RW_NVRAM                 00f2b000    00f31000    00006000
     RW_SECTION_B               01000000    01800000    00800000
-      VBLOCK_B                   01000000    01010000    00010000
-      FW_MAIN_B                  01010000    017fffc0    007effc0
-      RW_FWID_B                  017fffc0    01800000    00000040
+      VBLOCK_B                 01000000    01010000    00010000
+      FW_MAIN_B                01010000    017fffc0    007effc0
+      RW_FWID_B                017fffc0    01800000    00000040
     WP_RO                      01800000    02000000    00800000
-      RO_VPD                     01800000    01804000    00004000
-      RO_SECTION                 01804000    02000000    007fc000
-        FMAP                       01804000    01804800    00000800
-        RO_FRID                    01804800    01804840    00000040
-        GBB                        01805000    01875000    00070000
-        COREBOOT                   01875000    02000000    0078b000
+      RO_VPD                   01800000    01804000    00004000
+      RO_SECTION               01804000    02000000    007fc000
+        FMAP                   01804000    01804800    00000800
+        RO_FRID                01804800    01804840    00000040
+        GBB                    01805000    01875000    00070000
+        COREBOOT               01875000    02000000    0078b000
+"#;
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_flat() -> Result<(), String> {
+        init();
+        let mut result = Vec::new();
+        if let Err(e) = dump_human_readable(
+            &example_complex_fmap(),
+            0,
+            &TreeOptions {
+                ignore_overlap: true,
+                flat: true,
+                ..Default::default()
+            },
+            &mut result,
+        ) {
+            return Err(format!("dump_human_readable() failed with error: {e}"));
+        }
+        let expected = r#"# level name                   start       end         size
+0       -entire flash-         00000000    02000000    02000000
+1       SI_ALL                 00000000    00500000    00500000
+2       SI_DESC                00000000    00001000    00001000
+2       SI_ME                  00001000    00500000    004ff000
+3       CSE_LAYOUT             00001000    00003000    00002000
+3       CSE_RO                 00003000    0019d000    0019a000
+3       CSE_DATA               0019d000    00206000    00069000
+3       CSE_RW                 00206000    004f6000    002f0000
+1       SI_BIOS                00500000    02000000    01b00000
+2       RW_SECTION_A           00500000    00d00000    00800000
+3       VBLOCK_A               00500000    00510000    00010000
+3       FW_MAIN_A              00510000    00cfffc0    007effc0
+3       RW_FWID_A              00cfffc0    00d00000    00000040
+2       RW_LEGACY              00d00000    00f00000    00200000
+2       RW_MISC                00f00000    01000000    00100000
+3       UNIFIED_MRC_CACHE      00f00000    00f20000    00020000
+4       RECOVERY_MRC_CACHE     00f00000    00f10000    00010000
+4       RW_MRC_CACHE           00f10000    00f20000    00010000
+3       RW_ELOG                00f20000    00f24000    00004000
+3       RW_SHARED              00f24000    00f28000    00004000
+4       SHARED_DATA            00f24000    00f26000    00002000
+4       SHARED_DATA_DUPLICATE  00f24000    00f26000    00002000  // DUPLICATE
+4       VBLOCK_DEV             00f26000    00f28000    00002000
+3       RW_SPD_CACHE           00f28000    00f29000    00001000
+3       RW_VPD                 00f29000    00f2b000    00002000
+3       RW_NVRAM               00f2b000    00f31000    00006000
+2       RW_SECTION_B           01000000    01800000    00800000
+3       VBLOCK_B               01000000    01010000    00010000
+3       FW_MAIN_B              01010000    017fffc0    007effc0
+3       RW_FWID_B              017fffc0    01800000    00000040
+2       WP_RO                  01800000    02000000    00800000
+3       RO_VPD                 01800000    01804000    00004000
+3       RO_SECTION             01804000    02000000    007fc000
+4       FMAP                   01804000    01804800    00000800
+4       RO_FRID                01804800    01804840    00000040
+4       GBB                    01805000    01875000    00070000
+4       COREBOOT               01875000    02000000    0078b000
+"#;
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_no_dedup() -> Result<(), String> {
+        init();
+        let shared_lines = |dedup: bool| -> Result<String, String> {
+            let mut result = Vec::new();
+            if let Err(e) = dump_human_readable(
+                &example_complex_fmap(),
+                0,
+                &TreeOptions {
+                    ignore_overlap: true,
+                    no_dedup: !dedup,
+                    ..Default::default()
+                },
+                &mut result,
+            ) {
+                return Err(format!("dump_human_readable() failed with error: {e}"));
+            }
+            Ok(String::from_utf8(result)
+                .unwrap()
+                .lines()
+                .filter(|l| l.contains("SHARED") || l.contains("VBLOCK_DEV"))
+                .join("\n"))
+        };
+
+        let expected = "      RW_SHARED                00f24000    00f28000    00004000
+        SHARED_DATA            00f24000    00f26000    00002000
+        SHARED_DATA_DUPLICATE  00f24000    00f26000    00002000  // DUPLICATE
+        VBLOCK_DEV             00f26000    00f28000    00002000";
+        assert_eq!(shared_lines(true)?, expected);
+
+        let expected = "      RW_SHARED                00f24000    00f28000    00004000
+        SHARED_DATA            00f24000    00f26000    00002000
+        SHARED_DATA_DUPLICATE  00f24000    00f26000    00002000
+        VBLOCK_DEV             00f26000    00f28000    00002000";
+        assert_eq!(shared_lines(false)?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_max_depth() -> Result<(), String> {
+        init();
+        let mut result = Vec::new();
+        let opts = TreeOptions {
+            ignore_overlap: true,
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        if let Err(e) = dump_human_readable(&example_complex_fmap(), 0, &opts, &mut result) {
+            return Err(format!("dump_human_readable() failed with error: {e}"));
+        }
+        let expected = r#"# name            start       end         size
+-entire flash-    00000000    02000000    02000000
+  SI_ALL          00000000    00500000    00500000
+    SI_DESC       00000000    00001000    00001000
+    SI_ME         00001000    00500000    004ff000  (+4 more)
+  SI_BIOS         00500000    02000000    01b00000
+    RW_SECTION_A  00500000    00d00000    00800000  (+3 more)
+    RW_LEGACY     00d00000    00f00000    00200000
+    RW_MISC       00f00000    01000000    00100000  (+10 more)
+    RW_SECTION_B  01000000    01800000    00800000  (+3 more)
+    WP_RO         01800000    02000000    00800000  (+6 more)
 "#;
         assert_eq!(String::from_utf8(result).unwrap(), expected);
 
         Ok(())
     }
 
+    #[test]
+    fn test_dump_human_readable_filters() -> Result<(), String> {
+        init();
+        let dump = |leaves_only: bool, top_level_only: bool| -> Result<String, String> {
+            let mut result = Vec::new();
+            let opts = TreeOptions {
+                ignore_overlap: true,
+                leaves_only,
+                top_level_only,
+                ..Default::default()
+            };
+            if let Err(e) = dump_human_readable(&example_complex_fmap(), 0, &opts, &mut result) {
+                return Err(format!("dump_human_readable() failed with error: {e}"));
+            }
+            Ok(String::from_utf8(result).unwrap())
+        };
+
+        let expected = r#"# name          start       end         size
+-entire flash-  00000000    02000000    02000000
+  SI_ALL        00000000    00500000    00500000
+  SI_BIOS       00500000    02000000    01b00000
+"#;
+        assert_eq!(dump(false, true)?, expected);
+
+        let expected = r#"# name                 start       end         size
+SI_DESC                00000000    00001000    00001000
+CSE_LAYOUT             00001000    00003000    00002000
+CSE_RO                 00003000    0019d000    0019a000
+CSE_DATA               0019d000    00206000    00069000
+CSE_RW                 00206000    004f6000    002f0000
+VBLOCK_A               00500000    00510000    00010000
+FW_MAIN_A              00510000    00cfffc0    007effc0
+RW_FWID_A              00cfffc0    00d00000    00000040
+RW_LEGACY              00d00000    00f00000    00200000
+RECOVERY_MRC_CACHE     00f00000    00f10000    00010000
+RW_MRC_CACHE           00f10000    00f20000    00010000
+RW_ELOG                00f20000    00f24000    00004000
+SHARED_DATA            00f24000    00f26000    00002000
+SHARED_DATA_DUPLICATE  00f24000    00f26000    00002000  // DUPLICATE
+VBLOCK_DEV             00f26000    00f28000    00002000
+RW_SPD_CACHE           00f28000    00f29000    00001000
+RW_VPD                 00f29000    00f2b000    00002000
+RW_NVRAM               00f2b000    00f31000    00006000
+VBLOCK_B               01000000    01010000    00010000
+FW_MAIN_B              01010000    017fffc0    007effc0
+RW_FWID_B              017fffc0    01800000    00000040
+RO_VPD                 01800000    01804000    00004000
+FMAP                   01804000    01804800    00000800
+RO_FRID                01804800    01804840    00000040
+GBB                    01805000    01875000    00070000
+COREBOOT               01875000    02000000    0078b000
+"#;
+        assert_eq!(dump(true, false)?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_tree_style() -> Result<(), String> {
+        init();
+        let tree = |tree_style: TreeStyle, max_depth: Option<usize>| -> Result<String, String> {
+            let mut result = Vec::new();
+            let opts = TreeOptions {
+                ignore_overlap: true,
+                max_depth,
+                tree_style,
+                ..Default::default()
+            };
+            if let Err(e) = dump_human_readable(&example_complex_fmap(), 0, &opts, &mut result) {
+                return Err(format!("dump_human_readable() failed with error: {e}"));
+            }
+            Ok(String::from_utf8(result).unwrap())
+        };
+
+        let expected = r#"# name                start       end         size
+-entire flash-        00000000    02000000    02000000
+├── SI_ALL            00000000    00500000    00500000
+│   ├── SI_DESC       00000000    00001000    00001000
+│   └── SI_ME         00001000    00500000    004ff000  (+4 more)
+└── SI_BIOS           00500000    02000000    01b00000
+    ├── RW_SECTION_A  00500000    00d00000    00800000  (+3 more)
+    ├── RW_LEGACY     00d00000    00f00000    00200000
+    ├── RW_MISC       00f00000    01000000    00100000  (+10 more)
+    ├── RW_SECTION_B  01000000    01800000    00800000  (+3 more)
+    └── WP_RO         01800000    02000000    00800000  (+6 more)
+"#;
+        assert_eq!(tree(TreeStyle::Unicode, Some(2))?, expected);
+
+        // Aliases are connected as siblings following the node.
+        let expected = "    |   |-- RW_SHARED                  00f24000    00f28000    00004000
+    |   |   |-- SHARED_DATA            00f24000    00f26000    00002000
+    |   |   |-- SHARED_DATA_DUPLICATE  00f24000    00f26000    00002000  // DUPLICATE
+    |   |   `-- VBLOCK_DEV             00f26000    00f28000    00002000";
+        let lines = tree(TreeStyle::Ascii, None)?
+            .lines()
+            .filter(|l| l.contains("SHARED") || l.contains("VBLOCK_DEV"))
+            .join("\n");
+        assert_eq!(lines, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_min_gap() -> Result<(), String> {
+        init();
+        let mut result = Vec::new();
+        let opts = TreeOptions {
+            show_gaps: true,
+            min_gap: 0x1000,
+            ignore_overlap: true,
+            ..Default::default()
+        };
+        if let Err(e) = dump_human_readable(&example_complex_fmap(), 0, &opts, &mut result) {
+            return Err(format!("dump_human_readable() failed with error: {e}"));
+        }
+        // Gap of 0x7c0 bytes in RO_SECTION is hidden.
+        let expected = "      [UNUSED]                 004f6000    00500000    0000a000
+      [UNUSED]                 00f31000    01000000    000cf000";
+        let gaps = String::from_utf8(result)
+            .unwrap()
+            .lines()
+            .filter(|l| l.contains("[UNUSED]"))
+            .join("\n");
+        assert_eq!(gaps, expected);
+
+        Ok(())
+    }
+
     #[test]
     fn test_dump_human_readable_with_gaps() -> Result<(), String> {
         init();
         let mut result = Vec::new();
-        if let Err(e) = dump_human_readable(&example_complex_fmap(), true, true, &mut result) {
+        if let Err(e) = dump_human_readable(
+            &example_complex_fmap(),
+            0,
+            &TreeOptions {
+                show_gaps: true,
+                ignore_overlap: true,
+                ..Default::default()
+            },
+            &mut result,
+        ) {
             return Err(format!("dump_human_readable() failed with error: {e}"));
         }
-        let expected = r#"# name                     start       end         size
--entire flash-             00000000    02000000    02000000
-  SI_ALL                     00000000    00500000    00500000
+        let expected = r#"# name                         start       end         size
+-entire flash-                 00000000    02000000    02000000
+  SI_ALL                       00000000    00500000    00500000
     SI_DESC                    00000000    00001000    00001000
     SI_ME                      00001000    00500000    004ff000
-      CSE_LAYOUT                 00001000    00003000    00002000
-      CSE_RO                     00003000    0019d000    0019a000
-      CSE_DATA                   0019d000    00206000    00069000
-      CSE_RW                     00206000    004f6000    002f0000
-  SI_BIOS                    00500000    02000000    01b00000
+      CSE_LAYOUT               00001000    00003000    00002000
+      CSE_RO                   00003000    0019d000    0019a000
+      CSE_DATA                 0019d000    00206000    00069000
+      CSE_RW                   00206000    004f6000    002f0000
+      [UNUSED]                 004f6000    00500000    0000a000
+  SI_BIOS                      00500000    02000000    01b00000
     RW_SECTION_A               00500000    00d00000    00800000
-      VBLOCK_A                   00500000    00510000    00010000
-      FW_MAIN_A                  00510000    00cfffc0    007effc0
-      RW_FWID_A                  00cfffc0    00d00000    00000040
+      VBLOCK_A                 00500000    00510000    00010000
+      FW_MAIN_A                00510000    00cfffc0    007effc0
+      RW_FWID_A                00cfffc0    00d00000    00000040
     RW_LEGACY                  00d00000    00f00000    00200000
     RW_MISC                    00f00000    01000000    00100000
-      UNIFIED_MRC_CACHE          00f00000    00f20000    00020000
-        RECOVERY_MRC_CACHE         00f00000    00f10000    00010000
-        RW_MRC_CACHE               00f10000    00f20000    00010000
-      RW_ELOG                    00f20000    00f24000    00004000
-      RW_SHARED                  00f24000    00f28000    00004000
-        SHARED_DATA                00f24000    00f26000    00002000
-        SHARED_DATA_DUPLICATE      00f24000    00f26000    00002000  // DUPLICATE
-        VBLOCK_DEV                 00f26000    00f28000    00002000
-      RW_SPD_CACHE               00f28000    00f29000    00001000
-      RW_VPD                     00f29000    00f2b000    00002000
-      RW_NVRAM                   00f2b000    00f31000    00006000
+      UNIFIED_MRC_CACHE        00f00000    00f20000    00020000
+        RECOVERY_MRC_CACHE     00f00000    00f10000    00010000
+        RW_MRC_CACHE           00f10000    00f20000    00010000
+      RW_ELOG                  00f20000    00f24000    00004000
+      RW_SHARED                00f24000    00f28000    00004000
+        SHARED_DATA            00f24000    00f26000    00002000
+        SHARED_DATA_DUPLICATE  00f24000    00f26000    00002000  // DUPLICATE
+        VBLOCK_DEV             00f26000    00f28000    00002000
+      RW_SPD_CACHE             00f28000    00f29000    00001000
+      RW_VPD                   00f29000    00f2b000    00002000
+      RW_NVRAM                 00f2b000    00f31000    00006000
+      [UNUSED]                 00f31000    01000000    000cf000
     RW_SECTION_B               01000000    01800000    00800000
-      VBLOCK_B                   01000000    01010000    00010000
-      FW_MAIN_B                  01010000    017fffc0    007effc0
-      RW_FWID_B                  017fffc0    01800000    00000040
+      VBLOCK_B                 01000000    01010000    00010000
+      FW_MAIN_B                01010000    017fffc0    007effc0
+      RW_FWID_B                017fffc0    01800000    00000040
     WP_RO                      01800000    02000000    00800000
-      RO_VPD                     01800000    01804000    00004000
-      RO_SECTION                 01804000    02000000    007fc000
-        FMAP                       01804000    01804800    00000800
-        RO_FRID                    01804800    01804840    00000040
-        [UNUSED]                   01804840    01805000    000007c0
-        GBB                        01805000    01875000    00070000
-        COREBOOT                   01875000    02000000    0078b000
+      RO_VPD                   01800000    01804000    00004000
+      RO_SECTION               01804000    02000000    007fc000
+        FMAP                   01804000    01804800    00000800
+        RO_FRID                01804800    01804840    00000040
+        [UNUSED]               01804840    01805000    000007c0
+        GBB                    01805000    01875000    00070000
+        COREBOOT               01875000    02000000    0078b000
 "#;
         assert_eq!(String::from_utf8(result).unwrap(), expected);
 
@@ -789,7 +2130,17 @@ mod tests {
     fn test_dump_humap_readable_do_not_ignore_overlaps() -> Result<(), String> {
         init();
         let mut result = Vec::new();
-        if dump_human_readable(&example_complex_fmap(), true, false, &mut result).is_ok() {
+        if dump_human_readable(
+            &example_complex_fmap(),
+            0,
+            &TreeOptions {
+                show_gaps: true,
+                ..Default::default()
+            },
+            &mut result,
+        )
+        .is_ok()
+        {
             Err("Overlap error expected, got Ok()".to_string())
         } else {
             Ok(())
@@ -804,30 +2155,10 @@ mod tests {
             version_major: 1,
             version_minor: 1,
             areas: vec![
-                fmap::FMapArea {
-                    name: "bootblock".to_string(),
-                    offset: 0,
-                    size: 0x80,
-                    flags: fmap::FMapFlags::empty(),
-                },
-                fmap::FMapArea {
-                    name: "normal".to_string(),
-                    offset: 0x80,
-                    size: 0x80,
-                    flags: fmap::FMapFlags::Preserve,
-                },
-                fmap::FMapArea {
-                    name: "fallback".to_string(),
-                    offset: 0x100,
-                    size: 0x100,
-                    flags: fmap::FMapFlags::empty(),
-                },
-                fmap::FMapArea {
-                    name: "data".to_string(),
-                    offset: 0x200,
-                    size: 0x200,
-                    flags: fmap::FMapFlags::empty(),
-                },
+                fmap::FMapArea::new("bootblock", 0, 0x80),
+                fmap::FMapArea::new("normal", 0x80, 0x80).with_flags(fmap::FMapFlags::Preserve),
+                fmap::FMapArea::new("fallback", 0x100, 0x100),
+                fmap::FMapArea::new("data", 0x200, 0x200),
             ],
         }
     }
@@ -835,7 +2166,7 @@ mod tests {
     #[test]
     fn test_dump_parsable() -> Result<(), String> {
         let mut result = Vec::new();
-        if let Err(e) = dump_parsable(&example_fmap(), &mut result) {
+        if let Err(e) = dump_parsable(&example_fmap(), 0, &mut result) {
             return Err(format!("dump_parsable() failed with error: {e}"));
         }
         let expected = "bootblock 0 128\n\
@@ -847,10 +2178,69 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_dump_parsable2() -> Result<(), String> {
+        let mut result = Vec::new();
+        if let Err(e) = dump_parsable2(&example_fmap(), 0, &mut result) {
+            return Err(format!("dump_parsable2() failed with error: {e}"));
+        }
+        let expected = "bootblock 0 128 127\n\
+                        normal 128 128 255\n\
+                        fallback 256 256 511\n\
+                        data 512 512 1023\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        // End is the same as in flashrom format.
+        let mut flashrom = Vec::new();
+        if let Err(e) = dump_flashrom_parsable(&example_fmap(), 0x1000, &mut flashrom) {
+            return Err(format!("dump_flashrom_parsable() failed with error: {e}"));
+        }
+        let mut result = Vec::new();
+        if let Err(e) = dump_parsable2(&example_fmap(), 0x1000, &mut result) {
+            return Err(format!("dump_parsable2() failed with error: {e}"));
+        }
+        for (line, flashrom_line) in String::from_utf8(result)
+            .unwrap()
+            .lines()
+            .zip(String::from_utf8(flashrom).unwrap().lines())
+        {
+            let end = line.rsplit(' ').next().unwrap().parse::<u64>().unwrap();
+            let (_, flashrom_end) = flashrom_line.split_once(':').unwrap();
+            let flashrom_end = flashrom_end.split(' ').next().unwrap();
+            assert_eq!(format!("{end:#08x}"), flashrom_end);
+        }
+
+        // Area of zero size has no inclusive end. End at the top of the address space
+        // does not overflow.
+        let mut fmap = example_fmap();
+        fmap.areas.insert(0, fmap::FMapArea::new("EMPTY", 0x80, 0));
+        fmap.areas.insert(0, fmap::FMapArea::new("EMPTY0", 0, 0));
+        let mut result = Vec::new();
+        if let Err(e) = dump_parsable2(&fmap, 0, &mut result) {
+            return Err(format!("dump_parsable2() failed with error: {e}"));
+        }
+        let result = String::from_utf8(result).unwrap();
+        assert_eq!(
+            result.lines().take(2).collect_vec(),
+            ["EMPTY0 0 0 -", "EMPTY 128 0 -"]
+        );
+        let mut result = Vec::new();
+        if let Err(e) = dump_parsable2(&fmap, 0xffff_ffff_ffff_fc00, &mut result) {
+            return Err(format!("dump_parsable2() failed with error: {e}"));
+        }
+        let result = String::from_utf8(result).unwrap();
+        assert_eq!(
+            result.lines().last(),
+            Some("data 18446744073709551104 512 18446744073709551615")
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_dump_flashrom_parsable() -> Result<(), String> {
         let mut result = Vec::new();
-        if let Err(e) = dump_flashrom_parsable(&example_fmap(), &mut result) {
+        if let Err(e) = dump_flashrom_parsable(&example_fmap(), 0, &mut result) {
             return Err(format!("dump_flashrom_parsable() failed with error: {e}"));
         }
         let expected = "0x000000:0x00007f bootblock\n\
@@ -865,7 +2255,7 @@ mod tests {
     #[test]
     fn test_dump_ec_parsable() -> Result<(), String> {
         let mut result = Vec::new();
-        if let Err(e) = dump_ec_parsable(&example_fmap(), &mut result) {
+        if let Err(e) = dump_ec_parsable(&example_fmap(), 0, &mut result) {
             return Err(format!("dump_ec_parsable() failed with error: {e}"));
         }
         let expected = "bootblock 0 128 not-preserve\n\
@@ -876,4 +2266,1101 @@ mod tests {
 
         Ok(())
     }
+
+    fn unsorted_fmap() -> fmap::FMap {
+        let mut fmap = example_fmap();
+        fmap.areas.swap(0, 3);
+        fmap.areas.swap(1, 2);
+        fmap
+    }
+
+    #[test]
+    fn test_sort_by_offset() -> Result<(), String> {
+        let mut fmap = unsorted_fmap();
+        sort_areas(&mut fmap.areas, SortKey::Offset);
+        let mut result = Vec::new();
+        if let Err(e) = dump_parsable(&fmap, 0, &mut result) {
+            return Err(format!("dump_parsable() failed with error: {e}"));
+        }
+        let expected = "bootblock 0 128\n\
+                        normal 128 128\n\
+                        fallback 256 256\n\
+                        data 512 512\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_by_name() -> Result<(), String> {
+        let mut fmap = unsorted_fmap();
+        sort_areas(&mut fmap.areas, SortKey::Name);
+        let mut result = Vec::new();
+        if let Err(e) = dump_flashrom_parsable(&fmap, 0, &mut result) {
+            return Err(format!("dump_flashrom_parsable() failed with error: {e}"));
+        }
+        let expected = "0x000000:0x00007f bootblock\n\
+                        0x000200:0x0003ff data\n\
+                        0x000100:0x0001ff fallback\n\
+                        0x000080:0x0000ff normal\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_by_size() -> Result<(), String> {
+        let mut fmap = unsorted_fmap();
+        sort_areas(&mut fmap.areas, SortKey::Size);
+        let mut result = Vec::new();
+        if let Err(e) = dump_ec_parsable(&fmap, 0, &mut result) {
+            return Err(format!("dump_ec_parsable() failed with error: {e}"));
+        }
+        // Equal sizes keep FlashMap order.
+        let expected = "normal 128 128 preserve\n\
+                        bootblock 0 128 not-preserve\n\
+                        fallback 256 256 not-preserve\n\
+                        data 512 512 not-preserve\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_areas() -> Result<(), String> {
+        let mut fmap = example_fmap();
+        exclude_areas(
+            &mut fmap.areas,
+            &["normal".to_string(), "missing".to_string()],
+        );
+        let mut result = Vec::new();
+        if let Err(e) = dump_parsable(&fmap, 0, &mut result) {
+            return Err(format!("dump_parsable() failed with error: {e}"));
+        }
+        let expected = "bootblock 0 128\n\
+                        fallback 256 256\n\
+                        data 512 512\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_flag_areas() -> Result<(), String> {
+        let mut fmap = example_complex_fmap();
+        only_flag_areas(&mut fmap, &[fmap::FMapFlags::Preserve]);
+        let mut result = Vec::new();
+        if let Err(e) = dump_parsable(&fmap, 0, &mut result) {
+            return Err(format!("dump_parsable() failed with error: {e}"));
+        }
+        let expected = "UNIFIED_MRC_CACHE 15728640 131072\n\
+                        RW_ELOG 15859712 16384\n\
+                        RW_SPD_CACHE 15892480 4096\n\
+                        RW_VPD 15896576 8192\n\
+                        RW_NVRAM 15904768 24576\n\
+                        RO_VPD 25165824 16384\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        let mut fmap = example_complex_fmap();
+        only_flag_areas(
+            &mut fmap,
+            &[fmap::FMapFlags::Preserve, fmap::FMapFlags::Static],
+        );
+        assert!(fmap.areas.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_window_areas() -> Result<(), String> {
+        let names = |skip, count| {
+            let mut fmap = example_fmap();
+            window_areas(&mut fmap.areas, skip, count);
+            fmap.area_names().join(" ")
+        };
+        assert_eq!(names(None, None), "bootblock normal fallback data");
+        assert_eq!(names(Some(1), None), "normal fallback data");
+        assert_eq!(names(None, Some(2)), "bootblock normal");
+        assert_eq!(names(Some(1), Some(2)), "normal fallback");
+        assert_eq!(names(Some(3), Some(2)), "data");
+        assert_eq!(names(Some(5), None), "");
+        assert_eq!(names(None, Some(0)), "");
+
+        // Window is applied to sorted areas.
+        let mut fmap = unsorted_fmap();
+        sort_areas(&mut fmap.areas, SortKey::Offset);
+        window_areas(&mut fmap.areas, Some(1), Some(2));
+        let mut result = Vec::new();
+        if let Err(e) = dump_parsable(&fmap, 0, &mut result) {
+            return Err(format!("dump_parsable() failed with error: {e}"));
+        }
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "normal 128 128\nfallback 256 256\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_csv() -> Result<(), String> {
+        let mut fmap = example_fmap();
+        fmap.areas[2].name = "fall,back".to_string();
+        fmap.areas[2].flags = fmap::FMapFlags::Static | fmap::FMapFlags::RO;
+        let mut result = Vec::new();
+        if let Err(e) = dump_csv(&fmap, 0, &mut result) {
+            return Err(format!("dump_csv() failed with error: {e}"));
+        }
+        let expected = "name,start,end,size,flags\n\
+                        bootblock,0,128,128,\n\
+                        normal,128,256,128,PRESERVE\n\
+                        \"fall,back\",256,512,256,STATIC;RO\n\
+                        data,512,1024,512,\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        // End of the area reaching the top of the address space wraps to 0.
+        let mut result = Vec::new();
+        if let Err(e) = dump_csv(&example_fmap(), 0xffff_ffff_ffff_fc00, &mut result) {
+            return Err(format!("dump_csv() failed with error: {e}"));
+        }
+        let result = String::from_utf8(result).unwrap();
+        assert_eq!(
+            result.lines().last(),
+            Some("data,18446744073709551104,0,512,")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_ndjson() -> Result<(), String> {
+        let mut result = Vec::new();
+        if let Err(e) = dump_ndjson(&example_fmap(), 0, &mut result) {
+            return Err(format!("dump_ndjson() failed with error: {e}"));
+        }
+        let lines = String::from_utf8(result)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect_vec();
+        assert_eq!(
+            lines,
+            [
+                serde_json::json!({
+                    "type": "fmap", "name": "example", "version_major": 1, "version_minor": 1,
+                    "base": 0, "size": 1024, "nareas": 4,
+                }),
+                serde_json::json!({
+                    "type": "area", "name": "bootblock", "offset": 0, "size": 128, "flags": [],
+                }),
+                serde_json::json!({
+                    "type": "area", "name": "normal", "offset": 128, "size": 128,
+                    "flags": ["PRESERVE"],
+                }),
+                serde_json::json!({
+                    "type": "area", "name": "fallback", "offset": 256, "size": 256, "flags": [],
+                }),
+                serde_json::json!({
+                    "type": "area", "name": "data", "offset": 512, "size": 512, "flags": [],
+                }),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_schema() -> Result<(), String> {
+        let names = |format| {
+            output_schema(format)["columns"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|c| c["name"].as_str().unwrap().to_string())
+                .collect_vec()
+        };
+        assert_eq!(names(SchemaFormat::Parsable), ["name", "offset", "size"]);
+        assert_eq!(names(SchemaFormat::Flashrom), ["start", "end", "name"]);
+
+        // Column names of CSV match its header row.
+        let mut result = Vec::new();
+        if let Err(e) = dump_csv(&example_fmap(), 0, &mut result) {
+            return Err(format!("dump_csv() failed with error: {e}"));
+        }
+        let header = String::from_utf8(result).unwrap();
+        assert_eq!(
+            header.lines().next(),
+            Some(names(SchemaFormat::Csv).join(",").as_str())
+        );
+        assert_eq!(output_schema(SchemaFormat::Csv)["header"], true);
+        assert_eq!(output_schema(SchemaFormat::Ec)["format"], "ec");
+
+        // Fields of NDJSON objects match keys of printed objects.
+        let schema = output_schema(SchemaFormat::Ndjson);
+        assert_eq!(schema["format"], "ndjson");
+        let mut result = Vec::new();
+        if let Err(e) = dump_ndjson(&example_fmap(), 0, &mut result) {
+            return Err(format!("dump_ndjson() failed with error: {e}"));
+        }
+        let result = String::from_utf8(result).unwrap();
+        for (i, line) in result.lines().enumerate() {
+            let object: serde_json::Value = serde_json::from_str(line).unwrap();
+            let described = &schema["objects"][if i == 0 { 0 } else { 1 }];
+            assert_eq!(object["type"], described["type"]);
+            let fields = described["fields"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|f| f["name"].as_str().unwrap())
+                .sorted()
+                .collect_vec();
+            let keys = object
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(|k| k.as_str())
+                .sorted()
+                .collect_vec();
+            assert_eq!(fields, keys);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_covered_and_gap_bytes() {
+        let fmap = example_complex_fmap();
+        // Gaps are the [UNUSED] areas of human-readable dump, leaves cover the rest of flash.
+        assert_eq!(fmap.gap_bytes(), 0xa000 + 0xcf000 + 0x7c0);
+        assert_eq!(fmap.covered_bytes(), 0x2000000 - fmap.gap_bytes());
+    }
+
+    #[test]
+    fn test_dump_overlaps() -> Result<(), String> {
+        let mut result = Vec::new();
+        match dump_overlaps(&example_complex_fmap(), 0, &mut result) {
+            Ok(_) => return Err("dump_overlaps() expected to fail".to_string()),
+            Err(e) => assert_eq!(e.to_string(), "4 overlapping areas detected"),
+        }
+        // COREBOOT_OVERLAP stretches beyond all of its ancestors too.
+        let expected = r#""SI_BIOS" and "COREBOOT_OVERLAP" overlap at 0x1875001 - 0x2000000 (0x78afff bytes)
+"WP_RO" and "COREBOOT_OVERLAP" overlap at 0x1875001 - 0x2000000 (0x78afff bytes)
+"RO_SECTION" and "COREBOOT_OVERLAP" overlap at 0x1875001 - 0x2000000 (0x78afff bytes)
+"COREBOOT" and "COREBOOT_OVERLAP" overlap at 0x1875001 - 0x2000000 (0x78afff bytes)
+"#;
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        result = Vec::new();
+        if let Err(e) = dump_overlaps(&example_fmap(), 0, &mut result) {
+            return Err(format!("dump_overlaps() failed with error: {e}"));
+        }
+        assert!(result.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_names() -> Result<(), Box<dyn Error>> {
+        let mut fmap = example_fmap();
+        fmap.areas[1].name = "nor\u{1}mal".to_string();
+        let mut raw = Vec::new();
+        fmap.write(&mut raw)?;
+        // Invalid UTF-8 is decoded as empty name, so it is checked in raw bytes.
+        raw[fmap::HEADER_SIZE + 2 * fmap::AREA_SIZE + 8] = 0xff;
+
+        let names = raw_area_names(&raw, fmap.areas.len());
+        assert_eq!(
+            names,
+            [&b"bootblock"[..], b"nor\x01mal", b"\xffallback", b"data"]
+        );
+        let bad = names
+            .iter()
+            .filter(|name| !is_printable_name(name))
+            .map(|name| (common::hex(name), escape_name(name)))
+            .collect_vec();
+        assert_eq!(
+            bad,
+            [
+                ("6e6f72016d616c".to_string(), r"nor\x01mal".to_string()),
+                ("ff616c6c6261636b".to_string(), r"\xffallback".to_string()),
+            ]
+        );
+        assert_eq!(escape_name(br"a\b"), r"a\\b");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_flags() -> Result<(), Box<dyn Error>> {
+        let mut raw = Vec::new();
+        example_fmap().write(&mut raw)?;
+        check_flags(&raw)?;
+
+        // Flags of "normal" area with bit 4 set.
+        raw[fmap::HEADER_SIZE + 2 * fmap::AREA_SIZE - 2] |= 0x10;
+        match check_flags(&raw) {
+            Err(fmap::FMapError::UnknownFlags(name, 0x18)) if name == "normal" => Ok(()),
+            Ok(_) => Err("Unknown flag bits expected to fail".into()),
+            Err(e) => Err(format!("Unexpected error: {e}").into()),
+        }
+    }
+
+    #[test]
+    fn test_dump_py() -> Result<(), String> {
+        let mut fmap = example_fmap();
+        fmap.areas[2].name = "fall'back".to_string();
+        fmap.areas[2].flags = fmap::FMapFlags::Static | fmap::FMapFlags::RO;
+        let mut result = Vec::new();
+        if let Err(e) = dump_py(&fmap, &mut result) {
+            return Err(format!("dump_py() failed with error: {e}"));
+        }
+        let expected = "{'signature': b'__FMAP__', 'ver_major': 1, 'ver_minor': 1, 'base': 0, \
+                        'size': 1024, 'name': 'example', 'nareas': 4, 'areas': [\
+                        {'offset': 0, 'size': 128, 'name': 'bootblock', 'flags': 0, 'FLAGS': ()}, \
+                        {'offset': 128, 'size': 128, 'name': 'normal', 'flags': 8, \
+                        'FLAGS': ('FMAP_AREA_PRESERVE',)}, \
+                        {'offset': 256, 'size': 256, 'name': \"fall'back\", 'flags': 5, \
+                        'FLAGS': ('FMAP_AREA_STATIC', 'FMAP_AREA_RO')}, \
+                        {'offset': 512, 'size': 512, 'name': 'data', 'flags': 0, 'FLAGS': ()}]}\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    fn example_fmap_with_base() -> fmap::FMap {
+        let mut fmap = example_fmap();
+        fmap.base = 0xff000000;
+        fmap
+    }
+
+    #[test]
+    fn test_dump_parsable_flash_addresses() -> Result<(), String> {
+        let fmap = example_fmap_with_base();
+        let mut result = Vec::new();
+        if let Err(e) = dump_parsable(&fmap, fmap.base, &mut result) {
+            return Err(format!("dump_parsable() failed with error: {e}"));
+        }
+        let expected = "bootblock 4278190080 128\n\
+                        normal 4278190208 128\n\
+                        fallback 4278190336 256\n\
+                        data 4278190592 512\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_base_above_4g() -> Result<(), String> {
+        init();
+        let mut fmap = example_fmap();
+        fmap.base = 0x1_ffff_ff00;
+        let mut result = Vec::new();
+        if let Err(e) = dump_human_readable(&fmap, fmap.base, &TreeOptions::default(), &mut result)
+        {
+            return Err(format!("dump_human_readable() failed with error: {e}"));
+        }
+        let expected = "\
+# name          start        end          size
+-entire flash-  1ffffff00    200000300    000000400
+  bootblock     1ffffff00    1ffffff80    000000080
+  normal        1ffffff80    200000000    000000080
+  fallback      200000000    200000100    000000100
+  data          200000100    200000300    000000200
+";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_flashrom_parsable_flash_addresses() -> Result<(), String> {
+        let fmap = example_fmap_with_base();
+        let mut result = Vec::new();
+        if let Err(e) = dump_flashrom_parsable(&fmap, fmap.base, &mut result) {
+            return Err(format!("dump_flashrom_parsable() failed with error: {e}"));
+        }
+        let expected = "0xff000000:0xff00007f bootblock\n\
+                        0xff000080:0xff0000ff normal\n\
+                        0xff000100:0xff0001ff fallback\n\
+                        0xff000200:0xff0003ff data\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_flashrom_parsable_near_max_base() -> Result<(), String> {
+        let mut fmap = example_fmap();
+        fmap.base = 0xffff_ffff_ffff_fc00;
+        fmap.areas.insert(0, fmap::FMapArea::new("EMPTY", 0, 0));
+        let mut result = Vec::new();
+        if let Err(e) = dump_flashrom_parsable(&fmap, fmap.base, &mut result) {
+            return Err(format!("dump_flashrom_parsable() failed with error: {e}"));
+        }
+        // Area of zero size is skipped, the last one ends at the top of the address space.
+        let expected = "0xfffffffffffffc00:0xfffffffffffffc7f bootblock\n\
+                        0xfffffffffffffc80:0xfffffffffffffcff normal\n\
+                        0xfffffffffffffd00:0xfffffffffffffdff fallback\n\
+                        0xfffffffffffffe00:0xffffffffffffffff data\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_ec_parsable_flash_addresses() -> Result<(), String> {
+        let fmap = example_fmap_with_base();
+        let mut result = Vec::new();
+        if let Err(e) = dump_ec_parsable(&fmap, fmap.base, &mut result) {
+            return Err(format!("dump_ec_parsable() failed with error: {e}"));
+        }
+        let expected = "bootblock 4278190080 128 not-preserve\n\
+                        normal 4278190208 128 preserve\n\
+                        fallback 4278190336 256 not-preserve\n\
+                        data 4278190592 512 not-preserve\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_flash_addresses() -> Result<(), String> {
+        init();
+        let fmap = example_fmap_with_base();
+        let mut result = Vec::new();
+        if let Err(e) = dump_human_readable(
+            &fmap,
+            fmap.base,
+            &TreeOptions {
+                ignore_overlap: true,
+                ..Default::default()
+            },
+            &mut result,
+        ) {
+            return Err(format!("dump_human_readable() failed with error: {e}"));
+        }
+        let expected = r#"# name          start       end         size
+-entire flash-  ff000000    ff000400    00000400
+  bootblock     ff000000    ff000080    00000080
+  normal        ff000080    ff000100    00000080
+  fallback      ff000100    ff000200    00000100
+  data          ff000200    ff000400    00000200
+"#;
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_wide_addresses() -> Result<(), String> {
+        init();
+        let mut fmap = example_fmap();
+        fmap.base = 0x1_ff00_0000;
+        let mut result = Vec::new();
+        if let Err(e) = dump_human_readable(&fmap, fmap.base, &TreeOptions::default(), &mut result)
+        {
+            return Err(format!("dump_human_readable() failed with error: {e}"));
+        }
+        // All hex columns widen to 9 digits and headers stay above them.
+        let expected = r#"# name          start        end          size
+-entire flash-  1ff000000    1ff000400    000000400
+  bootblock     1ff000000    1ff000080    000000080
+  normal        1ff000080    1ff000100    000000080
+  fallback      1ff000100    1ff000200    000000100
+  data          1ff000200    1ff000400    000000200
+"#;
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_near_max_base() -> Result<(), String> {
+        init();
+        let mut fmap = example_fmap();
+        fmap.areas.remove(2);
+        // Flash ends exactly at the top of the 64-bit address space.
+        fmap.base = 0xffff_ffff_ffff_fc00;
+        let mut result = Vec::new();
+        if let Err(e) = dump_human_readable(
+            &fmap,
+            fmap.base,
+            &TreeOptions {
+                show_gaps: true,
+                ..Default::default()
+            },
+            &mut result,
+        ) {
+            return Err(format!("dump_human_readable() failed with error: {e}"));
+        }
+        let expected = r#"# name          start               end                 size
+-entire flash-  fffffffffffffc00    0000000000000000    0000000000000400
+  bootblock     fffffffffffffc00    fffffffffffffc80    0000000000000080
+  normal        fffffffffffffc80    fffffffffffffd00    0000000000000080
+  [UNUSED]      fffffffffffffd00    fffffffffffffe00    0000000000000100
+  data          fffffffffffffe00    0000000000000000    0000000000000200
+"#;
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_area_spanning_entire_flash() -> Result<(), String> {
+        init();
+        let mut fmap = example_fmap();
+        for name in ["WHOLE_FLASH", "-entire flash-", " ALIAS"] {
+            fmap.areas.push(fmap::FMapArea {
+                name: name.to_string(),
+                offset: 0,
+                size: 0x400,
+                flags: fmap::FMapFlags::empty(),
+            });
+        }
+        let mut result = Vec::new();
+        if let Err(e) = dump_human_readable(&fmap, 0, &TreeOptions::default(), &mut result) {
+            return Err(format!("dump_human_readable() failed with error: {e}"));
+        }
+        let expected = r#"# name            start       end         size
+-entire flash-    00000000    00000400    00000400
+   ALIAS          00000000    00000400    00000400
+  -entire flash-  00000000    00000400    00000400  // DUPLICATE
+  WHOLE_FLASH     00000000    00000400    00000400  // DUPLICATE
+    bootblock     00000000    00000080    00000080
+    normal        00000080    00000100    00000080
+    fallback      00000100    00000200    00000100
+    data          00000200    00000400    00000200
+"#;
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_area_beyond_flash() -> Result<(), String> {
+        init();
+        let mut fmap = example_fmap();
+        fmap.areas.push(fmap::FMapArea::new("BEYOND", 0x800, 0x100));
+        let mut result = Vec::new();
+        if let Err(e) = dump_human_readable(&fmap, 0, &TreeOptions::default(), &mut result) {
+            return Err(format!("dump_human_readable() failed with error: {e}"));
+        }
+        // Area beyond flash is not nested in the root, so it is not shown.
+        let expected = r#"# name          start       end         size
+-entire flash-  00000000    00000400    00000400
+  bootblock     00000000    00000080    00000080
+  normal        00000080    00000100    00000080
+  fallback      00000100    00000200    00000100
+  data          00000200    00000400    00000200
+"#;
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        // Area crossing the end of flash overlaps it.
+        fmap.areas
+            .push(fmap::FMapArea::new("CROSSING", 0x200, 0x400));
+        let error = dump_human_readable(&fmap, 0, &TreeOptions::default(), &mut Vec::new())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "1 overlapping areas detected. Terminating.");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_human_readable_with_root_level_gaps() -> Result<(), String> {
+        init();
+        let mut fmap = example_fmap();
+        // Leave space before the first and after the last area.
+        fmap.areas.remove(0);
+        fmap.areas.last_mut().unwrap().size = 0x100;
+        let mut result = Vec::new();
+        if let Err(e) = dump_human_readable(
+            &fmap,
+            0,
+            &TreeOptions {
+                show_gaps: true,
+                ..Default::default()
+            },
+            &mut result,
+        ) {
+            return Err(format!("dump_human_readable() failed with error: {e}"));
+        }
+        let expected = r#"# name          start       end         size
+-entire flash-  00000000    00000400    00000400
+  [UNUSED]      00000000    00000080    00000080
+  normal        00000080    00000100    00000080
+  fallback      00000100    00000200    00000100
+  data          00000200    00000300    00000100
+  [UNUSED]      00000300    00000400    00000100
+"#;
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_hexdump() -> Result<(), Box<dyn Error>> {
+        let mut image = vec![0u8; 0x40];
+        image[0x10..0x24].copy_from_slice(b"Hello, FlashMap!\x00\x01\x7f\xff");
+        let fmap = fmap::FMap {
+            size: 0x40,
+            areas: vec![
+                fmap::FMapArea {
+                    name: "TEXT".to_string(),
+                    offset: 0x10,
+                    size: 0x14,
+                    flags: fmap::FMapFlags::empty(),
+                },
+                fmap::FMapArea {
+                    name: "EMPTY".to_string(),
+                    offset: 0x20,
+                    size: 0,
+                    flags: fmap::FMapFlags::empty(),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let mut result = Vec::new();
+        dump_hexdump(
+            &mut std::io::Cursor::new(&image),
+            &fmap,
+            "TEXT",
+            0,
+            &mut result,
+        )?;
+        let expected = "\
+00000010  48 65 6c 6c 6f 2c 20 46  6c 61 73 68 4d 61 70 21  |Hello, FlashMap!|
+00000020  00 01 7f ff                                       |....|
+";
+        assert_eq!(String::from_utf8(result)?, expected);
+
+        let mut result = Vec::new();
+        dump_hexdump(
+            &mut std::io::Cursor::new(&image),
+            &fmap,
+            "EMPTY",
+            0,
+            &mut result,
+        )?;
+        assert!(result.is_empty());
+        assert!(dump_hexdump(
+            &mut std::io::Cursor::new(&image),
+            &fmap,
+            "NONE",
+            0,
+            &mut result
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sorted_areas_of_complex_fmap() {
+        let fmap = example_complex_fmap();
+        let names = fmap
+            .sorted_areas()
+            .iter()
+            .map(|ar| ar.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names[..10],
+            [
+                "SI_ALL",
+                "SI_DESC",
+                "SI_ME",
+                "CSE_LAYOUT",
+                "CSE_RO",
+                "CSE_DATA",
+                "CSE_RW",
+                "SI_BIOS",
+                "RW_SECTION_A",
+                "VBLOCK_A"
+            ]
+        );
+        // Same placement is ordered by name.
+        let shared = names.iter().position(|&n| n == "SHARED_DATA").unwrap();
+        assert_eq!(names[shared + 1], "SHARED_DATA_DUPLICATE");
+        assert_eq!(names[names.len() - 2..], ["COREBOOT", "COREBOOT_OVERLAP"]);
+    }
+
+    #[test]
+    fn test_self_area_of_complex_fmap() {
+        let fmap = example_complex_fmap();
+        // RO_SECTION starts at the same offset.
+        assert_eq!(
+            fmap.self_area(0x1804000).map(|ar| ar.name.as_str()),
+            Some("FMAP")
+        );
+        // Smallest of areas starting at given offset, if none is named FMAP.
+        assert_eq!(
+            fmap.self_area(0x1000).map(|ar| ar.name.as_str()),
+            Some("CSE_LAYOUT")
+        );
+        assert!(fmap.self_area(0x1804001).is_none());
+    }
+
+    #[test]
+    fn test_file_stamp() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let path = workdir.join("image.bin");
+        assert_eq!(file_stamp(&path), None);
+
+        std::fs::write(&path, [0u8; 4])?;
+        let stamp = file_stamp(&path);
+        assert!(stamp.is_some());
+        assert_eq!(file_stamp(&path), stamp);
+        std::fs::write(&path, [0u8; 8])?;
+        assert_ne!(file_stamp(&path), stamp);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_self_hash() -> Result<(), Box<dyn Error>> {
+        let mut d = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/example_fmap.bin");
+        let mut data = std::fs::read(&d)?;
+        let (fmap, offset) = fmap::FMap::from_bytes(&data)?;
+        let hash = self_hash(&mut std::io::Cursor::new(&data), &fmap, offset)?;
+        assert_eq!(
+            hash,
+            "b62d95017a37e574b46411cd21c63b54cc7c97ad4676fac21e1ffee9c5e2a366"
+        );
+
+        // Area data does not affect the hash, FMap entries do.
+        data[0] ^= 0xff;
+        assert_eq!(
+            self_hash(&mut std::io::Cursor::new(&data), &fmap, offset)?,
+            hash
+        );
+        let flags_offset = offset + fmap::HEADER_SIZE + fmap::AREA_SIZE - 2;
+        data[flags_offset] ^= 0x08;
+        assert_ne!(
+            self_hash(&mut std::io::Cursor::new(&data), &fmap, offset)?,
+            hash
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_raw() -> Result<(), Box<dyn Error>> {
+        let mut image = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        image.push("resources/test/example_fmap.bin");
+        let data = std::fs::read(&image)?;
+        let args = DumpFmapArgs {
+            image,
+            raw: true,
+            ..Default::default()
+        };
+        let mut result = Vec::new();
+        dump(&args, &mut result)?;
+        let size = fmap::HEADER_SIZE + 4 * fmap::AREA_SIZE;
+        assert_eq!(result, &data[0x200..0x200 + size]);
+        assert_eq!(fmap::FMap::from_bytes(&result)?.0.areas.len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_areas() {
+        let fmap = example_complex_fmap();
+        let findings = lint_areas(&fmap)
+            .into_iter()
+            .map(|(a, b)| (fmap.areas[a].name.as_str(), fmap.areas[b].name.as_str()))
+            .collect_vec();
+        assert_eq!(findings, [("COREBOOT_OVERLAP", "COREBOOT")]);
+
+        assert!(lint_areas(&example_fmap()).is_empty());
+    }
+
+    #[test]
+    fn test_lint_fix() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let mut fmap = example_fmap();
+        fmap.areas.push(fmap::FMapArea {
+            name: "data_bad".to_string(),
+            offset: 0x201,
+            size: 0x1fe,
+            flags: fmap::FMapFlags::empty(),
+        });
+        let mut data = vec![0xffu8; 0x400];
+        let mut encoded = Vec::new();
+        fmap.write(&mut encoded)?;
+        data[0x200..0x200 + encoded.len()].copy_from_slice(&encoded);
+        let image = workdir.join("image.bin");
+        std::fs::write(&image, &data)?;
+
+        let args = DumpFmapArgs {
+            image: image.clone(),
+            lint: true,
+            fix: true,
+            output: Some(workdir.join("fixed.bin")),
+            ..Default::default()
+        };
+        dump(&args, Vec::new())?;
+
+        let (fixed, offset) = fmap::FMap::from_bytes(&std::fs::read(workdir.join("fixed.bin"))?)?;
+        assert_eq!(offset, 0x200);
+        fmap.areas[4].offset = 0x200;
+        fmap.areas[4].size = 0x200;
+        assert_eq!(fixed, fmap);
+        assert_eq!(std::fs::read(&image)?, data);
+
+        // Names which are not valid UTF-8 and unknown flag bits of any entry are kept.
+        let bootblock = 0x200 + fmap::HEADER_SIZE;
+        data[bootblock + 8] = 0xff;
+        data[bootblock + fmap::AREA_SIZE - 2] = 0x30;
+        std::fs::write(&image, &data)?;
+        dump(&args, Vec::new())?;
+        let fixed = std::fs::read(workdir.join("fixed.bin"))?;
+        let suspect = bootblock + 4 * fmap::AREA_SIZE;
+        assert_eq!(fixed[..suspect], data[..suspect]);
+        assert_eq!(fixed[suspect..suspect + 8], [0, 2, 0, 0, 0, 2, 0, 0]);
+        assert_eq!(fixed[suspect + 8..], data[suspect + 8..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_show_hit_parsable() -> Result<(), Box<dyn Error>> {
+        let mut image = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        image.push("resources/test/example_fmap.bin");
+        let mut args = DumpFmapArgs {
+            image,
+            parsable: true,
+            show_hit: true,
+            ..Default::default()
+        };
+        let mut result = Vec::new();
+        dump(&args, &mut result)?;
+        let expected = "\
+# hit at 0x200
+bootblock 0 128
+normal 128 128
+fallback 256 256
+data 512 512
+";
+        assert_eq!(String::from_utf8(result)?, expected);
+
+        // Default format has its own hit line.
+        args.parsable = false;
+        let mut result = Vec::new();
+        dump(&args, &mut result)?;
+        assert!(String::from_utf8(result)?.starts_with("hit at 0x200\nfmap_signature:"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_zero_areas() -> Result<(), Box<dyn Error>> {
+        let mut image = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        image.push("resources/test/zero_areas_fmap.bin");
+        let mut args = DumpFmapArgs {
+            image,
+            human_readable: true,
+            ..Default::default()
+        };
+        let mut result = Vec::new();
+        dump(&args, &mut result)?;
+        assert_eq!(
+            String::from_utf8(result)?,
+            "# name  start       end         size\n"
+        );
+
+        args.human_readable = false;
+        args.parsable = true;
+        let mut result = Vec::new();
+        dump(&args, &mut result)?;
+        assert!(result.is_empty());
+
+        args.parsable = false;
+        let mut result = Vec::new();
+        dump(&args, &mut result)?;
+        assert!(String::from_utf8(result)?.contains("fmap_nareas:     0\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_fmd() -> Result<(), String> {
+        init();
+        let mut fmap = example_complex_fmap();
+        if dump_fmd(&fmap, &mut Vec::new()).is_ok() {
+            return Err("dump_fmd() expected to fail on overlapping areas".into());
+        }
+
+        fmap.areas.retain(|ar| ar.name != "COREBOOT_OVERLAP");
+        let mut result = Vec::new();
+        if let Err(e) = dump_fmd(&fmap, &mut result) {
+            return Err(format!("dump_fmd() failed with error: {e}"));
+        }
+        let expected = r#"FLASH@0x0 0x2000000 {
+	SI_ALL@0x0 0x500000 {
+		SI_DESC@0x0 0x1000
+		SI_ME@0x1000 0x4ff000 {
+			CSE_LAYOUT@0x0 0x2000
+			CSE_RO@0x2000 0x19a000
+			CSE_DATA@0x19c000 0x69000
+			CSE_RW@0x205000 0x2f0000
+		}
+	}
+	SI_BIOS@0x500000 0x1b00000 {
+		RW_SECTION_A@0x0 0x800000 {
+			VBLOCK_A@0x0 0x10000
+			FW_MAIN_A@0x10000 0x7effc0
+			RW_FWID_A@0x7fffc0 0x40
+		}
+		RW_LEGACY@0x800000 0x200000
+		RW_MISC@0xa00000 0x100000 {
+			UNIFIED_MRC_CACHE(PRESERVE)@0x0 0x20000 {
+				RECOVERY_MRC_CACHE@0x0 0x10000
+				RW_MRC_CACHE@0x10000 0x10000
+			}
+			RW_ELOG(PRESERVE)@0x20000 0x4000
+			RW_SHARED@0x24000 0x4000 {
+				SHARED_DATA@0x0 0x2000
+				# SHARED_DATA_DUPLICATE duplicates SHARED_DATA
+				VBLOCK_DEV@0x2000 0x2000
+			}
+			RW_SPD_CACHE(PRESERVE)@0x28000 0x1000
+			RW_VPD(PRESERVE)@0x29000 0x2000
+			RW_NVRAM(PRESERVE)@0x2b000 0x6000
+		}
+		RW_SECTION_B@0xb00000 0x800000 {
+			VBLOCK_B@0x0 0x10000
+			FW_MAIN_B@0x10000 0x7effc0
+			RW_FWID_B@0x7fffc0 0x40
+		}
+		WP_RO@0x1300000 0x800000 {
+			RO_VPD(PRESERVE)@0x0 0x4000
+			RO_SECTION@0x4000 0x7fc000 {
+				FMAP@0x0 0x800
+				RO_FRID@0x800 0x40
+				GBB@0x1000 0x70000
+				COREBOOT@0x71000 0x78b000
+			}
+		}
+	}
+}
+"#;
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_self() {
+        let mut fmap = fmap::FMap {
+            size: 0x1000,
+            areas: vec![
+                fmap::FMapArea {
+                    name: "RO".to_string(),
+                    offset: 0,
+                    size: 0x800,
+                    flags: fmap::FMapFlags::empty(),
+                },
+                fmap::FMapArea {
+                    name: "FMAP".to_string(),
+                    offset: 0x800,
+                    size: 0x100,
+                    flags: fmap::FMapFlags::empty(),
+                },
+            ],
+            ..Default::default()
+        };
+        assert!(check_self(&fmap, 0x800).is_empty());
+        assert_eq!(check_self(&fmap, 0x900).len(), 1);
+
+        // Header with two areas does not fit.
+        fmap.areas[1].size = 0x80;
+        assert_eq!(check_self(&fmap, 0x800).len(), 1);
+        assert_eq!(check_self(&fmap, 0x900).len(), 2);
+    }
+
+    #[test]
+    fn test_dump_full_parsable() -> Result<(), String> {
+        let fmap = fmap::FMap {
+            size: 0x400,
+            areas: vec![
+                fmap::FMapArea {
+                    name: "RO".to_string(),
+                    offset: 0,
+                    size: 0x200,
+                    flags: fmap::FMapFlags::Static
+                        | fmap::FMapFlags::Compressed
+                        | fmap::FMapFlags::RO,
+                },
+                fmap::FMapArea {
+                    name: "RW".to_string(),
+                    offset: 0x200,
+                    size: 0x200,
+                    flags: fmap::FMapFlags::empty(),
+                },
+            ],
+            ..Default::default()
+        };
+        let mut result = Vec::new();
+        if let Err(e) = dump_full_parsable(&fmap, 0, &mut result) {
+            return Err(format!("dump_full_parsable() failed with error: {e}"));
+        }
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "RO 0 512 STATIC,COMPRESSED,RO\nRW 512 512 NONE\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_search_options() -> Result<(), Box<dyn Error>> {
+        let mut image = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        image.push("resources/test/example_fmap.bin");
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let mut args = DumpFmapArgs {
+            image: image.clone(),
+            extract: true,
+            params: vec![("data".to_string(), workdir.join("data.bin"))],
+            ..Default::default()
+        };
+        run_command(&args)?;
+
+        // FlashMap at 0x200 is not aligned to 0x400, so it is not found.
+        args.align = Some(0x400);
+        assert!(matches!(
+            run_command(&args)
+                .unwrap_err()
+                .downcast_ref::<fmap::FMapError>(),
+            Some(fmap::FMapError::NotFound)
+        ));
+
+        // Search options not supported by extraction are rejected.
+        let param = format!("data:{}", workdir.join("data.bin"));
+        let command =
+            DumpFmapArgs::augment_args(clap::Command::new("dump_fmap")).disable_help_flag(true);
+        for option in [
+            "--search-from=0x100",
+            "--fmap-offset=0x200",
+            "--ifd",
+            "--strict-flags",
+            "--stats",
+        ] {
+            let error = command
+                .clone()
+                .try_get_matches_from(["dump_fmap", "-x", image.as_str(), option, &param])
+                .unwrap_err();
+            assert_eq!(
+                error.kind(),
+                clap::error::ErrorKind::ArgumentConflict,
+                "{option}"
+            );
+        }
+
+        Ok(())
+    }
 }
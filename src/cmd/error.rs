@@ -0,0 +1,138 @@
+use std::error::Error;
+
+use crate::fmap::FMapError;
+
+/* Process exit codes. 0 is reserved for success. */
+pub const EXIT_OTHER: i32 = 1;
+pub const EXIT_NOT_FOUND: i32 = 2;
+pub const EXIT_CORRUPTED_HEADER: i32 = 3;
+pub const EXIT_INCORRECT_VERSION: i32 = 4;
+pub const EXIT_IO_ERROR: i32 = 5;
+pub const EXIT_AREA_NOT_FOUND: i32 = 6;
+pub const EXIT_AREA_OUT_OF_BOUNDS: i32 = 7;
+pub const EXIT_INVALID_RELOCATION: i32 = 8;
+pub const EXIT_AREA_OVERLAP: i32 = 9;
+pub const EXIT_UNKNOWN_FLAGS: i32 = 10;
+
+/// Returns stable kind name and distinct process exit code of error returned by a command.
+pub fn error_kind(e: &(dyn Error + 'static)) -> (&'static str, i32) {
+    if let Some(e) = e.downcast_ref::<FMapError>() {
+        let code = match e {
+            FMapError::NotFound => EXIT_NOT_FOUND,
+            FMapError::CorruptedHeader => EXIT_CORRUPTED_HEADER,
+            FMapError::IncorrectVersion(_, _) => EXIT_INCORRECT_VERSION,
+            FMapError::IOError { .. } => EXIT_IO_ERROR,
+            FMapError::AreaNotFound(_) => EXIT_AREA_NOT_FOUND,
+            FMapError::AreaOutOfBounds(_) => EXIT_AREA_OUT_OF_BOUNDS,
+            FMapError::InvalidRelocation(_) => EXIT_INVALID_RELOCATION,
+            FMapError::AreaOverlap(_, _) => EXIT_AREA_OVERLAP,
+            FMapError::UnknownFlags(_, _) => EXIT_UNKNOWN_FLAGS,
+            // Report the first failure, so single kind of failure has always the same code.
+            FMapError::Multiple(errors) => match errors.first() {
+                Some(first) => error_kind(first).1,
+                None => EXIT_OTHER,
+            },
+        };
+        (e.kind(), code)
+    } else if e.is::<std::io::Error>() {
+        ("IOError", EXIT_IO_ERROR)
+    } else {
+        ("Other", EXIT_OTHER)
+    }
+}
+
+/// Machine-readable report of error returned by a command, e.g.
+/// `{"error": "flash map not found", "kind": "NotFound"}`.
+pub fn error_json(e: &(dyn Error + 'static)) -> serde_json::Value {
+    serde_json::json!({ "error": e.to_string(), "kind": error_kind(e).0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boxed(e: impl Error + 'static) -> Box<dyn Error> {
+        Box::new(e)
+    }
+
+    #[test]
+    fn test_error_kind() {
+        let cases: Vec<(Box<dyn Error>, &str, i32)> = vec![
+            (boxed(FMapError::NotFound), "NotFound", 2),
+            (boxed(FMapError::CorruptedHeader), "CorruptedHeader", 3),
+            (
+                boxed(FMapError::IncorrectVersion(2, 0)),
+                "IncorrectVersion",
+                4,
+            ),
+            (
+                boxed(FMapError::from(std::io::Error::from(
+                    std::io::ErrorKind::UnexpectedEof,
+                ))),
+                "IOError",
+                5,
+            ),
+            (
+                boxed(std::io::Error::from(std::io::ErrorKind::NotFound)),
+                "IOError",
+                5,
+            ),
+            (
+                boxed(FMapError::AreaNotFound("A".to_string())),
+                "AreaNotFound",
+                6,
+            ),
+            (
+                boxed(FMapError::AreaOutOfBounds("A".to_string())),
+                "AreaOutOfBounds",
+                7,
+            ),
+            (
+                boxed(FMapError::InvalidRelocation(-1)),
+                "InvalidRelocation",
+                8,
+            ),
+            (
+                boxed(FMapError::AreaOverlap("A".to_string(), "B".to_string())),
+                "AreaOverlap",
+                9,
+            ),
+            (
+                boxed(FMapError::UnknownFlags("A".to_string(), 0x10)),
+                "UnknownFlags",
+                10,
+            ),
+            (
+                boxed(FMapError::Multiple(vec![
+                    FMapError::AreaNotFound("A".to_string()),
+                    FMapError::AreaOutOfBounds("B".to_string()),
+                ])),
+                "Multiple",
+                6,
+            ),
+            ("some error".into(), "Other", 1),
+        ];
+        for (e, kind, code) in cases.iter() {
+            assert_eq!(error_kind(e.as_ref()), (*kind, *code), "{e}");
+        }
+    }
+
+    #[test]
+    fn test_error_json() {
+        assert_eq!(
+            error_json(boxed(FMapError::AreaNotFound("RO_VPD".to_string())).as_ref()),
+            serde_json::json!({
+                "error": "flash map area not found: RO_VPD",
+                "kind": "AreaNotFound",
+            })
+        );
+        let e: Box<dyn Error> = "Only one section can be written to stdout".into();
+        assert_eq!(
+            error_json(e.as_ref()),
+            serde_json::json!({
+                "error": "Only one section can be written to stdout",
+                "kind": "Other",
+            })
+        );
+    }
+}
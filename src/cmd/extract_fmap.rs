@@ -1,76 +1,985 @@
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fs::{self, File};
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{stdout, BufWriter, Read, Seek, SeekFrom, Write};
 
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use clap::{Args, ValueHint};
-use log::error;
+use log::{error, warn};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
-use crate::{cmd::common, fmap};
+use crate::{
+    cmd::{archive, common, crc_footer, image, ops},
+    fmap,
+};
 
-#[derive(Args)]
+#[derive(Args, Default)]
 pub struct ExtractFmapArgs {
     #[arg(required = true, index = 1, value_hint = ValueHint::FilePath, value_parser = common::file_exists_validator)]
     /// Firmware image path.
     pub(in crate::cmd) image: Utf8PathBuf,
 
-    #[arg(required = true, index = 2, trailing_var_arg = true, value_parser = common::area_to_file_mapping_param_valid)]
+    #[arg(required_unless_present_any = ["ops", "archive"], index = 2, trailing_var_arg = true, value_parser = common::area_mapping_or_name_param_valid)]
     /// List of mappings from FlashMap section to file in format SECTION:FILE.
+    /// Use `-` as FILE to write the section to stdout. If only SECTION is given,
+    /// FILE is named after it, e.g. FW_MAIN_A.bin. See --lowercase, --prefix and --suffix.
+    /// SECTION can also be raw byte range `@OFFSET+SIZE` or `@START-END`, e.g. @0x1000+0x200,
+    /// or `@fmap` for FlashMap header and area entries found in the image.
+    /// Append `@OFFSET` to SECTION to select one of areas sharing the name, e.g. FW_MAIN_A@0x510000.
+    /// Append `=sha256:HEX` to FILE to fail if extracted data has different digest.
     /// Example: FW_MAIN_A:fw_main_a.bin
     pub(in crate::cmd) params: Vec<(String, Utf8PathBuf)>,
+
+    #[arg(long, action)]
+    /// Append footer with CRC32 of the area content to each extracted file.
+    pub(in crate::cmd) with_crc: bool,
+
+    #[arg(long, value_hint = ValueHint::FilePath, value_parser = common::file_exists_validator)]
+    /// JSON file with list of operations to execute in addition to mappings, e.g.
+    /// [{"area": "FW_MAIN_A", "file": "a.bin", "range": [0, 1024], "index": 0}]
+    pub(in crate::cmd) ops: Option<Utf8PathBuf>,
+
+    #[arg(long, default_value = "0", value_parser = common::decimal_or_hex_validator_u32)]
+    /// Flash offset of the first byte of the image, for images being partial flash dumps.
+    /// Subtracted from area offsets, similarly to how `fmap_base` places flash in memory.
+    pub(in crate::cmd) base: u32,
+
+    #[arg(long, action)]
+    /// Treat SECTION as glob pattern (`*`, `?`) matched against all area names.
+    /// `%s` in FILE is replaced with the matched area name. Example: RW_SECTION_*:%s.bin
+    pub(in crate::cmd) glob: bool,
+
+    #[arg(long, value_hint = ValueHint::FilePath, value_parser = common::archive_path_validator)]
+    /// Write extracted areas as entries named after the areas into single .tar or .zip archive.
+    /// FILE of mappings is ignored. All areas are extracted if no mappings are given.
+    pub(in crate::cmd) archive: Option<Utf8PathBuf>,
+
+    #[arg(long, action)]
+    /// Lowercase names of files named after sections.
+    pub(in crate::cmd) lowercase: bool,
+
+    #[arg(long, value_name = "PREFIX")]
+    /// Prepend PREFIX, e.g. directory, to names of files named after sections.
+    pub(in crate::cmd) prefix: Option<String>,
+
+    #[arg(long, value_name = "SUFFIX")]
+    /// Append SUFFIX instead of `.bin` to names of files named after sections.
+    pub(in crate::cmd) suffix: Option<String>,
+
+    #[arg(long, action)]
+    /// Warn about areas not found in FlashMap instead of failing.
+    pub(in crate::cmd) ignore_missing: bool,
+
+    #[arg(long, value_name = "NAME")]
+    /// Do not extract area with given name, e.g. when extracting all areas. Can be repeated.
+    pub(in crate::cmd) exclude: Vec<String>,
+
+    #[arg(long, action)]
+    /// Fail if more than one FlashMap area has the same name, instead of warning.
+    pub(in crate::cmd) strict_names: bool,
+
+    #[arg(long, value_name = "SIZE", value_parser = common::decimal_or_hex_validator_u64)]
+    /// Search for FlashMap only within first SIZE bytes of the image.
+    pub(in crate::cmd) max_scan: Option<u64>,
+
+    #[arg(long, value_name = "N", value_parser = common::alignment_validator)]
+    /// Check only offsets aligned to N bytes, a power of two, when searching for FlashMap.
+    pub(in crate::cmd) align: Option<usize>,
+
+    #[arg(long, action)]
+    /// Create missing parent directories of output files.
+    pub(in crate::cmd) mkdir: bool,
+
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath)]
+    /// Write JSON list of extracted areas with their offsets, sizes, outputs and SHA-256 of data.
+    pub(in crate::cmd) manifest: Option<Utf8PathBuf>,
+}
+
+/// Record of single extracted area.
+#[derive(Debug, PartialEq, Serialize)]
+struct ManifestEntry {
+    area: String,
+    offset: u32,
+    size: u32,
+    output: Utf8PathBuf,
+    sha256: String,
+}
+
+fn write_manifest(path: &Utf8Path, entries: &[ManifestEntry]) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, entries)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reader computing SHA-256 of all data read through it.
+struct HashReader<R: Read> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Copies `size` bytes from `reader` to `writer` without buffering whole area in memory.
+/// Appends CRC footer if `with_crc` is set. Returns SHA-256 of area data.
+fn copy_area(
+    reader: &mut impl Read,
+    size: u32,
+    mut writer: impl Write,
+    with_crc: bool,
+) -> std::io::Result<String> {
+    let mut area = HashReader {
+        inner: reader.take(size as u64),
+        hasher: Sha256::new(),
+    };
+    let copied = if with_crc {
+        let mut crc_writer = crc_footer::Writer::new(&mut writer);
+        let copied = std::io::copy(&mut area, &mut crc_writer)?;
+        crc_writer.finish()?;
+        copied
+    } else {
+        std::io::copy(&mut area, &mut writer)?
+    };
+    if copied != size as u64 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("Only {copied} of {size} bytes available"),
+        ));
+    }
+    writer.flush()?;
+    Ok(common::hex(&area.hasher.finalize()))
+}
+
+/// Path of file for area `name` extracted without explicit output file.
+fn auto_file_name(name: &str, args: &ExtractFmapArgs) -> Utf8PathBuf {
+    let name = if args.lowercase {
+        name.to_lowercase()
+    } else {
+        name.to_string()
+    };
+    Utf8PathBuf::from(format!(
+        "{}{name}{}",
+        args.prefix.as_deref().unwrap_or(""),
+        args.suffix.as_deref().unwrap_or(".bin")
+    ))
+}
+
+/// Compares SHA-256 of extracted data with digest expected by `op`, if any. Logs mismatch.
+fn digest_matches(op: &ops::Op, sha256: &str) -> bool {
+    match &op.sha256 {
+        Some(expected) if !expected.eq_ignore_ascii_case(sha256) => {
+            error!(
+                "SHA-256 of area '{}' is {sha256}, expected {expected}",
+                op.area
+            );
+            false
+        }
+        _ => true,
+    }
 }
 
 pub fn run_command(args: &ExtractFmapArgs) -> Result<(), Box<dyn Error>> {
-    let mut input_file = File::open(&args.image)?;
-    let (fmap, _) = fmap::FMap::find_fmap(&mut input_file)?;
-    let mut errors_encountered = false;
-
-    for (area_name, output_path) in args.params.iter() {
-        let ar = match fmap.get(area_name) {
-            None => {
-                error!("FlashMap area '{}' not found", area_name);
-                errors_encountered = true;
+    let mut ops = ops::from_params(&args.params);
+    if let Some(path) = &args.ops {
+        ops.append(&mut ops::read_ops_file(path)?);
+    }
+
+    let mut input_file = image::open(&args.image)?;
+    let (fmap, fmap_offset) = match fmap::FMap::find_fmap_with_opts(
+        &mut input_file,
+        &fmap::SearchOptions {
+            max_scan: args.max_scan,
+            min_align: args.align,
+            ..Default::default()
+        },
+    ) {
+        Ok(v) => v,
+        // Offset ranges can be extracted from images without valid FlashMap.
+        Err(fmap::FMapError::NotFound)
+            if !ops.is_empty()
+                && ops
+                    .iter()
+                    .all(|op| op.area.starts_with('@') && op.area != common::FMAP_SECTION) =>
+        {
+            (fmap::FMap::default(), 0)
+        }
+        Err(e) => return Err(e.into()),
+    };
+    common::check_duplicate_names(&fmap, args.strict_names)?;
+    if args.archive.is_some() && ops.is_empty() {
+        ops = fmap
+            .areas
+            .iter()
+            .map(|ar| ops::Op {
+                area: ar.name.clone(),
+                ..Default::default()
+            })
+            .collect();
+    }
+    ops::check_areas_present(&ops, &fmap)?;
+    if args.glob {
+        ops = ops::expand_globs(ops, &fmap)?;
+    }
+    ops.retain(|op| !args.exclude.contains(&op.area));
+    if args.archive.is_none() {
+        for op in ops.iter_mut() {
+            // Raw ranges have no name to derive file name from.
+            if op.file.is_none() && !op.erase && !op.area.starts_with('@') {
+                op.file = Some(auto_file_name(&op.area, args));
+            }
+        }
+    }
+    if ops
+        .iter()
+        .filter(|op| op.file.as_deref() == Some("-".into()))
+        .count()
+        > 1
+    {
+        return Err("Only one section can be written to stdout".into());
+    }
+
+    let image_size = image::size(&mut input_file)?;
+    let mut failures = Vec::new();
+    let mut area_errors = Vec::new();
+    let mut archive_entries: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut manifest = Vec::new();
+
+    for op in ops.iter() {
+        let area_name = &op.area;
+        if op.erase || (op.file.is_none() && args.archive.is_none()) {
+            error!("Extraction of area '{}' requires output file", area_name);
+            failures.push((area_name.clone(), "no output file"));
+            continue;
+        }
+        let (offset, size) = if op.area == common::FMAP_SECTION {
+            let size = fmap::HEADER_SIZE + fmap.areas.len() * fmap::AREA_SIZE;
+            // Flash offset of FlashMap found in the image starting at --base.
+            match u32::try_from(fmap_offset)
+                .ok()
+                .and_then(|offset| args.base.checked_add(offset))
+            {
+                None => {
+                    error!(
+                        "FlashMap at {:#x} does not fit in 32-bit flash offsets with base {:#x}",
+                        fmap_offset, args.base
+                    );
+                    area_errors.push(fmap::FMapError::AreaOutOfBounds(area_name.clone()));
+                    failures.push((area_name.clone(), "out of bounds"));
+                    continue;
+                }
+                Some(offset) => (offset, size as u32),
+            }
+        } else if let Some(range) = op.area.strip_prefix('@') {
+            // Raw offset range does not need FlashMap.
+            match common::offset_range_validator(range) {
+                Err(e) => {
+                    error!("{e}");
+                    failures.push((area_name.clone(), "invalid range"));
+                    continue;
+                }
+                Ok(v) => v,
+            }
+        } else {
+            let ar = match op.find_area(&fmap) {
+                None if args.ignore_missing => {
+                    warn!("FlashMap area '{}' not found, skipping", area_name);
+                    continue;
+                }
+                None => {
+                    error!("FlashMap area '{}' not found", area_name);
+                    area_errors.push(fmap::FMapError::AreaNotFound(area_name.clone()));
+                    failures.push((area_name.clone(), "not found"));
+                    continue;
+                }
+                Some(v) => v,
+            };
+
+            // Verify area
+            let issues = fmap.area_issues(ar);
+            if issues
+                .iter()
+                .any(|i| matches!(i, fmap::LayoutIssue::ZeroSize { .. }))
+            {
+                error!("Area '{}' has zero size", area_name);
+                continue;
+            }
+            if issues
+                .iter()
+                .any(|i| matches!(i, fmap::LayoutIssue::OutOfBounds { .. }))
+            {
+                error!("Area '{}' stretches beyond image", area_name);
+                area_errors.push(fmap::FMapError::AreaOutOfBounds(area_name.clone()));
+                failures.push((area_name.clone(), "out of bounds"));
                 continue;
             }
-            Some(v) => v,
+            match op.target_range(ar) {
+                Err(e) => {
+                    error!("{e}");
+                    failures.push((area_name.clone(), "invalid range"));
+                    continue;
+                }
+                Ok(v) => v,
+            }
         };
 
-        // Verify area
-        if ar.size == 0 {
-            error!("Area '{}' has zero size", area_name);
+        let image_offset = match common::offset_in_image(offset, size, args.base, image_size) {
+            Err(e) => {
+                error!("Area '{area_name}': {e}");
+                area_errors.push(fmap::FMapError::AreaOutOfBounds(area_name.clone()));
+                failures.push((area_name.clone(), "out of bounds"));
+                continue;
+            }
+            Ok(v) => v,
+        };
+
+        if let Err(error) = input_file.seek(SeekFrom::Start(image_offset)) {
+            error!(
+                "Unable to read from image file '{}' at {}. Error: {:?}",
+                args.image, offset, error
+            );
+            failures.push((area_name.clone(), "read error"));
             continue;
         }
-        if ar.offset + ar.size > fmap.size {
-            error!("Area '{}' stretches beyond image", area_name);
+
+        let output_path = match (&args.archive, &op.file) {
+            (None, Some(v)) => v,
+            _ => {
+                if archive_entries.iter().any(|(name, _)| name == area_name) {
+                    warn!("Area '{}' already archived, skipping duplicate", area_name);
+                    continue;
+                }
+                let mut area_buf = Vec::new();
+                let sha256 = match copy_area(&mut input_file, size, &mut area_buf, args.with_crc) {
+                    Err(error) => {
+                        error!(
+                            "Unable to read from image file '{}'. Error: {:?}",
+                            args.image, error
+                        );
+                        failures.push((area_name.clone(), "read error"));
+                        continue;
+                    }
+                    Ok(v) => v,
+                };
+                if !digest_matches(op, &sha256) {
+                    failures.push((area_name.clone(), "digest mismatch"));
+                    continue;
+                }
+                archive_entries.push((area_name.clone(), area_buf));
+                manifest.push(ManifestEntry {
+                    area: area_name.clone(),
+                    offset,
+                    size,
+                    output: args.archive.clone().unwrap_or_default(),
+                    sha256,
+                });
+                continue;
+            }
+        };
+
+        // Stream area directly to the output, so memory use does not depend on area size.
+        let sha256 = if output_path == "-" {
+            match copy_area(&mut input_file, size, stdout().lock(), args.with_crc) {
+                Err(error) => {
+                    error!("Unable to write to stdout. Error: {:?}", error);
+                    failures.push((area_name.clone(), "write error"));
+                    continue;
+                }
+                Ok(v) => v,
+            }
+        } else {
+            if let Some(parent) = output_path.parent().filter(|p| args.mkdir && *p != "") {
+                if let Err(error) = fs::create_dir_all(parent) {
+                    error!(
+                        "Unable to create directory '{}'. Error: {:?}",
+                        parent, error
+                    );
+                    failures.push((area_name.clone(), "write error"));
+                    continue;
+                }
+            }
+            match File::create(output_path)
+                .and_then(|f| copy_area(&mut input_file, size, BufWriter::new(f), args.with_crc))
+            {
+                Err(error) => {
+                    error!(
+                        "Unable to write to the file '{}'. Error: {:?}",
+                        output_path, error
+                    );
+                    // Do not leave partially extracted area behind.
+                    let _ = fs::remove_file(output_path);
+                    failures.push((area_name.clone(), "write error"));
+                    continue;
+                }
+                Ok(v) => v,
+            }
+        };
+        if !digest_matches(op, &sha256) {
+            // Do not leave unverified data behind.
+            if output_path != "-" {
+                let _ = fs::remove_file(output_path);
+            }
+            failures.push((area_name.clone(), "digest mismatch"));
             continue;
         }
+        manifest.push(ManifestEntry {
+            area: area_name.clone(),
+            offset,
+            size,
+            output: output_path.clone(),
+            sha256,
+        });
+    }
 
-        if let Err(error) = input_file.seek(SeekFrom::Start(ar.offset as u64)) {
-            error!(
-                "Unable to read from image file '{}' at {}. Error: {:?}",
-                args.image, ar.offset, error
-            );
+    if !failures.is_empty() {
+        error!("{}", ops::failure_summary(&failures, ops.len()));
+    }
+
+    if let Some(path) = &args.archive {
+        if let Err(error) = archive::write(path, &archive_entries) {
+            return Err(format!("Unable to write archive '{path}'. Error: {error}").into());
         }
+    }
 
-        let mut area_buf: Vec<u8> = vec![0u8; ar.size as usize];
-        if let Err(error) = input_file.read_exact(&mut area_buf) {
-            error!(
-                "Unable to read from image file '{}'. Error: {:?}",
-                args.image, error
-            );
+    fmap::FMapError::collect(area_errors)?;
+    if !failures.is_empty() {
+        return Err("Errors occured during extraction. Data might not be valid.".into());
+    }
+    if let Some(path) = &args.manifest {
+        if let Err(error) = write_manifest(path, &manifest) {
+            return Err(format!("Unable to write manifest '{path}'. Error: {error}").into());
         }
+    }
+    Ok(())
+}
 
-        if let Err(error) = fs::write(output_path, area_buf) {
-            error!(
-                "Unable to write to the file '{}'. Error: {:?}",
-                output_path, error
-            );
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_fmap_path() -> Utf8PathBuf {
+        let mut d = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/example_fmap.bin");
+        d
+    }
+
+    #[test]
+    fn test_multiple_stdout_outputs_rejected() -> Result<(), String> {
+        let args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            params: vec![
+                ("normal".to_string(), Utf8PathBuf::from("-")),
+                ("data".to_string(), Utf8PathBuf::from("-")),
+            ],
+            ..Default::default()
+        };
+        match run_command(&args) {
+            Ok(_) => Err("Extraction of two sections to stdout expected to fail".to_string()),
+            Err(_) => Ok(()),
         }
     }
 
-    if errors_encountered {
-        Err("Errors occured during extraction. Data might not be valid.".into())
-    } else {
+    #[test]
+    fn test_area_beyond_eof_fails() -> Result<(), Box<dyn Error>> {
+        // FMAP claiming 4KiB flash in 1KiB image with area placed past the end of file.
+        let mut image = vec![0xffu8; 0x400];
+        let mut header = Vec::new();
+        header.extend_from_slice(fmap::SIGNATURE);
+        header.extend_from_slice(&[1, 0]);
+        header.extend_from_slice(&0u64.to_le_bytes());
+        header.extend_from_slice(&0x1000u32.to_le_bytes());
+        header.extend_from_slice(&[0u8; fmap::NAME_LEN]);
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&0x300u32.to_le_bytes());
+        header.extend_from_slice(&0x200u32.to_le_bytes());
+        let mut name = [0u8; fmap::NAME_LEN];
+        name[..4].copy_from_slice(b"PAST");
+        header.extend_from_slice(&name);
+        header.extend_from_slice(&0u16.to_le_bytes());
+        image[..header.len()].copy_from_slice(&header);
+
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image_path = workdir.join("image.bin");
+        fs::write(&image_path, image)?;
+
+        let args = ExtractFmapArgs {
+            image: image_path,
+            params: vec![("PAST".to_string(), workdir.join("past.bin"))],
+            ..Default::default()
+        };
+        if run_command(&args).is_ok() {
+            return Err("Extraction of area beyond end of file expected to fail".into());
+        }
+        assert!(!workdir.join("past.bin").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_areas_reported_as_typed_errors() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            params: vec![
+                ("missing".to_string(), workdir.join("missing.bin")),
+                ("normal".to_string(), workdir.join("normal.bin")),
+                ("other".to_string(), workdir.join("other.bin")),
+            ],
+            ..Default::default()
+        };
+        let e = match run_command(&args) {
+            Ok(_) => return Err("Extraction of missing areas expected to fail".into()),
+            Err(e) => e,
+        };
+        match e.downcast_ref::<fmap::FMapError>() {
+            Some(fmap::FMapError::Multiple(errors)) => {
+                assert!(matches!(&errors[0], fmap::FMapError::AreaNotFound(n) if n == "missing"));
+                assert!(matches!(&errors[1], fmap::FMapError::AreaNotFound(n) if n == "other"));
+            }
+            _ => return Err(format!("Unexpected error: {e}").into()),
+        }
+        assert!(workdir.join("normal.bin").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_from_archive() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            archive: Some(workdir.join("areas.zip")),
+            exclude: vec!["normal".to_string(), "data".to_string()],
+            ..Default::default()
+        };
+        run_command(&args)?;
+
+        let archive = zip::ZipArchive::new(File::open(workdir.join("areas.zip"))?)?;
+        let names = archive.file_names().map(String::from).collect::<Vec<_>>();
+        assert_eq!(names, vec!["bootblock", "fallback"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignore_missing_areas() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let mut args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            params: vec![
+                ("missing".to_string(), workdir.join("missing.bin")),
+                ("normal".to_string(), workdir.join("normal.bin")),
+            ],
+            ignore_missing: true,
+            ..Default::default()
+        };
+        run_command(&args)?;
+        assert!(workdir.join("normal.bin").exists());
+        assert!(!workdir.join("missing.bin").exists());
+
+        // Other errors are still reported.
+        args.params = vec![
+            ("missing".to_string(), workdir.join("missing.bin")),
+            ("normal".to_string(), workdir.join("no_such_dir/normal.bin")),
+        ];
+        if run_command(&args).is_ok() {
+            return Err("Extraction to non-existent directory expected to fail".into());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_offset_range() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image = fs::read(example_fmap_path())?;
+
+        let mut args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            params: vec![
+                ("@0x90+0x20".to_string(), workdir.join("a.bin")),
+                ("@0x3f0-0x400".to_string(), workdir.join("b.bin")),
+            ],
+            ..Default::default()
+        };
+        run_command(&args)?;
+        assert_eq!(fs::read(workdir.join("a.bin"))?, &image[0x90..0xb0]);
+        assert_eq!(fs::read(workdir.join("b.bin"))?, &image[0x3f0..0x400]);
+
+        // Range beyond end of file.
+        args.params = vec![("@0x3f0+0x20".to_string(), workdir.join("c.bin"))];
+        if run_command(&args).is_ok() {
+            return Err("Extraction of range beyond end of file expected to fail".into());
+        }
+
+        // Image without FlashMap.
+        let no_fmap_path = workdir.join("no_fmap.bin");
+        fs::write(&no_fmap_path, &image[..0x200])?;
+        args.image = no_fmap_path;
+        args.params = vec![("@0x10+0x10".to_string(), workdir.join("d.bin"))];
+        run_command(&args)?;
+        assert_eq!(fs::read(workdir.join("d.bin"))?, &image[0x10..0x20]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_zero_areas() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let mut image = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        image.push("resources/test/zero_areas_fmap.bin");
+
+        let mut args = ExtractFmapArgs {
+            image,
+            params: vec![("data".to_string(), workdir.join("data.bin"))],
+            ..Default::default()
+        };
+        match run_command(&args) {
+            Ok(_) => return Err("Extraction from FlashMap without areas expected to fail".into()),
+            Err(e) => assert_eq!(e.to_string(), "No areas in FlashMap `ZERO'"),
+        }
+
+        args.params = vec![];
+        args.archive = Some(workdir.join("areas.zip"));
+        if run_command(&args).is_ok() {
+            return Err("Archiving FlashMap without areas expected to fail".into());
+        }
+
+        // Offset ranges do not need areas.
+        args.archive = None;
+        args.params = vec![("@0x0+0x10".to_string(), workdir.join("range.bin"))];
+        run_command(&args)?;
+        assert_eq!(fs::read(workdir.join("range.bin"))?, [0xffu8; 0x10]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_ops_file() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let ops_path = workdir.join("ops.json");
+        fs::write(
+            &ops_path,
+            format!(
+                r#"[{{"area": "normal", "file": "{0}/normal.bin"}},
+                    {{"area": "fallback", "file": "{0}/fallback_part.bin", "range": [16, 48]}}]"#,
+                workdir
+            ),
+        )?;
+
+        let args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            params: vec![],
+            ops: Some(ops_path),
+            ..Default::default()
+        };
+        run_command(&args)?;
+
+        let image = fs::read(example_fmap_path())?;
+        assert_eq!(fs::read(workdir.join("normal.bin"))?, &image[0x80..0x100]);
+        assert_eq!(
+            fs::read(workdir.join("fallback_part.bin"))?,
+            &image[0x110..0x130]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_from_partial_dump_with_base() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image = fs::read(example_fmap_path())?;
+        // Partial dump starting at "fallback" area.
+        let partial_path = workdir.join("partial.bin");
+        fs::write(&partial_path, &image[0x100..])?;
+
+        let mut args = ExtractFmapArgs {
+            image: partial_path,
+            params: vec![
+                ("fallback".to_string(), workdir.join("fallback.bin")),
+                ("data".to_string(), workdir.join("data.bin")),
+            ],
+            base: 0x100,
+            ..Default::default()
+        };
+        run_command(&args)?;
+        assert_eq!(
+            fs::read(workdir.join("fallback.bin"))?,
+            &image[0x100..0x200]
+        );
+        assert_eq!(fs::read(workdir.join("data.bin"))?, &image[0x200..0x400]);
+
+        // Area placed before the partial dump.
+        args.params = vec![("normal".to_string(), workdir.join("normal.bin"))];
+        if run_command(&args).is_ok() {
+            return Err("Extraction of area placed before base expected to fail".into());
+        }
+
+        // Area placed beyond the partial dump.
+        args.base = 0;
+        args.params = vec![("data".to_string(), workdir.join("data2.bin"))];
+        if run_command(&args).is_ok() {
+            return Err("Extraction of area placed beyond image expected to fail".into());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_all_to_archive() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            archive: Some(workdir.join("areas.zip")),
+            ..Default::default()
+        };
+        run_command(&args)?;
+
+        let image = fs::read(example_fmap_path())?;
+        let mut archive = zip::ZipArchive::new(File::open(workdir.join("areas.zip"))?)?;
+        let names = archive.file_names().map(String::from).collect::<Vec<_>>();
+        assert_eq!(names, vec!["bootblock", "normal", "fallback", "data"]);
+        let mut data = Vec::new();
+        archive.by_name("fallback")?.read_to_end(&mut data)?;
+        assert_eq!(data, &image[0x100..0x200]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let manifest_path = workdir.join("manifest.json");
+        let args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            params: vec![
+                ("normal".to_string(), workdir.join("normal.bin")),
+                ("@0x10+0x20".to_string(), workdir.join("range.bin")),
+            ],
+            with_crc: true,
+            manifest: Some(manifest_path.clone()),
+            ..Default::default()
+        };
+        run_command(&args)?;
+
+        let image = fs::read(example_fmap_path())?;
+        let manifest: serde_json::Value = serde_json::from_slice(&fs::read(&manifest_path)?)?;
+        // Hash covers area data only, without CRC footer.
+        assert_eq!(
+            manifest,
+            serde_json::json!([
+                {
+                    "area": "normal",
+                    "offset": 0x80,
+                    "size": 0x80,
+                    "output": workdir.join("normal.bin"),
+                    "sha256": common::hex(&Sha256::digest(&image[0x80..0x100])),
+                },
+                {
+                    "area": "@0x10+0x20",
+                    "offset": 0x10,
+                    "size": 0x20,
+                    "output": workdir.join("range.bin"),
+                    "sha256": common::hex(&Sha256::digest(&image[0x10..0x30])),
+                },
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_with_digest() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image = fs::read(example_fmap_path())?;
+        let digest = common::hex(&Sha256::digest(&image[0x80..0x100]));
+
+        let mut args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            params: vec![(
+                "normal".to_string(),
+                Utf8PathBuf::from(format!("{}=sha256:{digest}", workdir.join("normal.bin"))),
+            )],
+            ..Default::default()
+        };
+        run_command(&args)?;
+        assert_eq!(fs::read(workdir.join("normal.bin"))?, &image[0x80..0x100]);
+
+        // Digest of other area does not match.
+        args.params = vec![(
+            "fallback".to_string(),
+            Utf8PathBuf::from(format!("{}=sha256:{digest}", workdir.join("fallback.bin"))),
+        )];
+        if run_command(&args).is_ok() {
+            return Err("Extraction with mismatching digest expected to fail".into());
+        }
+        assert!(!workdir.join("fallback.bin").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_large_area_streamed() -> Result<(), Box<dyn Error>> {
+        // Area much larger than buffer of `std::io::copy`, filled with non-repeating bytes.
+        let (offset, size) = (0x1000usize, 0x300000usize);
+        let fmap = fmap::FMap {
+            name: "FMAP".to_string(),
+            version_major: 1,
+            version_minor: 1,
+            base: 0,
+            size: (offset + size) as u32,
+            areas: vec![
+                fmap::FMapArea::new("FMAP", 0, offset as u32),
+                fmap::FMapArea::new("BIG", offset as u32, size as u32),
+            ],
+        };
+        let mut image = (0..offset + size)
+            .map(|i| (i ^ (i >> 8) ^ (i >> 16)) as u8)
+            .collect::<Vec<_>>();
+        let mut encoded = Vec::new();
+        fmap.write(&mut encoded)?;
+        image[..encoded.len()].copy_from_slice(&encoded);
+
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image_path = workdir.join("image.bin");
+        fs::write(&image_path, &image)?;
+        let mut args = ExtractFmapArgs {
+            image: image_path,
+            params: vec![("BIG".to_string(), workdir.join("big.bin"))],
+            ..Default::default()
+        };
+        run_command(&args)?;
+        let area = &image[offset..offset + size];
+        assert!(fs::read(workdir.join("big.bin"))? == area);
+
+        args.with_crc = true;
+        args.params = vec![("BIG".to_string(), workdir.join("big_crc.bin"))];
+        run_command(&args)?;
+        let mut expected = area.to_vec();
+        expected.extend_from_slice(crc_footer::MAGIC);
+        expected.extend_from_slice(&crc32fast::hash(area).to_le_bytes());
+        assert!(fs::read(workdir.join("big_crc.bin"))? == expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_mkdir() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let output = workdir.join("RW/SECTION_A/normal.bin");
+        let mut args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            params: vec![("normal".to_string(), output.clone())],
+            ..Default::default()
+        };
+        if run_command(&args).is_ok() {
+            return Err("Extraction to non-existent directory expected to fail".into());
+        }
+
+        args.mkdir = true;
+        run_command(&args)?;
+        assert_eq!(fs::read(&output)?.len(), 0x80);
+
+        // Directory cannot be created in place of a file.
+        args.params = vec![("data".to_string(), output.join("data.bin"))];
+        if run_command(&args).is_ok() {
+            return Err("Creating directory in place of a file expected to fail".into());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_fmap_header() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let output = workdir.join("fmap.bin");
+        let args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            params: vec![common::area_mapping_or_name_param_valid(&format!(
+                "@fmap:{output}"
+            ))?],
+            ..Default::default()
+        };
+        run_command(&args)?;
+
+        // Example image has no FMAP area, header of 4 areas is at 0x200.
+        let image = fs::read(example_fmap_path())?;
+        let (fmap, offset) = fmap::FMap::from_bytes(&image)?;
+        assert!(fmap.get("FMAP").is_none());
+        let size = fmap::HEADER_SIZE + 4 * fmap::AREA_SIZE;
+        let header = fs::read(&output)?;
+        assert_eq!(header, &image[offset..offset + size]);
+        assert_eq!(fmap::FMap::parse_bytes(&header)?, fmap);
+
+        // Flash offset of FlashMap does not fit in 32 bits with such base.
+        let args = ExtractFmapArgs {
+            base: 0xffff_ff00,
+            ..args
+        };
+        match run_command(&args) {
+            Ok(_) => return Err("Extraction beyond 32-bit flash offset expected to fail".into()),
+            Err(e) => assert!(matches!(
+                e.downcast_ref::<fmap::FMapError>(),
+                Some(fmap::FMapError::AreaOutOfBounds(name)) if name == "@fmap"
+            )),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_auto_named() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let mut args = ExtractFmapArgs {
+            image: example_fmap_path(),
+            params: vec![
+                common::area_mapping_or_name_param_valid("normal")?,
+                ("data".to_string(), workdir.join("data.out")),
+            ],
+            prefix: Some(format!("{workdir}/")),
+            manifest: Some(workdir.join("manifest.json")),
+            ..Default::default()
+        };
+        run_command(&args)?;
+        assert_eq!(fs::read(workdir.join("normal.bin"))?.len(), 0x80);
+        assert_eq!(fs::read(workdir.join("data.out"))?.len(), 0x200);
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(workdir.join("manifest.json"))?)?;
+        assert_eq!(manifest[0]["output"], workdir.join("normal.bin").as_str());
+
+        // Transforms apply to each area matched by glob.
+        args.params = vec![common::area_mapping_or_name_param_valid("*a*")?];
+        args.glob = true;
+        args.lowercase = true;
+        args.suffix = Some(".img".to_string());
+        args.exclude = vec!["data".to_string()];
+        run_command(&args)?;
+        assert!(workdir.join("normal.img").exists());
+        assert!(workdir.join("fallback.img").exists());
+        assert!(!workdir.join("data.img").exists());
+
         Ok(())
     }
 }
@@ -0,0 +1,184 @@
+use std::error::Error;
+use std::io::{stdout, Read, Seek, SeekFrom, Write};
+
+use camino::Utf8PathBuf;
+use clap::{Args, ValueHint};
+
+use crate::{
+    cmd::{common, image},
+    fmap,
+};
+
+#[derive(Args, Default)]
+pub struct FwidArgs {
+    #[arg(required = true, index = 1, value_hint = ValueHint::FilePath, value_parser = common::file_exists_validator)]
+    /// Firmware image path.
+    pub(in crate::cmd) image: Utf8PathBuf,
+
+    #[arg(index = 2, value_name = "SECTION")]
+    /// Areas to read firmware IDs from. RO_FRID and all RW_FWID* areas by default.
+    pub(in crate::cmd) areas: Vec<String>,
+
+    #[arg(long, value_name = "STR")]
+    /// Fail if any of the firmware IDs differs from STR.
+    pub(in crate::cmd) expected: Option<String>,
+
+    #[arg(long, value_name = "SIZE", value_parser = common::decimal_or_hex_validator_u64)]
+    /// Search for FlashMap only within first SIZE bytes of the image.
+    pub(in crate::cmd) max_scan: Option<u64>,
+
+    #[arg(long, value_name = "N", value_parser = common::alignment_validator)]
+    /// Check only offsets aligned to N bytes, a power of two, when searching for FlashMap.
+    pub(in crate::cmd) align: Option<usize>,
+}
+
+/// Checks whether area with `name` holds firmware ID in ChromeOS images.
+fn is_fwid_area(name: &str) -> bool {
+    name == "RO_FRID" || name.starts_with("RW_FWID")
+}
+
+/// Reads firmware IDs from areas of `fmap` named `names`, or from all firmware ID areas
+/// if `names` is empty. Returns (area name, ID) pairs.
+fn read_fwids(
+    reader: &mut (impl Read + Seek),
+    fmap: &fmap::FMap,
+    names: &[String],
+) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let areas = if names.is_empty() {
+        fmap.areas()
+            .iter()
+            .filter(|ar| is_fwid_area(&ar.name))
+            .collect::<Vec<_>>()
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                fmap.areas()
+                    .find(name)
+                    .ok_or(fmap::FMapError::AreaNotFound(name.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    if areas.is_empty() {
+        return Err("No firmware ID areas in FlashMap".into());
+    }
+
+    let image_size = image::size(reader)?;
+    let mut fwids = Vec::new();
+    for ar in areas.into_iter() {
+        if ar.end() > image_size {
+            return Err(fmap::FMapError::AreaOutOfBounds(ar.name.clone()).into());
+        }
+        let mut data = vec![0u8; ar.size as usize];
+        reader.seek(SeekFrom::Start(ar.offset as u64))?;
+        reader.read_exact(&mut data)?;
+        fwids.push((ar.name.clone(), fmap::decode::c_string(&data)));
+    }
+    Ok(fwids)
+}
+
+pub fn run_command(args: &FwidArgs) -> Result<(), Box<dyn Error>> {
+    let mut input_file = image::open(&args.image)?;
+    let (fmap, _) = fmap::FMap::find_fmap_with_opts(
+        &mut input_file,
+        &fmap::SearchOptions {
+            max_scan: args.max_scan,
+            min_align: args.align,
+            ..Default::default()
+        },
+    )?;
+
+    let fwids = read_fwids(&mut input_file, &fmap, &args.areas)?;
+    let mut writer = stdout().lock();
+    for (area, fwid) in fwids.iter() {
+        writeln!(writer, "{area}: {fwid}")?;
+    }
+
+    if let Some(expected) = &args.expected {
+        if let Some((area, fwid)) = fwids.iter().find(|(_, fwid)| fwid != expected) {
+            return Err(
+                format!("Firmware ID in area '{area}' is '{fwid}', expected '{expected}'").into(),
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn example_image() -> Result<(fmap::FMap, Vec<u8>), Box<dyn Error>> {
+        let fmap = fmap::FMap {
+            name: "FMAP".to_string(),
+            version_major: 1,
+            version_minor: 1,
+            base: 0,
+            size: 0x400,
+            areas: vec![
+                fmap::FMapArea::new("RO_FRID", 0x0, 0x40),
+                fmap::FMapArea::new("RW_FWID_A", 0x40, 0x40),
+                fmap::FMapArea::new("RW_FWID_B", 0x80, 0x40),
+                fmap::FMapArea::new("FMAP", 0x200, 0x200),
+            ],
+        };
+        let mut data = vec![0u8; 0x400];
+        data[..6].copy_from_slice(b"Foo.10");
+        data[0x40..0x46].copy_from_slice(b"Foo.10");
+        // ID filling the whole area without NUL terminator.
+        data[0x80..0xc0].fill(b'B');
+        let mut encoded = Vec::new();
+        fmap.write(&mut encoded)?;
+        data[0x200..0x200 + encoded.len()].copy_from_slice(&encoded);
+        Ok((fmap, data))
+    }
+
+    #[test]
+    fn test_read_fwids() -> Result<(), Box<dyn Error>> {
+        let (fmap, data) = example_image()?;
+        let mut reader = Cursor::new(data);
+        assert_eq!(
+            read_fwids(&mut reader, &fmap, &[])?,
+            [
+                ("RO_FRID".to_string(), "Foo.10".to_string()),
+                ("RW_FWID_A".to_string(), "Foo.10".to_string()),
+                ("RW_FWID_B".to_string(), "B".repeat(0x40)),
+            ]
+        );
+        assert_eq!(
+            read_fwids(&mut reader, &fmap, &["RW_FWID_A".to_string()])?,
+            [("RW_FWID_A".to_string(), "Foo.10".to_string())]
+        );
+        if read_fwids(&mut reader, &fmap, &["RW_FWID_C".to_string()]).is_ok() {
+            return Err("Reading ID from missing area expected to fail".into());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expected_fwid() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image = workdir.join("image.bin");
+        std::fs::write(&image, example_image()?.1)?;
+
+        let mut args = FwidArgs {
+            image,
+            areas: vec!["RO_FRID".to_string(), "RW_FWID_A".to_string()],
+            expected: Some("Foo.10".to_string()),
+            ..Default::default()
+        };
+        run_command(&args)?;
+
+        args.areas.push("RW_FWID_B".to_string());
+        match run_command(&args) {
+            Ok(_) => Err("Mismatching firmware ID expected to fail".into()),
+            Err(e) => {
+                assert!(e.to_string().starts_with("Firmware ID in area 'RW_FWID_B'"));
+                Ok(())
+            }
+        }
+    }
+}
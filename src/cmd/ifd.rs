@@ -0,0 +1,169 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use log::info;
+
+use crate::fmap;
+
+/* Intel Flash Descriptor. Only fields needed to locate BIOS region are parsed. */
+const SIGNATURE: u32 = 0x0ff0_a55a;
+/// Descriptor signature is at 0x10, or at 0 in images made for old chipsets.
+const SIGNATURE_OFFSETS: [u64; 2] = [0x10, 0];
+const BIOS_REGION_INDEX: u64 = 1;
+
+fn read_u32(reader: &mut (impl Read + Seek), offset: u64) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Returns (offset, size) of BIOS region described by Intel Flash Descriptor,
+/// or `None` if the image has no descriptor or BIOS region is not used.
+pub fn bios_region(reader: &mut (impl Read + Seek)) -> io::Result<Option<(u64, u64)>> {
+    for signature_offset in SIGNATURE_OFFSETS {
+        match read_u32(reader, signature_offset) {
+            Ok(SIGNATURE) => (),
+            Ok(_) => continue,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let flmap0 = read_u32(reader, signature_offset + 4)?;
+        let frba = (((flmap0 >> 16) & 0xff) << 4) as u64;
+        let flreg = read_u32(reader, frba + BIOS_REGION_INDEX * 4)?;
+        let base = ((flreg & 0x7fff) as u64) << 12;
+        let limit = ((((flreg >> 16) & 0x7fff) as u64) << 12) | 0xfff;
+        if base > limit {
+            return Ok(None);
+        }
+        return Ok(Some((base, limit - base + 1)));
+    }
+    Ok(None)
+}
+
+/// Searches for FMap within BIOS region of image with Intel Flash Descriptor. Search
+/// options `opts` apply to the whole image and are narrowed to the region.
+/// Returns FMap and its offset in the whole image, or `None` if image has no descriptor
+/// or FMap was not found in BIOS region.
+pub fn find_fmap_in_bios_region(
+    reader: &mut (impl Read + Seek),
+    opts: &fmap::SearchOptions,
+) -> Result<Option<(fmap::FMap, usize)>, fmap::FMapError> {
+    let (offset, size) = match bios_region(reader)? {
+        None => return Ok(None),
+        Some(v) => v,
+    };
+    info!("BIOS region found at {offset:#x}, size {size:#x}");
+    let end = offset + size;
+    let region_opts = fmap::SearchOptions {
+        start: opts.start.max(offset as usize),
+        max_scan: Some(opts.max_scan.map_or(end, |max| max.min(end))),
+        min_align: opts.min_align,
+    };
+    match fmap::FMap::find_fmap_with_opts(reader, &region_opts) {
+        Ok(found) => Ok(Some(found)),
+        Err(fmap::FMapError::NotFound) => Ok(None),
+        Err(fmap::FMapError::IOError { source })
+            if source.kind() == io::ErrorKind::UnexpectedEof =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Image of 0x4000 bytes with descriptor placing BIOS region at 0x1000..0x4000.
+    fn ifd_image() -> Vec<u8> {
+        let mut data = vec![0xffu8; 0x4000];
+        data[0x10..0x14].copy_from_slice(&SIGNATURE.to_le_bytes());
+        // FRBA = 0x40
+        data[0x14..0x18].copy_from_slice(&0x0004_0000u32.to_le_bytes());
+        // Descriptor region at 0..0x1000, BIOS region at 0x1000..0x4000
+        data[0x40..0x44].copy_from_slice(&0x0000_0000u32.to_le_bytes());
+        data[0x44..0x48].copy_from_slice(&0x0003_0001u32.to_le_bytes());
+        data
+    }
+
+    fn place_fmap(data: &mut [u8], offset: usize, name: &str) {
+        let fmap = fmap::FMap {
+            name: name.to_string(),
+            version_major: fmap::VERSION_MAJOR as u8,
+            size: 0x4000,
+            ..Default::default()
+        };
+        let mut header = Vec::new();
+        fmap.write(&mut header).unwrap();
+        data[offset..offset + header.len()].copy_from_slice(&header);
+    }
+
+    #[test]
+    fn test_bios_region() -> io::Result<()> {
+        assert_eq!(
+            bios_region(&mut Cursor::new(ifd_image()))?,
+            Some((0x1000, 0x3000))
+        );
+        assert_eq!(bios_region(&mut Cursor::new(vec![0xffu8; 0x1000]))?, None);
+        assert_eq!(bios_region(&mut Cursor::new(vec![0u8; 2]))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_fmap_in_bios_region() -> Result<(), Box<dyn std::error::Error>> {
+        let mut data = ifd_image();
+        // Bogus FMap in descriptor region is found first by the whole image scan.
+        place_fmap(&mut data, 0x800, "BOGUS");
+        place_fmap(&mut data, 0x1800, "REAL");
+        assert_eq!(fmap::FMap::from_bytes(&data)?.1, 0x800);
+
+        match find_fmap_in_bios_region(&mut Cursor::new(&data), &Default::default())? {
+            Some((fmap, offset)) => {
+                assert_eq!(offset, 0x1800);
+                assert_eq!(fmap.name, "REAL");
+            }
+            None => return Err("FMap not found in BIOS region".into()),
+        }
+
+        assert!(find_fmap_in_bios_region(
+            &mut Cursor::new(vec![0xffu8; 0x1000]),
+            &Default::default()
+        )?
+        .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_fmap_in_bios_region_with_opts() -> Result<(), Box<dyn std::error::Error>> {
+        let mut data = ifd_image();
+        place_fmap(&mut data, 0x1800, "REAL");
+        let find = |opts: fmap::SearchOptions| {
+            find_fmap_in_bios_region(&mut Cursor::new(&data), &opts)
+                .map(|found| found.map(|(_, offset)| offset))
+        };
+
+        // Options are image offsets, so the region is not scanned before 0x1000.
+        let max_scan = |max| fmap::SearchOptions {
+            max_scan: Some(max),
+            ..Default::default()
+        };
+        assert_eq!(find(max_scan(0x1000))?, None);
+        assert_eq!(find(max_scan(0x2000))?, Some(0x1800));
+        let align = |align| fmap::SearchOptions {
+            min_align: Some(align),
+            ..Default::default()
+        };
+        assert_eq!(find(align(0x1000))?, None);
+        assert_eq!(find(align(0x800))?, Some(0x1800));
+        let start = |start| fmap::SearchOptions {
+            start,
+            ..Default::default()
+        };
+        assert_eq!(find(start(0x1900))?, None);
+        assert_eq!(find(start(0x1800))?, Some(0x1800));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+use camino::Utf8Path;
+use log::info;
+
+pub trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Xz,
+}
+
+/// Detects compression of data in `reader` by its magic bytes. Rewinds reader afterwards.
+pub fn detect_compression(reader: &mut (impl Read + Seek)) -> io::Result<Compression> {
+    let mut magic = [0u8; 6];
+    reader.seek(SeekFrom::Start(0))?;
+    let mut len = 0;
+    while len < magic.len() {
+        match reader.read(&mut magic[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    reader.seek(SeekFrom::Start(0))?;
+
+    let magic = &magic[..len];
+    Ok(if magic.starts_with(GZIP_MAGIC) {
+        Compression::Gzip
+    } else if magic.starts_with(XZ_MAGIC) {
+        Compression::Xz
+    } else {
+        Compression::None
+    })
+}
+
+/// Decompresses whole `reader` into memory, so it can be searched for FlashMap.
+pub fn decompress(reader: impl Read, compression: Compression) -> io::Result<Cursor<Vec<u8>>> {
+    let mut data = Vec::new();
+    match compression {
+        Compression::None => {
+            let mut reader = reader;
+            reader.read_to_end(&mut data)?;
+        }
+        Compression::Gzip => {
+            flate2::read::MultiGzDecoder::new(reader).read_to_end(&mut data)?;
+        }
+        Compression::Xz => {
+            lzma_rs::xz_decompress(&mut io::BufReader::new(reader), &mut data)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{e:?}")))?;
+        }
+    }
+    Ok(Cursor::new(data))
+}
+
+/// Device file, e.g. /dev/mtd0, whose size cannot be obtained by seeking to its end.
+/// Seeking relative to the end uses size reported by the device instead.
+#[cfg(target_os = "linux")]
+struct Device {
+    file: File,
+    size: u64,
+}
+
+#[cfg(target_os = "linux")]
+impl Read for Device {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Seek for Device {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::End(delta) => match self.size.checked_add_signed(delta) {
+                Some(offset) => self.file.seek(SeekFrom::Start(offset)),
+                None => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Invalid seek to a negative or overflowing position",
+                )),
+            },
+            _ => self.file.seek(pos),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+const BLKGETSIZE64: u32 = ior(0x12, 114, std::mem::size_of::<usize>());
+#[cfg(target_os = "linux")]
+const MEMGETINFO: u32 = ior(b'M' as u32, 1, std::mem::size_of::<MtdInfoUser>());
+
+/// Request number of ioctl reading `size` bytes, as `_IOR()` macro of Linux computes it.
+#[cfg(target_os = "linux")]
+const fn ior(kind: u32, number: u32, size: usize) -> u32 {
+    (2 << 30) | ((size as u32) << 16) | (kind << 8) | number
+}
+
+/// `struct mtd_info_user` of Linux MTD character devices.
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+#[repr(C)]
+struct MtdInfoUser {
+    kind: u8,
+    flags: u32,
+    size: u32,
+    erasesize: u32,
+    writesize: u32,
+    oobsize: u32,
+    padding: u64,
+}
+
+/// Size of block or MTD character device opened as `file`, reported by the driver.
+/// Returns `None` for other files or if the driver does not report the size.
+#[cfg(target_os = "linux")]
+fn device_size(file: &File) -> Option<u64> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::io::AsRawFd;
+
+    let file_type = file.metadata().ok()?.file_type();
+    if file_type.is_block_device() {
+        let mut size = 0u64;
+        let ret = unsafe { libc::ioctl(file.as_raw_fd(), BLKGETSIZE64 as _, &mut size) };
+        (ret == 0).then_some(size)
+    } else if file_type.is_char_device() {
+        let mut info = MtdInfoUser::default();
+        let ret = unsafe { libc::ioctl(file.as_raw_fd(), MEMGETINFO as _, &mut info) };
+        (ret == 0).then_some(info.size as u64)
+    } else {
+        None
+    }
+}
+
+/// Wraps device files reporting zero size or not seekable to their end into `Device`.
+/// Regular files are returned as they are.
+fn with_device_size(mut file: File) -> io::Result<Box<dyn ReadSeek>> {
+    #[cfg(target_os = "linux")]
+    if matches!(file.seek(SeekFrom::End(0)), Ok(0) | Err(_)) {
+        if let Some(size) = device_size(&file) {
+            info!("Using size {size:#x} reported by the device");
+            file.seek(SeekFrom::Start(0))?;
+            return Ok(Box::new(Device { file, size }));
+        }
+    }
+    file.seek(SeekFrom::Start(0))?;
+    Ok(Box::new(file))
+}
+
+/// Opens firmware image for reading. Gzip and xz compressed images are decompressed,
+/// uncompressed ones are read directly from the file or device.
+pub fn open(path: &Utf8Path) -> io::Result<Box<dyn ReadSeek>> {
+    let mut file = File::open(path)?;
+    match detect_compression(&mut file)? {
+        Compression::None => with_device_size(file),
+        compression => {
+            info!("Decompressing {compression:?} image '{path}'");
+            Ok(Box::new(decompress(file, compression)?))
+        }
+    }
+}
+
+/// Returns size of data available in `reader`.
+pub fn size(reader: &mut impl Seek) -> io::Result<u64> {
+    let position = reader.stream_position()?;
+    let size = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(position))?;
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use camino::Utf8PathBuf;
+
+    use crate::fmap;
+
+    fn example_fmap_path() -> Utf8PathBuf {
+        let mut d = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/example_fmap.bin");
+        d
+    }
+
+    #[test]
+    fn test_open_compressed_images() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image = std::fs::read(example_fmap_path())?;
+
+        let gz_path = workdir.join("image.bin.gz");
+        let mut encoder =
+            flate2::write::GzEncoder::new(File::create(&gz_path)?, flate2::Compression::default());
+        encoder.write_all(&image)?;
+        encoder.finish()?;
+
+        let xz_path = workdir.join("image.bin.xz");
+        let mut xz = Vec::new();
+        lzma_rs::xz_compress(&mut Cursor::new(&image), &mut xz)?;
+        std::fs::write(&xz_path, xz)?;
+
+        for (path, compression) in [
+            (example_fmap_path(), Compression::None),
+            (gz_path, Compression::Gzip),
+            (xz_path, Compression::Xz),
+        ] {
+            assert_eq!(detect_compression(&mut File::open(&path)?)?, compression);
+            let mut reader = open(&path)?;
+            assert_eq!(size(&mut reader)?, image.len() as u64);
+            let (_, offset) = fmap::FMap::find_fmap(&mut reader)?;
+            assert_eq!(offset, 0x200);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_compression_short_data() -> io::Result<()> {
+        assert_eq!(
+            detect_compression(&mut Cursor::new([0x1f]))?,
+            Compression::None
+        );
+        assert_eq!(
+            detect_compression(&mut Cursor::new([0x1f, 0x8b]))?,
+            Compression::Gzip
+        );
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_device_size() -> Result<(), Box<dyn std::error::Error>> {
+        let file = File::open(example_fmap_path())?;
+        assert_eq!(device_size(&file), None);
+
+        // Device seeks relative to its reported size, not the size of the file.
+        let mut device = Device { file, size: 0x300 };
+        assert_eq!(size(&mut device)?, 0x300);
+        assert_eq!(device.seek(SeekFrom::End(-0x100))?, 0x200);
+        assert!(device.seek(SeekFrom::End(-0x301)).is_err());
+        let (_, offset) = fmap::FMap::find_fmap(&mut device)?;
+        assert_eq!(offset, 0x200);
+
+        Ok(())
+    }
+}
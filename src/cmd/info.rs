@@ -0,0 +1,144 @@
+use std::error::Error;
+use std::io::{stdout, Write};
+
+use camino::Utf8PathBuf;
+use clap::{Args, ValueEnum, ValueHint};
+
+use crate::{
+    cmd::{common, image},
+    fmap,
+};
+
+#[derive(Args)]
+pub struct InfoArgs {
+    #[arg(required = true, index = 1, value_hint = ValueHint::FilePath, value_parser = common::file_exists_validator)]
+    /// Firmware image path.
+    pub(in crate::cmd) image: Utf8PathBuf,
+
+    #[arg(required = true, index = 2)]
+    /// FlashMap section name.
+    pub(in crate::cmd) area: String,
+
+    #[arg(long, value_enum)]
+    /// Print only value of given field.
+    pub(in crate::cmd) field: Option<Field>,
+
+    #[arg(long, action)]
+    /// Fail if more than one FlashMap area has the same name, instead of warning.
+    pub(in crate::cmd) strict_names: bool,
+
+    #[arg(long, value_name = "SIZE", value_parser = common::decimal_or_hex_validator_u64)]
+    /// Search for FlashMap only within first SIZE bytes of the image.
+    pub(in crate::cmd) max_scan: Option<u64>,
+
+    #[arg(long, value_name = "N", value_parser = common::alignment_validator)]
+    /// Check only offsets aligned to N bytes, a power of two, when searching for FlashMap.
+    pub(in crate::cmd) align: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub(in crate::cmd) enum Field {
+    Offset,
+    Size,
+    End,
+    Flags,
+}
+
+/// Returns (key, value) pairs describing `area`.
+fn area_info(area: &fmap::FMapArea) -> Vec<(Field, String)> {
+    let flags = area.flags.names();
+    vec![
+        (Field::Offset, format!("{:#x}", area.offset)),
+        (Field::Size, format!("{:#x}", area.size)),
+        (Field::End, format!("{:#x}", area.end())),
+        (
+            Field::Flags,
+            if flags.is_empty() {
+                String::from("NONE")
+            } else {
+                flags.join(",")
+            },
+        ),
+    ]
+}
+
+fn dump_info(
+    area: &fmap::FMapArea,
+    field: Option<Field>,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    for (key, value) in area_info(area).iter() {
+        match field {
+            Some(f) if f == *key => writeln!(writer, "{value}")?,
+            Some(_) => (),
+            None => writeln!(
+                writer,
+                "{}: {}",
+                key.to_possible_value().unwrap().get_name(),
+                value
+            )?,
+        }
+    }
+    Ok(())
+}
+
+pub fn run_command(args: &InfoArgs) -> Result<(), Box<dyn Error>> {
+    let mut input_file = image::open(&args.image)?;
+    let (fmap, _) = fmap::FMap::find_fmap_with_opts(
+        &mut input_file,
+        &fmap::SearchOptions {
+            max_scan: args.max_scan,
+            min_align: args.align,
+            ..Default::default()
+        },
+    )?;
+    common::check_duplicate_names(&fmap, args.strict_names)?;
+
+    let area = match fmap.get(&args.area) {
+        Some(v) => v,
+        None => return Err(fmap::FMapError::AreaNotFound(args.area.clone()).into()),
+    };
+    dump_info(area, args.field, &mut stdout())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_area() -> fmap::FMapArea {
+        fmap::FMapArea {
+            name: "FW_MAIN_A".to_string(),
+            offset: 0x1000,
+            size: 0x200,
+            flags: fmap::FMapFlags::Static | fmap::FMapFlags::Preserve,
+        }
+    }
+
+    #[test]
+    fn test_dump_info() -> Result<(), String> {
+        let mut result = Vec::new();
+        if let Err(e) = dump_info(&example_area(), None, &mut result) {
+            return Err(format!("dump_info() failed with error: {e}"));
+        }
+        let expected = "offset: 0x1000\n\
+                        size: 0x200\n\
+                        end: 0x1200\n\
+                        flags: STATIC,PRESERVE\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_info_field() -> Result<(), String> {
+        let mut result = Vec::new();
+        if let Err(e) = dump_info(&example_area(), Some(Field::End), &mut result) {
+            return Err(format!("dump_info() failed with error: {e}"));
+        }
+        assert_eq!(String::from_utf8(result).unwrap(), "0x1200\n");
+
+        Ok(())
+    }
+}
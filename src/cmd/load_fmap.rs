@@ -1,21 +1,27 @@
 use std::error::Error;
-use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::fs::{File, OpenOptions};
+use std::io::{stdin, stdout, Read, Seek, SeekFrom, Write};
 
-use crate::{cmd::common, fmap};
-use camino::Utf8PathBuf;
-use clap::{arg, Args, ValueHint};
-use log::{error, info};
+use crate::{
+    cmd::{common, crc_footer, image, ops},
+    fmap,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{Args, ValueHint};
+use log::{error, info, warn};
+use serde::Serialize;
 use tempfile::tempfile;
 
-#[derive(Args)]
+#[derive(Args, Default)]
 pub struct LoadFmapArgs {
     #[arg(required = true, index = 1, value_hint = ValueHint::FilePath, value_parser = common::file_exists_validator)]
     /// Firmware image path.
     pub(in crate::cmd) image: Utf8PathBuf,
 
-    #[arg(required = true, index = 2, trailing_var_arg = true, value_parser = common::area_to_file_mapping_param_valid)]
+    #[arg(required_unless_present = "ops", index = 2, trailing_var_arg = true, value_parser = common::area_to_file_mapping_param_valid)]
     /// List of mappings from FlashMap section to file in format SECTION:FILE.
+    /// Use `-` as FILE to read the section from stdin.
+    /// Append `@OFFSET` to SECTION to select one of areas sharing the name.
     /// Example: FW_MAIN_A:fw_main_a.bin
     pub(in crate::cmd) params: Vec<(String, Utf8PathBuf)>,
 
@@ -23,73 +29,444 @@ pub struct LoadFmapArgs {
     /// Output file path.
     pub(in crate::cmd) output: Option<Utf8PathBuf>,
 
-    #[arg(long, default_value = "0xff", value_parser = common::decimal_or_hex_validator_u8)]
-    pub(in crate::cmd) fill_value: u8,
+    #[arg(long, default_value = "0xff", value_parser = common::fill_pattern_validator)]
+    /// Byte value or hex pattern (e.g. 0xDEADBEEF) used to fill area space not covered by file.
+    // Fully qualified Vec prevents clap from treating it as a list of arguments.
+    pub(in crate::cmd) fill_value: ::std::vec::Vec<u8>,
+
+    #[arg(long, action)]
+    /// Verify and strip CRC footer (see `extract-fmap --with-crc`) from each input file.
+    pub(in crate::cmd) verify_crc: bool,
+
+    #[arg(long, value_hint = ValueHint::FilePath, value_parser = common::file_exists_validator)]
+    /// JSON file with list of operations to execute in addition to mappings, e.g.
+    /// [{"area": "FW_MAIN_A", "file": "a.bin", "range": [0, 1024]}, {"area": "RW_NVRAM", "erase": true}]
+    pub(in crate::cmd) ops: Option<Utf8PathBuf>,
+
+    #[arg(long, default_value = "0", value_parser = common::decimal_or_hex_validator_u32)]
+    /// Flash offset of the first byte of the image, for images being partial flash dumps.
+    /// Subtracted from area offsets, similarly to how `fmap_base` places flash in memory.
+    pub(in crate::cmd) base: u32,
+
+    #[arg(long, action)]
+    /// Treat SECTION as glob pattern (`*`, `?`) matched against all area names.
+    /// `%s` in FILE is replaced with the matched area name. Example: RW_SECTION_*:%s.bin
+    pub(in crate::cmd) glob: bool,
+
+    #[arg(long, action)]
+    /// Validate mappings and print what would be written without modifying any file.
+    pub(in crate::cmd) dry_run: bool,
+
+    #[arg(long, value_name = "SUFFIX", num_args = 0..=1, require_equals = true, default_missing_value = "bak", conflicts_with = "output")]
+    /// Copy original image to IMAGE.SUFFIX (IMAGE.bak by default) before modifying it in place.
+    pub(in crate::cmd) backup: Option<String>,
+
+    #[arg(long, action)]
+    /// Warn about areas not found in FlashMap instead of failing.
+    pub(in crate::cmd) ignore_missing: bool,
+
+    #[arg(long, value_name = "SIZE", value_parser = common::decimal_or_hex_validator_u64)]
+    /// Extend output image to SIZE bytes, filling added space with fill value.
+    pub(in crate::cmd) pad_to: Option<u64>,
+
+    #[arg(long, action)]
+    /// Print JSON record with range and number of data and padding bytes of each written area
+    /// to stdout, one per line.
+    pub(in crate::cmd) json_log: bool,
+
+    #[arg(long, value_name = "BYTE", value_parser = common::decimal_or_hex_validator_u8)]
+    /// Fill all areas not named in mappings nor operations with BYTE before loading, so output
+    /// contains only loaded data. FlashMap itself is kept intact.
+    pub(in crate::cmd) fill_unmapped: Option<u8>,
+
+    #[arg(long, action, conflicts_with = "fill_unmapped")]
+    /// Same as --fill-unmapped 0.
+    pub(in crate::cmd) zero_unmapped: bool,
+
+    #[arg(long, action)]
+    /// Fail if more than one FlashMap area has the same name, instead of warning.
+    pub(in crate::cmd) strict_names: bool,
+
+    #[arg(long, action)]
+    /// Fail if input file does not fit in its area, instead of loading only its beginning.
+    pub(in crate::cmd) strict_size: bool,
+
+    #[arg(long, value_name = "SIZE", value_parser = common::decimal_or_hex_validator_u64)]
+    /// Search for FlashMap only within first SIZE bytes of the image.
+    pub(in crate::cmd) max_scan: Option<u64>,
+
+    #[arg(long, value_name = "N", value_parser = common::alignment_validator)]
+    /// Check only offsets aligned to N bytes, a power of two, when searching for FlashMap.
+    pub(in crate::cmd) align: Option<usize>,
+}
+
+/// Record of single loaded area, printed as JSON line with --json-log.
+#[derive(Debug, PartialEq, Serialize)]
+struct LoadRecord {
+    area: String,
+    offset: u32,
+    size: u32,
+    /// Input file, none if the area was erased.
+    file: Option<Utf8PathBuf>,
+    data_bytes: usize,
+    padding_bytes: usize,
+}
+
+/// Path of the backup of `image` created with given `suffix`.
+fn backup_path(image: &Utf8Path, suffix: &str) -> Utf8PathBuf {
+    let mut path = image.as_str().to_string();
+    path.push('.');
+    path.push_str(suffix.trim_start_matches('.'));
+    Utf8PathBuf::from(path)
+}
+
+/// Fills `buf` by repeating `pattern` from its beginning. Last repetition may be partial.
+fn fill_with_pattern(buf: &mut [u8], pattern: &[u8]) {
+    for (b, p) in buf.iter_mut().zip(pattern.iter().cycle()) {
+        *b = *p;
+    }
+}
+
+/// Reads from `reader` until `buf` is full or end of data is reached.
+/// Returns number of bytes read.
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        match reader.read(&mut buf[total..]) {
+            Ok(0) => break,
+            Ok(n) => total += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total)
+}
+
+/// Fills areas of `fmap` not targeted by any of `ops` with `value` in `image`. Bytes of the
+/// FlashMap at `fmap_offset` are restored afterwards, as some of the filled areas may contain it.
+fn fill_unmapped(
+    image: &mut (impl Read + Write + Seek),
+    fmap: &fmap::FMap,
+    fmap_offset: usize,
+    ops: &[ops::Op],
+    base: u32,
+    image_size: u64,
+    value: u8,
+) -> std::io::Result<()> {
+    let mut fmap_data = vec![0u8; fmap::HEADER_SIZE + fmap.areas.len() * fmap::AREA_SIZE];
+    image.seek(SeekFrom::Start(fmap_offset as u64))?;
+    image.read_exact(&mut fmap_data)?;
+
+    for ar in fmap
+        .areas
+        .iter()
+        .filter(|ar| !ops.iter().any(|op| op.area == ar.name))
+    {
+        let image_offset = match common::offset_in_image(ar.offset, ar.size, base, image_size) {
+            Err(e) => {
+                warn!("Area '{}' not filled: {e}", ar.name);
+                continue;
+            }
+            Ok(v) => v,
+        };
+        info!("Filling unmapped area '{}' with {value:#04x}", ar.name);
+        image.seek(SeekFrom::Start(image_offset))?;
+        image.write_all(&vec![value; ar.size as usize])?;
+    }
+
+    image.seek(SeekFrom::Start(fmap_offset as u64))?;
+    image.write_all(&fmap_data)
 }
 
 pub fn run_command(args: &LoadFmapArgs) -> Result<(), Box<dyn Error>> {
-    let mut input_file = File::open(&args.image)?;
-    let (fmap, _) = fmap::FMap::find_fmap(&mut input_file)?;
+    let records = load(args)?;
+    if args.json_log {
+        let mut writer = stdout().lock();
+        for record in records.iter() {
+            serde_json::to_writer(&mut writer, record)?;
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
+
+/// Loads areas according to `args`. Returns records of written areas.
+fn load(args: &LoadFmapArgs) -> Result<Vec<LoadRecord>, Box<dyn Error>> {
+    let mut ops = ops::from_params(&args.params);
+    if let Some(path) = &args.ops {
+        ops.append(&mut ops::read_ops_file(path)?);
+    }
+
+    if let Some(op) = ops.iter().find(|op| op.sha256.is_some()) {
+        return Err(format!(
+            "Digest of area '{}' can be verified only when extracting",
+            op.area
+        )
+        .into());
+    }
+
+    let compression = image::detect_compression(&mut File::open(&args.image)?)?;
+    if compression != image::Compression::None && args.output.is_none() && !args.dry_run {
+        return Err("Compressed image cannot be modified in place. Please use --output".into());
+    }
+
+    let mut input_file = OpenOptions::new()
+        .read(true)
+        .write(args.output.is_none() && !args.dry_run)
+        .open(&args.image)?;
+    let mut image_data: Box<dyn image::ReadSeek> = match compression {
+        image::Compression::None => Box::new(input_file.try_clone()?),
+        _ => Box::new(image::decompress(&input_file, compression)?),
+    };
+    let (fmap, fmap_offset) = fmap::FMap::find_fmap_with_opts(
+        &mut image_data,
+        &fmap::SearchOptions {
+            max_scan: args.max_scan,
+            min_align: args.align,
+            ..Default::default()
+        },
+    )?;
+    common::check_duplicate_names(&fmap, args.strict_names)?;
+    ops::check_areas_present(&ops, &fmap)?;
+    if args.glob {
+        ops = ops::expand_globs(ops, &fmap)?;
+    }
+    if ops
+        .iter()
+        .filter(|op| op.file.as_deref() == Some("-".into()))
+        .count()
+        > 1
+    {
+        return Err("Only one section can be read from stdin".into());
+    }
+    let mut image_size = image::size(&mut image_data)?;
+    if let Some(pad_size) = args.pad_to {
+        if pad_size < image_size {
+            return Err(
+                format!("Image size {image_size:#x} exceeds pad size {pad_size:#x}").into(),
+            );
+        }
+        if let Some(ar) = fmap
+            .areas
+            .iter()
+            .find(|ar| ar.end() > args.base as u64 + pad_size)
+        {
+            return Err(format!(
+                "Area '{}' ends at {:#x}, beyond pad size {pad_size:#x}",
+                ar.name,
+                ar.end()
+            )
+            .into());
+        }
+    }
 
+    image_data.seek(SeekFrom::Start(0))?;
     let mut output_file = tempfile()?;
-    if let Err(e) = std::io::copy(&mut input_file, &mut output_file) {
+    if let Err(e) = std::io::copy(&mut image_data, &mut output_file) {
         return Err(format!("Failed to prepare workfile. Please check permissions to default temporary directory: `{}'. Error: {e}", std::env::temp_dir().display()).into());
     }
+    if let Some(pad_size) = args.pad_to {
+        let mut padding = vec![0u8; (pad_size - image_size) as usize];
+        fill_with_pattern(&mut padding, &args.fill_value);
+        output_file.write_all(&padding)?;
+        info!("Padded image from {image_size:#x} to {pad_size:#x} bytes");
+        image_size = pad_size;
+    }
+    let unmapped_value = if args.zero_unmapped {
+        Some(0)
+    } else {
+        args.fill_unmapped
+    };
+    if let Some(value) = unmapped_value {
+        fill_unmapped(
+            &mut output_file,
+            &fmap,
+            fmap_offset,
+            &ops,
+            args.base,
+            image_size,
+            value,
+        )?;
+    }
 
-    let mut errors_encountered = false;
-    for (area_name, path) in args.params.iter() {
-        let ar = match fmap.get(area_name) {
+    let mut failures = Vec::new();
+    let mut area_errors = Vec::new();
+    let mut records = Vec::new();
+    for op in ops.iter() {
+        let area_name = &op.area;
+        let ar = match op.find_area(&fmap) {
+            None if args.ignore_missing => {
+                warn!("FlashMap area '{}' not found, skipping", area_name);
+                continue;
+            }
             None => {
                 error!("FlashMap area '{}' not found", area_name);
-                errors_encountered = true;
+                area_errors.push(fmap::FMapError::AreaNotFound(area_name.clone()));
+                failures.push((area_name.clone(), "not found"));
                 continue;
             }
             Some(v) => v,
         };
 
         // Verify area
-        if ar.offset + ar.size > fmap.size {
+        let issues = fmap.area_issues(ar);
+        if issues
+            .iter()
+            .any(|i| matches!(i, fmap::LayoutIssue::OutOfBounds { .. }))
+        {
             error!("Area '{}' stretches beyond image", area_name);
-            errors_encountered = true;
+            area_errors.push(fmap::FMapError::AreaOutOfBounds(area_name.clone()));
+            failures.push((area_name.clone(), "out of bounds"));
             continue;
         }
-
-        let mut area_file = match File::open(path) {
+        let (offset, size) = match op.target_range(ar) {
             Err(e) => {
-                error!("Failed to open file `{path}'. Error: {e}");
-                errors_encountered = true;
+                error!("{e}");
+                failures.push((area_name.clone(), "invalid range"));
                 continue;
             }
             Ok(v) => v,
         };
 
-        let mut buf = vec![args.fill_value; ar.size as usize];
-        match area_file.read(&mut buf) {
+        let image_offset = match common::offset_in_image(offset, size, args.base, image_size) {
             Err(e) => {
-                error!("Failed to read file `{path}': Error: {e}");
-                errors_encountered = true;
+                error!("Area '{area_name}': {e}");
+                area_errors.push(fmap::FMapError::AreaOutOfBounds(area_name.clone()));
+                failures.push((area_name.clone(), "out of bounds"));
                 continue;
             }
-            Ok(v) => {
-                info!("Read {v} bytes from `{path}'");
-            }
+            Ok(v) => v,
         };
 
-        if let Err(e) = output_file.seek(SeekFrom::Start(ar.offset as u64)) {
+        let mut buf = vec![0u8; size as usize];
+        let mut data_size = 0;
+        let source;
+        if op.erase {
+            info!("Erasing {size} bytes of area '{area_name}'");
+            source = String::from("erase");
+        } else {
+            let path = match &op.file {
+                None => {
+                    error!("Loading area '{area_name}' requires input file or erase");
+                    failures.push((area_name.clone(), "no input file"));
+                    continue;
+                }
+                Some(v) => v,
+            };
+            let (mut area_file, file_size): (Box<dyn Read>, Option<u64>) = if path == "-" {
+                (Box::new(stdin().lock()), None)
+            } else {
+                match File::open(path) {
+                    Err(e) => {
+                        error!("Failed to open file `{path}'. Error: {e}");
+                        failures.push((area_name.clone(), "open error"));
+                        continue;
+                    }
+                    Ok(v) => {
+                        let len = v.metadata().map(|m| m.len()).ok();
+                        (Box::new(v), len)
+                    }
+                }
+            };
+
+            if args.verify_crc {
+                let mut data = Vec::new();
+                if let Err(e) = area_file.read_to_end(&mut data) {
+                    error!("Failed to read file `{path}': Error: {e}");
+                    failures.push((area_name.clone(), "read error"));
+                    continue;
+                }
+                let content = match crc_footer::verify_and_strip(&data) {
+                    Err(e) => {
+                        error!("Failed to verify file `{path}': {e}");
+                        failures.push((area_name.clone(), "CRC mismatch"));
+                        continue;
+                    }
+                    Ok(v) => v,
+                };
+                if content.len() > buf.len() {
+                    error!(
+                        "File `{path}' content ({} bytes) does not fit in area '{area_name}'",
+                        content.len()
+                    );
+                    failures.push((area_name.clone(), "too large"));
+                    continue;
+                }
+                buf[..content.len()].copy_from_slice(content);
+                data_size = content.len();
+                info!("Read {data_size} bytes from `{path}'");
+            } else {
+                match read_up_to(&mut area_file, &mut buf) {
+                    Err(e) => {
+                        error!("Failed to read file `{path}': Error: {e}");
+                        failures.push((area_name.clone(), "read error"));
+                        continue;
+                    }
+                    Ok(v) => {
+                        data_size = v;
+                        info!("Read {v} bytes from `{path}'");
+                    }
+                };
+                if args.strict_size && data_size == buf.len() {
+                    match area_file.read(&mut [0u8; 1]) {
+                        Ok(0) => (),
+                        Ok(_) => {
+                            error!("File `{path}' does not fit in area '{area_name}'");
+                            failures.push((area_name.clone(), "too large"));
+                            continue;
+                        }
+                        Err(e) => {
+                            error!("Failed to read file `{path}': Error: {e}");
+                            failures.push((area_name.clone(), "read error"));
+                            continue;
+                        }
+                    }
+                }
+            }
+            source = format!("`{path}' ({} bytes)", file_size.unwrap_or(data_size as u64));
+        }
+        if args.dry_run {
+            println!("{area_name}: offset {offset:#x}, size {size:#x} <- {source}");
+        }
+        fill_with_pattern(&mut buf[data_size..], &args.fill_value);
+
+        if let Err(e) = output_file.seek(SeekFrom::Start(image_offset)) {
             error!("Failed to write to the area '{area_name}', Error: {e}");
-            errors_encountered = true;
+            failures.push((area_name.clone(), "write error"));
             continue;
         }
 
-        if let Err(e) = output_file.write(&buf) {
+        if let Err(e) = output_file.write_all(&buf) {
             error!("Failed to write to the area '{area_name}', Error: {e}");
-            errors_encountered = true;
+            failures.push((area_name.clone(), "write error"));
+            continue;
         }
+        info!(
+            "Area '{area_name}': [{offset:#x}, {:#x}) <- {data_size} bytes from {source}, {} bytes of padding",
+            offset as u64 + size as u64,
+            size as usize - data_size
+        );
+        records.push(LoadRecord {
+            area: area_name.clone(),
+            offset,
+            size,
+            file: if op.erase { None } else { op.file.clone() },
+            data_bytes: data_size,
+            padding_bytes: size as usize - data_size,
+        });
     }
 
-    if errors_encountered {
+    if !failures.is_empty() {
+        error!("{}", ops::failure_summary(&failures, ops.len()));
+    }
+    fmap::FMapError::collect(area_errors)?;
+    if !failures.is_empty() {
         return Err("Errors occured during loading".into());
     }
+    if args.dry_run {
+        return Ok(records);
+    }
+    output_file.seek(SeekFrom::Start(0))?;
     match &args.output {
         Some(path) => {
             let mut final_file = match File::create(path) {
@@ -109,6 +486,17 @@ pub fn run_command(args: &LoadFmapArgs) -> Result<(), Box<dyn Error>> {
             }
         }
         None => {
+            if let Some(suffix) = &args.backup {
+                let path = backup_path(&args.image, suffix);
+                if let Err(e) = std::fs::copy(&args.image, &path) {
+                    return Err(format!(
+                        "Failed to create backup `{path}'. Image left untouched. Error: {e}"
+                    )
+                    .into());
+                }
+                info!("Created backup `{path}'");
+            }
+            input_file.seek(SeekFrom::Start(0))?;
             if let Err(e) = std::io::copy(&mut output_file, &mut input_file) {
                 return Err(format!(
                     "Failed to move data from workbuffer to the output file. Error: {e}"
@@ -117,5 +505,359 @@ pub fn run_command(args: &LoadFmapArgs) -> Result<(), Box<dyn Error>> {
             }
         }
     }
-    Ok(())
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::extract_fmap;
+
+    fn example_fmap_path() -> Utf8PathBuf {
+        let mut d = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/example_fmap.bin");
+        d
+    }
+
+    #[test]
+    fn test_fill_with_pattern() {
+        let mut buf = [0u8; 7];
+        fill_with_pattern(&mut buf, &[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(buf, [0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe]);
+
+        let mut buf = [0u8; 5];
+        fill_with_pattern(&mut buf[2..], &[0xff]);
+        assert_eq!(buf, [0, 0, 0xff, 0xff, 0xff]);
+
+        let mut buf = [0u8; 3];
+        fill_with_pattern(&mut buf, &[0x01, 0x02]);
+        assert_eq!(buf, [0x01, 0x02, 0x01]);
+    }
+
+    #[test]
+    fn test_read_up_to() -> Result<(), Box<dyn Error>> {
+        // Reader returning data in small chunks, like pipes do.
+        let data = [1u8, 2, 3, 4, 5, 6, 7];
+        let mut reader = std::io::Cursor::new(&data[..]).chain(&data[..2]);
+        let mut buf = [0u8; 8];
+        assert_eq!(read_up_to(&mut reader, &mut buf)?, 8);
+        assert_eq!(buf, [1, 2, 3, 4, 5, 6, 7, 1]);
+        assert_eq!(read_up_to(&mut reader, &mut buf)?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_stdin_inputs_rejected() -> Result<(), String> {
+        let args = LoadFmapArgs {
+            image: example_fmap_path(),
+            params: vec![
+                ("normal".to_string(), Utf8PathBuf::from("-")),
+                ("data".to_string(), Utf8PathBuf::from("-")),
+            ],
+            output: Some(Utf8PathBuf::from("unused.bin")),
+            fill_value: vec![0xff],
+            ..Default::default()
+        };
+        match run_command(&args) {
+            Ok(_) => Err("Loading two sections from stdin expected to fail".to_string()),
+            Err(_) => Ok(()),
+        }
+    }
+
+    #[test]
+    fn test_crc_round_trip() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let area_path = workdir.join("normal.bin");
+        let output_path = workdir.join("output.bin");
+
+        extract_fmap::run_command(&extract_fmap::ExtractFmapArgs {
+            image: example_fmap_path(),
+            params: vec![("normal".to_string(), area_path.clone())],
+            with_crc: true,
+            ..Default::default()
+        })?;
+        assert_eq!(
+            std::fs::metadata(&area_path)?.len(),
+            0x80 + crc_footer::FOOTER_SIZE as u64
+        );
+
+        let mut load_args = LoadFmapArgs {
+            image: example_fmap_path(),
+            params: vec![("normal".to_string(), area_path.clone())],
+            output: Some(output_path.clone()),
+            fill_value: vec![0xff],
+            verify_crc: true,
+            ..Default::default()
+        };
+        run_command(&load_args)?;
+        assert_eq!(
+            std::fs::read(&output_path)?,
+            std::fs::read(example_fmap_path())?
+        );
+
+        // Tamper with the area content.
+        let mut data = std::fs::read(&area_path)?;
+        data[0] ^= 0xff;
+        std::fs::write(&area_path, data)?;
+        load_args.output = Some(workdir.join("tampered.bin"));
+        if run_command(&load_args).is_ok() {
+            return Err("Loading tampered file expected to fail".into());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_zero_areas() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let area_path = workdir.join("data.bin");
+        std::fs::write(&area_path, [0u8; 0x10])?;
+        let mut image = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        image.push("resources/test/zero_areas_fmap.bin");
+
+        let args = LoadFmapArgs {
+            image,
+            params: vec![("data".to_string(), area_path)],
+            output: Some(workdir.join("output.bin")),
+            fill_value: vec![0xff],
+            ..Default::default()
+        };
+        match run_command(&args) {
+            Ok(_) => return Err("Loading into FlashMap without areas expected to fail".into()),
+            Err(e) => assert_eq!(e.to_string(), "No areas in FlashMap `ZERO'"),
+        }
+        assert!(!workdir.join("output.bin").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_unmapped() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let area_path = workdir.join("normal.bin");
+        std::fs::write(&area_path, [0xaau8; 0x80])?;
+
+        let args = LoadFmapArgs {
+            image: example_fmap_path(),
+            params: vec![("normal".to_string(), area_path)],
+            output: Some(workdir.join("output.bin")),
+            fill_value: vec![0xff],
+            zero_unmapped: true,
+            ..Default::default()
+        };
+        run_command(&args)?;
+
+        let original = std::fs::read(example_fmap_path())?;
+        let fmap_end = 0x200 + fmap::HEADER_SIZE + 4 * fmap::AREA_SIZE;
+        let mut expected = vec![0u8; 0x400];
+        expected[0x80..0x100].fill(0xaa);
+        expected[0x200..fmap_end].copy_from_slice(&original[0x200..fmap_end]);
+        assert_eq!(std::fs::read(workdir.join("output.bin"))?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_records() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        std::fs::write(workdir.join("normal.bin"), [0u8; 0x80])?;
+        std::fs::write(workdir.join("fallback.bin"), [0u8; 0x10])?;
+
+        let args = LoadFmapArgs {
+            image: example_fmap_path(),
+            params: vec![
+                ("normal".to_string(), workdir.join("normal.bin")),
+                ("fallback".to_string(), workdir.join("fallback.bin")),
+            ],
+            output: Some(workdir.join("output.bin")),
+            fill_value: vec![0xff],
+            ..Default::default()
+        };
+        assert_eq!(
+            load(&args)?,
+            [
+                LoadRecord {
+                    area: "normal".to_string(),
+                    offset: 0x80,
+                    size: 0x80,
+                    file: Some(workdir.join("normal.bin")),
+                    data_bytes: 0x80,
+                    padding_bytes: 0,
+                },
+                LoadRecord {
+                    area: "fallback".to_string(),
+                    offset: 0x100,
+                    size: 0x100,
+                    file: Some(workdir.join("fallback.bin")),
+                    data_bytes: 0x10,
+                    padding_bytes: 0xf0,
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignore_missing_areas() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let area_path = workdir.join("normal.bin");
+        std::fs::write(&area_path, [0u8; 0x80])?;
+
+        let mut args = LoadFmapArgs {
+            image: example_fmap_path(),
+            params: vec![
+                ("missing".to_string(), workdir.join("missing.bin")),
+                ("normal".to_string(), area_path),
+            ],
+            output: Some(workdir.join("output.bin")),
+            fill_value: vec![0xff],
+            ..Default::default()
+        };
+        if run_command(&args).is_ok() {
+            return Err("Loading missing area expected to fail".into());
+        }
+
+        args.ignore_missing = true;
+        run_command(&args)?;
+        let mut expected = std::fs::read(example_fmap_path())?;
+        expected[0x80..0x100].copy_from_slice(&[0u8; 0x80]);
+        assert_eq!(std::fs::read(workdir.join("output.bin"))?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run_does_not_modify_image() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image_path = workdir.join("image.bin");
+        std::fs::copy(example_fmap_path(), &image_path)?;
+        let area_path = workdir.join("normal.bin");
+        std::fs::write(&area_path, [0u8; 0x10])?;
+
+        let mut args = LoadFmapArgs {
+            image: image_path.clone(),
+            params: vec![("normal".to_string(), area_path)],
+            fill_value: vec![0xff],
+            dry_run: true,
+            ..Default::default()
+        };
+        run_command(&args)?;
+        assert_eq!(
+            std::fs::read(&image_path)?,
+            std::fs::read(example_fmap_path())?
+        );
+
+        args.params = vec![("missing".to_string(), workdir.join("missing.bin"))];
+        if run_command(&args).is_ok() {
+            return Err("Dry run with invalid mapping expected to fail".into());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_into_compressed_image() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let original = std::fs::read(example_fmap_path())?;
+        let image_path = workdir.join("image.bin.gz");
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(&image_path)?,
+            flate2::Compression::default(),
+        );
+        encoder.write_all(&original)?;
+        encoder.finish()?;
+        let area_path = workdir.join("normal.bin");
+        std::fs::write(&area_path, [0u8; 0x80])?;
+
+        let mut args = LoadFmapArgs {
+            image: image_path,
+            params: vec![("normal".to_string(), area_path)],
+            fill_value: vec![0xff],
+            ..Default::default()
+        };
+        if run_command(&args).is_ok() {
+            return Err("In-place load into compressed image expected to fail".into());
+        }
+
+        args.output = Some(workdir.join("output.bin"));
+        run_command(&args)?;
+        let mut expected = original;
+        expected[0x80..0x100].copy_from_slice(&[0u8; 0x80]);
+        assert_eq!(std::fs::read(workdir.join("output.bin"))?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup_before_in_place_load() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image_path = workdir.join("image.bin");
+        std::fs::copy(example_fmap_path(), &image_path)?;
+        let area_path = workdir.join("normal.bin");
+        std::fs::write(&area_path, [0u8; 0x80])?;
+
+        let mut args = LoadFmapArgs {
+            image: image_path.clone(),
+            params: vec![("normal".to_string(), area_path)],
+            fill_value: vec![0xff],
+            backup: Some("bak".to_string()),
+            ..Default::default()
+        };
+        run_command(&args)?;
+        let original = std::fs::read(example_fmap_path())?;
+        assert_eq!(std::fs::read(workdir.join("image.bin.bak"))?, original);
+        let modified = std::fs::read(&image_path)?;
+        assert_eq!(&modified[0x80..0x100], &[0u8; 0x80]);
+
+        // Backup in non-existent directory fails before touching the image.
+        args.backup = Some("d/bak".to_string());
+        std::fs::write(workdir.join("normal.bin"), [1u8; 0x80])?;
+        if run_command(&args).is_ok() {
+            return Err("Load with failing backup expected to fail".into());
+        }
+        assert_eq!(std::fs::read(&image_path)?, modified);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pad_to() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let area_path = workdir.join("normal.bin");
+        let output_path = workdir.join("padded.bin");
+        std::fs::write(&area_path, [1u8, 2, 3, 4])?;
+
+        let mut args = LoadFmapArgs {
+            image: example_fmap_path(),
+            params: vec![("normal".to_string(), area_path)],
+            output: Some(output_path.clone()),
+            fill_value: vec![0xa5],
+            pad_to: Some(0x800),
+            ..Default::default()
+        };
+        run_command(&args)?;
+        let mut expected = std::fs::read(example_fmap_path())?;
+        expected[0x80..0x100].fill(0xa5);
+        expected[0x80..0x84].copy_from_slice(&[1, 2, 3, 4]);
+        expected.resize(0x800, 0xa5);
+        assert_eq!(std::fs::read(&output_path)?, expected);
+
+        args.pad_to = Some(0x3ff);
+        if run_command(&args).is_ok() {
+            return Err("Padding to size smaller than image expected to fail".into());
+        }
+
+        Ok(())
+    }
 }
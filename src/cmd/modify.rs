@@ -0,0 +1,317 @@
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use camino::Utf8PathBuf;
+use clap::{Args, ValueHint};
+use log::info;
+
+use crate::{
+    cmd::{common, image},
+    fmap,
+};
+
+/// Offset of base field within serialized FMap header.
+const HEADER_BASE_OFFSET: usize = fmap::SIGNATURE.len() + 1 + 1;
+
+/// Offset of flags field within serialized FMap area.
+const AREA_FLAGS_OFFSET: usize = 4 + 4 + fmap::NAME_LEN;
+
+#[derive(Args, Default)]
+pub struct ModifyArgs {
+    #[arg(required = true, index = 1, value_hint = ValueHint::FilePath, value_parser = common::file_exists_validator)]
+    /// Firmware image path.
+    pub(in crate::cmd) image: Utf8PathBuf,
+
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    /// Output file path. Image is modified in place if not given.
+    pub(in crate::cmd) output: Option<Utf8PathBuf>,
+
+    #[arg(long, value_name = "SECTION:FLAG", value_parser = common::area_flag_param_valid)]
+    /// Set flag of the area, e.g. RW_NVRAM:PRESERVE. Can be repeated.
+    pub(in crate::cmd) set_flag: Vec<(String, fmap::FMapFlags)>,
+
+    #[arg(long, value_name = "SECTION:FLAG", value_parser = common::area_flag_param_valid)]
+    /// Clear flag of the area, e.g. RO_SECTION:RO. Can be repeated. Applied after --set-flag.
+    pub(in crate::cmd) clear_flag: Vec<(String, fmap::FMapFlags)>,
+
+    #[arg(long, value_name = "DELTA", allow_hyphen_values = true, value_parser = common::signed_decimal_or_hex_validator_i64)]
+    /// Add DELTA (may be negative) to FlashMap base and offsets of all areas.
+    /// Area data is not moved.
+    pub(in crate::cmd) relocate: Option<i64>,
+
+    #[arg(long, value_name = "SIZE", value_parser = common::decimal_or_hex_validator_u64)]
+    /// Search for FlashMap only within first SIZE bytes of the image.
+    pub(in crate::cmd) max_scan: Option<u64>,
+
+    #[arg(long, value_name = "N", value_parser = common::alignment_validator)]
+    /// Check only offsets aligned to N bytes, a power of two, when searching for FlashMap.
+    pub(in crate::cmd) align: Option<usize>,
+}
+
+/// Flag changes requested by `args` as (area name, flag, set) in order of application.
+fn flag_changes(args: &ModifyArgs) -> impl Iterator<Item = (&String, fmap::FMapFlags, bool)> {
+    args.set_flag
+        .iter()
+        .map(|(area, flag)| (area, *flag, true))
+        .chain(
+            args.clear_flag
+                .iter()
+                .map(|(area, flag)| (area, *flag, false)),
+        )
+}
+
+/// Applies flag changes to areas of `fmap`. Returns indices of modified areas.
+fn modify_flags(fmap: &mut fmap::FMap, args: &ModifyArgs) -> Result<Vec<usize>, fmap::FMapError> {
+    let mut modified = Vec::new();
+    let mut errors = Vec::new();
+    for (area_name, flag, value) in flag_changes(args) {
+        match fmap.areas.iter().position(|ar| ar.name == *area_name) {
+            None => errors.push(fmap::FMapError::AreaNotFound(area_name.clone())),
+            Some(index) => {
+                fmap.areas[index].flags.set(flag, value);
+                if !modified.contains(&index) {
+                    modified.push(index);
+                }
+            }
+        }
+    }
+    fmap::FMapError::collect(errors)?;
+    Ok(modified)
+}
+
+/// Applies flag changes of area `name` to its flags `bits` as stored in the image. Bits
+/// unknown to `FMapFlags`, which are dropped when area is decoded, are kept.
+fn modify_raw_flags(bits: u16, name: &str, args: &ModifyArgs) -> u16 {
+    flag_changes(args)
+        .filter(|(area, _, _)| *area == name)
+        .fold(bits, |bits, (_, flag, value)| {
+            if value {
+                bits | flag.bits()
+            } else {
+                bits & !flag.bits()
+            }
+        })
+}
+
+pub fn run_command(args: &ModifyArgs) -> Result<(), Box<dyn Error>> {
+    let mut image_data = image::open(&args.image)?;
+    let (mut fmap, fmap_offset) = fmap::FMap::find_fmap_with_opts(
+        &mut image_data,
+        &fmap::SearchOptions {
+            max_scan: args.max_scan,
+            min_align: args.align,
+            ..Default::default()
+        },
+    )?;
+    let modified = modify_flags(&mut fmap, args)?;
+    if let Some(delta) = args.relocate {
+        fmap.relocate(delta)?;
+    }
+
+    let mut output_file = match &args.output {
+        Some(path) => {
+            image_data.seek(SeekFrom::Start(0))?;
+            std::io::copy(&mut image_data, &mut File::create(path)?)?;
+            OpenOptions::new().write(true).open(path)?
+        }
+        None => {
+            if image::detect_compression(&mut File::open(&args.image)?)? != image::Compression::None
+            {
+                return Err(
+                    "Compressed image cannot be modified in place. Please use --output".into(),
+                );
+            }
+            OpenOptions::new().write(true).open(&args.image)?
+        }
+    };
+
+    // Only changed fields are patched in place, so raw bytes the decoder does not keep, e.g.
+    // unknown flag bits, stay intact.
+    if args.relocate.is_some() {
+        output_file.seek(SeekFrom::Start((fmap_offset + HEADER_BASE_OFFSET) as u64))?;
+        output_file.write_all(&fmap.base.to_le_bytes())?;
+        for (index, area) in fmap.areas.iter().enumerate() {
+            let offset = fmap_offset + fmap::HEADER_SIZE + index * fmap::AREA_SIZE;
+            output_file.seek(SeekFrom::Start(offset as u64))?;
+            output_file.write_all(&area.offset.to_le_bytes())?;
+        }
+        info!("FlashMap relocated, base set to {:#x}", fmap.base);
+    }
+
+    for index in modified {
+        let area = &fmap.areas[index];
+        let offset = fmap_offset + fmap::HEADER_SIZE + index * fmap::AREA_SIZE + AREA_FLAGS_OFFSET;
+        let mut bits = [0u8; 2];
+        image_data.seek(SeekFrom::Start(offset as u64))?;
+        image_data.read_exact(&mut bits)?;
+        let bits = modify_raw_flags(u16::from_le_bytes(bits), &area.name, args);
+        output_file.seek(SeekFrom::Start(offset as u64))?;
+        output_file.write_all(&bits.to_le_bytes())?;
+        info!("Area '{}' flags set to {:#x}", area.name, bits);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_fmap_path() -> Utf8PathBuf {
+        let mut d = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/example_fmap.bin");
+        d
+    }
+
+    #[test]
+    fn test_modify_flags() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let output_path = workdir.join("modified.bin");
+
+        run_command(&ModifyArgs {
+            image: example_fmap_path(),
+            output: Some(output_path.clone()),
+            set_flag: vec![
+                ("data".to_string(), fmap::FMapFlags::Preserve),
+                ("normal".to_string(), fmap::FMapFlags::RO),
+            ],
+            clear_flag: vec![("normal".to_string(), fmap::FMapFlags::Compressed)],
+            ..Default::default()
+        })?;
+
+        let original = std::fs::read(example_fmap_path())?;
+        let modified = std::fs::read(&output_path)?;
+        let (fmap, _) = fmap::FMap::from_bytes(&modified)?;
+        let flags = fmap.areas.iter().map(|ar| ar.flags).collect::<Vec<_>>();
+        assert_eq!(
+            flags,
+            vec![
+                fmap::FMapFlags::Static,
+                fmap::FMapFlags::Static | fmap::FMapFlags::RO,
+                fmap::FMapFlags::Static | fmap::FMapFlags::Compressed,
+                fmap::FMapFlags::Preserve,
+            ]
+        );
+        // Only flags of two areas differ.
+        let diff = original
+            .iter()
+            .zip(modified.iter())
+            .filter(|(a, b)| a != b)
+            .count();
+        assert_eq!(original.len(), modified.len());
+        assert_eq!(diff, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_modify_flags_keeps_unknown_bits() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image = workdir.join("image.bin");
+        let output_path = workdir.join("modified.bin");
+        // Flags of "normal" area: STATIC, RO and unknown bit 4.
+        let flags = 0x200 + fmap::HEADER_SIZE + 2 * fmap::AREA_SIZE - 2;
+        let mut data = std::fs::read(example_fmap_path())?;
+        data[flags..flags + 2].copy_from_slice(&0x15u16.to_le_bytes());
+        std::fs::write(&image, &data)?;
+
+        run_command(&ModifyArgs {
+            image,
+            output: Some(output_path.clone()),
+            set_flag: vec![("normal".to_string(), fmap::FMapFlags::Preserve)],
+            clear_flag: vec![("normal".to_string(), fmap::FMapFlags::RO)],
+            ..Default::default()
+        })?;
+
+        let modified = std::fs::read(&output_path)?;
+        assert_eq!(modified[flags..flags + 2], 0x19u16.to_le_bytes());
+        assert_eq!(modified[..flags], data[..flags]);
+        assert_eq!(modified[flags + 2..], data[flags + 2..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relocate() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let output_path = workdir.join("relocated.bin");
+
+        let mut args = ModifyArgs {
+            image: example_fmap_path(),
+            output: Some(output_path.clone()),
+            set_flag: vec![("data".to_string(), fmap::FMapFlags::Preserve)],
+            relocate: Some(0x1000),
+            ..Default::default()
+        };
+        run_command(&args)?;
+        let original = std::fs::read(example_fmap_path())?;
+        let relocated = std::fs::read(&output_path)?;
+        let (fmap, offset) = fmap::FMap::from_bytes(&relocated)?;
+        assert_eq!(offset, 0x200);
+        assert_eq!(fmap.base, 0x1000);
+        assert_eq!(
+            fmap.areas.iter().map(|ar| ar.offset).collect::<Vec<_>>(),
+            vec![0x1000, 0x1080, 0x1100, 0x1200]
+        );
+        assert_eq!(fmap.areas[3].flags, fmap::FMapFlags::Preserve);
+        // Area data outside of FMap is untouched.
+        assert_eq!(relocated[..0x200], original[..0x200]);
+
+        args.relocate = Some(-1);
+        assert!(matches!(
+            run_command(&args)
+                .unwrap_err()
+                .downcast_ref::<fmap::FMapError>(),
+            Some(fmap::FMapError::InvalidRelocation(-1))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relocate_keeps_unknown_flag_bits() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image = workdir.join("image.bin");
+        let output_path = workdir.join("relocated.bin");
+        // Flags of "normal" area: STATIC, RO and unknown bit 4.
+        let flags = 0x200 + fmap::HEADER_SIZE + 2 * fmap::AREA_SIZE - 2;
+        let mut data = std::fs::read(example_fmap_path())?;
+        data[flags..flags + 2].copy_from_slice(&0x15u16.to_le_bytes());
+        std::fs::write(&image, &data)?;
+
+        run_command(&ModifyArgs {
+            image,
+            output: Some(output_path.clone()),
+            relocate: Some(0x1000),
+            ..Default::default()
+        })?;
+
+        let relocated = std::fs::read(&output_path)?;
+        assert_eq!(relocated[flags..flags + 2], 0x15u16.to_le_bytes());
+        let (fmap, _) = fmap::FMap::from_bytes(&relocated)?;
+        assert_eq!(fmap.base, 0x1000);
+        assert_eq!(
+            fmap.areas.iter().map(|ar| ar.offset).collect::<Vec<_>>(),
+            vec![0x1000, 0x1080, 0x1100, 0x1200]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_modify_missing_area() {
+        let mut fmap = fmap::FMap::default();
+        let args = ModifyArgs {
+            set_flag: vec![("MISSING".to_string(), fmap::FMapFlags::RO)],
+            ..Default::default()
+        };
+        assert!(matches!(
+            modify_flags(&mut fmap, &args),
+            Err(fmap::FMapError::AreaNotFound(name)) if name == "MISSING"
+        ));
+    }
+}
@@ -0,0 +1,293 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use log::warn;
+use serde::Deserialize;
+
+use itertools::Itertools;
+
+use crate::{cmd::common, fmap};
+
+/// Single extract/load operation. Batch of operations can be provided as JSON list, e.g.
+/// `[{"area": "FW_MAIN_A", "file": "a.bin", "range": [0, 1024]}]`.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Op {
+    /// Name of the FlashMap area.
+    pub area: String,
+    /// File to extract the data to or load the data from.
+    #[serde(default)]
+    pub file: Option<Utf8PathBuf>,
+    /// Byte range [start, end) relative to the area start. Whole area if not provided.
+    #[serde(default)]
+    pub range: Option<[u32; 2]>,
+    /// Selects n-th (starting from 0) area if multiple areas share the same name.
+    #[serde(default)]
+    pub index: Option<usize>,
+    /// Selects area starting at this offset if multiple areas share the same name.
+    #[serde(default)]
+    pub offset: Option<u32>,
+    /// Fill the range with fill value instead of loading a file. Load only.
+    #[serde(default)]
+    pub erase: bool,
+    /// Expected SHA-256 of extracted data as hex string. Extract only.
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+impl Op {
+    /// Finds area selected by the operation. Warns if the name is ambiguous and neither
+    /// index nor offset is given, in which case the first area with the name is used.
+    pub fn find_area<'a>(&self, fmap: &'a fmap::FMap) -> Option<&'a fmap::FMapArea> {
+        let mut named = fmap.areas.iter().filter(|a| a.name == self.area);
+        match (self.index, self.offset) {
+            (Some(i), _) => named.nth(i),
+            (None, Some(offset)) => named.find(|a| a.offset == offset),
+            (None, None) => {
+                let first = named.next()?;
+                if named.next().is_some() {
+                    warn!(
+                        "Multiple areas named '{}', using the one at {:#x}. Append @OFFSET \
+                         to the section to select another one",
+                        self.area, first.offset
+                    );
+                }
+                Some(first)
+            }
+        }
+    }
+
+    /// Returns (offset, size) in the image of data targeted by the operation.
+    pub fn target_range(&self, area: &fmap::FMapArea) -> Result<(u32, u32), String> {
+        match self.range {
+            None => Ok((area.offset, area.size)),
+            Some([start, end]) => {
+                if start > end || end > area.size {
+                    return Err(format!(
+                        "Range [{start:#x}, {end:#x}) does not fit in area '{}' of size {:#x}",
+                        area.name, area.size
+                    ));
+                }
+                Ok((area.offset + start, end - start))
+            }
+        }
+    }
+}
+
+/// Converts SECTION:FILE mappings into operations. SECTION may be followed by offset
+/// qualifier, see `common::split_offset_qualifier`, and FILE by expected digest, see
+/// `common::split_digest`. Empty FILE leaves operation without file.
+pub fn from_params(params: &[(String, Utf8PathBuf)]) -> Vec<Op> {
+    params
+        .iter()
+        .map(|(area, file)| {
+            let (area, offset) =
+                common::split_offset_qualifier(area).unwrap_or((area.as_str(), None));
+            let (file, sha256) =
+                common::split_digest(file.as_str()).unwrap_or((file.as_str(), None));
+            Op {
+                area: area.to_string(),
+                offset,
+                file: Some(Utf8PathBuf::from(file)).filter(|f| !f.as_str().is_empty()),
+                sha256,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Replaces operations with glob patterns (`*`, `?`) in area name with operations on each
+/// matching area. `%s` in the file path is substituted with the matched area name.
+pub fn expand_globs(ops: Vec<Op>, fmap: &fmap::FMap) -> Result<Vec<Op>, String> {
+    let mut expanded = Vec::new();
+    for op in ops.into_iter() {
+        if !common::is_glob(&op.area) {
+            expanded.push(op);
+            continue;
+        }
+        let names = fmap
+            .areas
+            .iter()
+            .map(|a| &a.name)
+            .filter(|name| common::glob_match(&op.area, name))
+            .unique()
+            .collect_vec();
+        if names.is_empty() {
+            return Err(format!("Pattern '{}' does not match any area", op.area));
+        }
+        for name in names {
+            expanded.push(Op {
+                area: name.clone(),
+                file: op
+                    .file
+                    .as_ref()
+                    .map(|f| Utf8PathBuf::from(f.as_str().replace("%s", name))),
+                ..op.clone()
+            });
+        }
+    }
+    Ok(expanded)
+}
+
+pub fn read_ops_file(path: &Utf8Path) -> Result<Vec<Op>, Box<dyn Error>> {
+    let file = File::open(path)?;
+    match serde_json::from_reader(BufReader::new(file)) {
+        Ok(ops) => Ok(ops),
+        Err(e) => Err(format!("Failed to parse operations file `{path}'. Error: {e}").into()),
+    }
+}
+
+/// Rejects operations on named areas when FlashMap has no areas at all. Offset ranges
+/// (`@start:end`) do not depend on areas and are always accepted.
+pub fn check_areas_present(ops: &[Op], fmap: &fmap::FMap) -> Result<(), String> {
+    if fmap.areas.is_empty() && (ops.is_empty() || ops.iter().any(|op| !op.area.starts_with('@'))) {
+        return Err(format!("No areas in FlashMap `{}'", fmap.name));
+    }
+    Ok(())
+}
+
+/// Formats summary of failed operations, e.g. "2 of 5 areas failed: FOO (not found), BAR (read error)".
+pub fn failure_summary(failures: &[(String, &str)], total: usize) -> String {
+    format!(
+        "{} of {} areas failed: {}",
+        failures.len(),
+        total,
+        failures
+            .iter()
+            .map(|(area, reason)| format!("{area} ({reason})"))
+            .join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ops() -> Result<(), String> {
+        let ops: Vec<Op> = match serde_json::from_str(
+            r#"[{"area": "A", "file": "a.bin", "range": [16, 32]},
+                {"area": "B", "index": 1, "erase": true}]"#,
+        ) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("Failed to parse operations: {e}")),
+        };
+        assert_eq!(
+            ops,
+            vec![
+                Op {
+                    area: "A".to_string(),
+                    file: Some(Utf8PathBuf::from("a.bin")),
+                    range: Some([16, 32]),
+                    ..Default::default()
+                },
+                Op {
+                    area: "B".to_string(),
+                    index: Some(1),
+                    erase: true,
+                    ..Default::default()
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_globs() -> Result<(), String> {
+        let area = |name: &str| fmap::FMapArea {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let fmap = fmap::FMap {
+            areas: vec![area("RW_SECTION_A"), area("VBLOCK_A"), area("RW_SECTION_B")],
+            ..Default::default()
+        };
+        let ops = from_params(&[
+            ("RW_SECTION_?".to_string(), Utf8PathBuf::from("out_%s.bin")),
+            ("VBLOCK_A".to_string(), Utf8PathBuf::from("vblock.bin")),
+        ]);
+        assert_eq!(
+            expand_globs(ops, &fmap)?,
+            from_params(&[
+                (
+                    "RW_SECTION_A".to_string(),
+                    Utf8PathBuf::from("out_RW_SECTION_A.bin")
+                ),
+                (
+                    "RW_SECTION_B".to_string(),
+                    Utf8PathBuf::from("out_RW_SECTION_B.bin")
+                ),
+                ("VBLOCK_A".to_string(), Utf8PathBuf::from("vblock.bin")),
+            ])
+        );
+
+        let ops = from_params(&[("FW_MAIN_*".to_string(), Utf8PathBuf::from("%s.bin"))]);
+        assert!(expand_globs(ops, &fmap).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_target_range() {
+        let area = fmap::FMapArea {
+            name: "A".to_string(),
+            offset: 0x100,
+            size: 0x80,
+            flags: fmap::FMapFlags::empty(),
+        };
+        let mut op = Op {
+            area: "A".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(op.target_range(&area), Ok((0x100, 0x80)));
+        op.range = Some([0x10, 0x20]);
+        assert_eq!(op.target_range(&area), Ok((0x110, 0x10)));
+        op.range = Some([0x10, 0x81]);
+        assert!(op.target_range(&area).is_err());
+        op.range = Some([0x20, 0x10]);
+        assert!(op.target_range(&area).is_err());
+    }
+
+    #[test]
+    fn test_find_area_qualified() {
+        let fmap = fmap::FMap {
+            areas: vec![
+                fmap::FMapArea::new("FW_MAIN_A", 0x10000, 0x1000),
+                fmap::FMapArea::new("FW_MAIN_A", 0x510000, 0x1000),
+            ],
+            ..Default::default()
+        };
+        let ops = from_params(&[
+            (
+                "FW_MAIN_A@0x510000".to_string(),
+                Utf8PathBuf::from("out.bin"),
+            ),
+            ("FW_MAIN_A".to_string(), Utf8PathBuf::from("out.bin")),
+            (
+                "FW_MAIN_A@0x20000".to_string(),
+                Utf8PathBuf::from("out.bin"),
+            ),
+        ]);
+        assert_eq!(ops[0].area, "FW_MAIN_A");
+        assert_eq!(ops[0].offset, Some(0x510000));
+        assert_eq!(ops[0].find_area(&fmap), Some(&fmap.areas[1]));
+        // Ambiguous name without qualifier selects the first area.
+        assert_eq!(ops[1].find_area(&fmap), Some(&fmap.areas[0]));
+        assert_eq!(ops[2].find_area(&fmap), None);
+    }
+
+    #[test]
+    fn test_failure_summary() {
+        let failures = vec![
+            ("FOO".to_string(), "not found"),
+            ("BAR".to_string(), "out of bounds"),
+        ];
+        assert_eq!(
+            failure_summary(&failures, 5),
+            "2 of 5 areas failed: FOO (not found), BAR (out of bounds)"
+        );
+    }
+}
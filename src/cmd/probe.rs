@@ -0,0 +1,115 @@
+use std::error::Error;
+use std::io::{Read, Seek};
+use std::process::exit;
+
+use camino::Utf8PathBuf;
+use clap::{Args, ValueHint};
+
+use crate::{
+    cmd::{common, error, image},
+    fmap,
+};
+
+#[derive(Args)]
+pub struct ProbeArgs {
+    #[arg(required = true, index = 1, value_hint = ValueHint::FilePath, value_parser = common::file_exists_validator)]
+    /// Firmware image path.
+    pub(in crate::cmd) image: Utf8PathBuf,
+
+    #[arg(long, short = 'o', action)]
+    /// Print offset of found FlashMap.
+    pub(in crate::cmd) print_offset: bool,
+
+    #[arg(long, value_name = "SIZE", value_parser = common::decimal_or_hex_validator_u64)]
+    /// Search for FlashMap only within first SIZE bytes of the image.
+    pub(in crate::cmd) max_scan: Option<u64>,
+
+    #[arg(long, value_name = "N", value_parser = common::alignment_validator)]
+    /// Check only offsets aligned to N bytes, a power of two, when searching for FlashMap.
+    pub(in crate::cmd) align: Option<usize>,
+}
+
+/// Returns offset of FlashMap on success or exit code to report on failure.
+fn probe(reader: &mut (impl Read + Seek), opts: &fmap::SearchOptions) -> Result<usize, i32> {
+    match fmap::FMap::find_fmap_with_opts(reader, opts) {
+        Ok((_, offset)) => Ok(offset),
+        Err(e) => Err(error::error_kind(&e).1),
+    }
+}
+
+pub fn run_command(args: &ProbeArgs) -> Result<(), Box<dyn Error>> {
+    let opts = fmap::SearchOptions {
+        max_scan: args.max_scan,
+        min_align: args.align,
+        ..Default::default()
+    };
+    let code = match image::open(&args.image) {
+        Ok(mut input_file) => match probe(&mut input_file, &opts) {
+            Ok(offset) => {
+                if args.print_offset {
+                    println!("{offset:#x}");
+                }
+                return Ok(());
+            }
+            Err(code) => code,
+        },
+        Err(e) => error::error_kind(&e).1,
+    };
+    // Probing reports result only via exit code.
+    exit(code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_probe_example_file() -> Result<(), String> {
+        let mut d = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/example_fmap.bin");
+        let mut fmap_file = match File::open(&d) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(format!(
+                    "Failed to open test resource file `{d}'. Error: {e}"
+                ))
+            }
+        };
+
+        let opts = |max_scan, min_align| fmap::SearchOptions {
+            max_scan,
+            min_align,
+            ..Default::default()
+        };
+        assert_eq!(probe(&mut fmap_file, &opts(None, None)), Ok(0x200));
+        assert_eq!(probe(&mut fmap_file, &opts(None, Some(0x200))), Ok(0x200));
+        // FlashMap lies beyond scanned part of the image.
+        assert_eq!(
+            probe(&mut fmap_file, &opts(Some(0x200), None)),
+            Err(error::EXIT_NOT_FOUND)
+        );
+        // FlashMap is not aligned to required boundary.
+        assert_eq!(
+            probe(&mut fmap_file, &opts(None, Some(0x400))),
+            Err(error::EXIT_NOT_FOUND)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_probe_no_fmap() {
+        let mut reader = Cursor::new(vec![0xffu8; 0x400]);
+        assert_eq!(
+            probe(&mut reader, &Default::default()),
+            Err(error::EXIT_NOT_FOUND)
+        );
+
+        let mut reader = Cursor::new(b"too_short".to_vec());
+        assert_eq!(
+            probe(&mut reader, &Default::default()),
+            Err(error::EXIT_IO_ERROR)
+        );
+    }
+}
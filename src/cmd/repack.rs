@@ -0,0 +1,183 @@
+use std::error::Error;
+use std::process::Command;
+
+use camino::Utf8PathBuf;
+use clap::{Args, ValueHint};
+use log::info;
+
+use crate::cmd::{common, extract_fmap, load_fmap};
+
+#[derive(Args, Default)]
+pub struct RepackArgs {
+    #[arg(required = true, index = 1, value_hint = ValueHint::FilePath, value_parser = common::file_exists_validator)]
+    /// Firmware image path.
+    pub(in crate::cmd) image: Utf8PathBuf,
+
+    #[arg(required = true, index = 2, value_name = "SECTION")]
+    /// Names of FlashMap sections to repack.
+    pub(in crate::cmd) areas: Vec<String>,
+
+    #[arg(long, required = true, value_name = "COMMAND")]
+    /// Shell command run in temporary directory with sections extracted to files named
+    /// after them. The files are also passed to the command as arguments.
+    /// Example: --exec 'patch_fw "$1"'
+    pub(in crate::cmd) exec: String,
+
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    /// Output file path. Image is modified in place if not given.
+    pub(in crate::cmd) output: Option<Utf8PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SUFFIX",
+        default_value = "bak",
+        conflicts_with = "output"
+    )]
+    /// Suffix of the backup copy of the image created before modifying it in place.
+    pub(in crate::cmd) backup: String,
+}
+
+pub fn run_command(args: &RepackArgs) -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    let workdir = match Utf8PathBuf::from_path_buf(dir.path().to_path_buf()) {
+        Ok(v) => v,
+        Err(path) => {
+            return Err(format!(
+                "Temporary directory path `{}' is not valid UTF-8",
+                path.display()
+            )
+            .into())
+        }
+    };
+    let params = args
+        .areas
+        .iter()
+        .map(|area| (area.clone(), workdir.join(area)))
+        .collect::<Vec<_>>();
+
+    extract_fmap::run_command(&extract_fmap::ExtractFmapArgs {
+        image: args.image.clone(),
+        params: params.clone(),
+        ..Default::default()
+    })?;
+
+    info!("Running `{}' in `{workdir}'", args.exec);
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(&args.exec)
+        .arg("sh")
+        .args(args.areas.iter())
+        .current_dir(&workdir)
+        .status()?;
+    if !status.success() {
+        return Err(format!(
+            "Command `{}' failed with {status}. Image left untouched",
+            args.exec
+        )
+        .into());
+    }
+
+    load_fmap::run_command(&load_fmap::LoadFmapArgs {
+        image: args.image.clone(),
+        params,
+        output: args.output.clone(),
+        fill_value: vec![0xff],
+        strict_size: true,
+        backup: match args.output {
+            None => Some(args.backup.clone()),
+            Some(_) => None,
+        },
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn example_image(workdir: &Utf8PathBuf) -> Result<Utf8PathBuf, Box<dyn Error>> {
+        let mut d = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/example_fmap.bin");
+        let image = workdir.join("image.bin");
+        fs::copy(d, &image)?;
+        Ok(image)
+    }
+
+    #[test]
+    fn test_repack_in_place() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image = example_image(&workdir)?;
+        let original = fs::read(&image)?;
+
+        let args = RepackArgs {
+            image: image.clone(),
+            areas: vec!["normal".to_string()],
+            exec: r#"test "$1" = normal && printf patched > normal"#.to_string(),
+            backup: "bak".to_string(),
+            ..Default::default()
+        };
+        run_command(&args)?;
+
+        let mut expected = original.clone();
+        expected[0x80..0x100].fill(0xff);
+        expected[0x80..0x87].copy_from_slice(b"patched");
+        assert_eq!(fs::read(&image)?, expected);
+        assert_eq!(fs::read(workdir.join("image.bin.bak"))?, original);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repack_command_failure() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image = example_image(&workdir)?;
+        let original = fs::read(&image)?;
+
+        let args = RepackArgs {
+            image: image.clone(),
+            areas: vec!["normal".to_string()],
+            exec: "printf patched > normal && false".to_string(),
+            backup: "bak".to_string(),
+            ..Default::default()
+        };
+        if run_command(&args).is_ok() {
+            return Err("Repacking with failing command expected to fail".into());
+        }
+        assert_eq!(fs::read(&image)?, original);
+        assert!(!workdir.join("image.bin.bak").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repack_grown_section() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let workdir = Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+        let image = example_image(&workdir)?;
+        let original = fs::read(&image)?;
+
+        // Section filling its area exactly is loaded back.
+        let mut args = RepackArgs {
+            image: image.clone(),
+            areas: vec!["normal".to_string()],
+            exec: "true".to_string(),
+            backup: "bak".to_string(),
+            ..Default::default()
+        };
+        run_command(&args)?;
+        assert_eq!(fs::read(&image)?, original);
+
+        // Section grown beyond its area is not truncated.
+        fs::remove_file(workdir.join("image.bin.bak"))?;
+        args.exec = "printf x >> normal".to_string();
+        if run_command(&args).is_ok() {
+            return Err("Repacking section larger than its area expected to fail".into());
+        }
+        assert_eq!(fs::read(&image)?, original);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use std::error::Error;
+use std::io::{stdout, Write};
+
+use camino::Utf8PathBuf;
+use clap::{Args, ValueHint};
+
+use crate::{
+    cmd::{common, dump_fmap, image},
+    fmap,
+};
+
+#[derive(Args)]
+pub struct SummaryArgs {
+    #[arg(required = true, index = 1, value_hint = ValueHint::FilePath, value_parser = common::file_exists_validator)]
+    /// Firmware image path.
+    pub(in crate::cmd) image: Utf8PathBuf,
+
+    #[arg(long, short, action)]
+    /// Print summary in single line easy to parse by scripts.
+    /// <flash size> <covered bytes> <unused bytes> <areas> <preserve areas> <utilization %>
+    pub(in crate::cmd) parsable: bool,
+
+    #[arg(long, action)]
+    /// Do not terminate on encountering overlapping sections.
+    pub(in crate::cmd) ignore_overlapping_sections: bool,
+
+    #[arg(long, value_name = "SIZE", value_parser = common::decimal_or_hex_validator_u64)]
+    /// Search for FlashMap only within first SIZE bytes of the image.
+    pub(in crate::cmd) max_scan: Option<u64>,
+
+    #[arg(long, value_name = "N", value_parser = common::alignment_validator)]
+    /// Check only offsets aligned to N bytes, a power of two, when searching for FlashMap.
+    pub(in crate::cmd) align: Option<usize>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    flash_size: usize,
+    covered_bytes: usize,
+    unused_bytes: usize,
+    areas: usize,
+    preserve_areas: usize,
+}
+
+impl Summary {
+    fn utilization(&self) -> f64 {
+        if self.flash_size == 0 {
+            return 0.0;
+        }
+        self.covered_bytes as f64 * 100.0 / self.flash_size as f64
+    }
+}
+
+fn summarize(fmap: &fmap::FMap, ignore_overlap: bool) -> Result<Summary, Box<dyn Error>> {
+    let issues = fmap.validate();
+    dump_fmap::check_overlaps(&issues, fmap.size as u64, ignore_overlap)?;
+
+    Ok(Summary {
+        flash_size: fmap.size as usize,
+        covered_bytes: fmap.covered_bytes() as usize,
+        unused_bytes: fmap.gap_bytes() as usize,
+        areas: fmap.areas.len(),
+        preserve_areas: fmap
+            .areas
+            .iter()
+            .filter(|a| a.flags.contains(fmap::FMapFlags::Preserve))
+            .count(),
+    })
+}
+
+fn dump_summary(summary: &Summary, mut writer: impl Write) -> std::io::Result<()> {
+    writeln!(writer, "flash_size:      {0:#x} ({0})", summary.flash_size)?;
+    writeln!(
+        writer,
+        "covered_bytes:   {0:#x} ({0})",
+        summary.covered_bytes
+    )?;
+    writeln!(
+        writer,
+        "unused_bytes:    {0:#x} ({0})",
+        summary.unused_bytes
+    )?;
+    writeln!(writer, "areas:           {}", summary.areas)?;
+    writeln!(writer, "preserve_areas:  {}", summary.preserve_areas)?;
+    writeln!(writer, "utilization:     {:.2}%", summary.utilization())?;
+    Ok(())
+}
+
+fn dump_summary_parsable(summary: &Summary, mut writer: impl Write) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "{} {} {} {} {} {:.2}",
+        summary.flash_size,
+        summary.covered_bytes,
+        summary.unused_bytes,
+        summary.areas,
+        summary.preserve_areas,
+        summary.utilization()
+    )
+}
+
+pub fn run_command(args: &SummaryArgs) -> Result<(), Box<dyn Error>> {
+    let mut input_file = image::open(&args.image)?;
+    let (fmap, _) = fmap::FMap::find_fmap_with_opts(
+        &mut input_file,
+        &fmap::SearchOptions {
+            max_scan: args.max_scan,
+            min_align: args.align,
+            ..Default::default()
+        },
+    )?;
+
+    let summary = summarize(&fmap, args.ignore_overlapping_sections)?;
+    if args.parsable {
+        dump_summary_parsable(&summary, &mut stdout())?;
+    } else {
+        dump_summary(&summary, &mut stdout())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_fmap_with_gap() -> fmap::FMap {
+        fmap::FMap {
+            name: "example".to_string(),
+            base: 0,
+            size: 0x400,
+            version_major: 1,
+            version_minor: 1,
+            areas: vec![
+                fmap::FMapArea {
+                    name: "RO".to_string(),
+                    offset: 0,
+                    size: 0x200,
+                    flags: fmap::FMapFlags::empty(),
+                },
+                fmap::FMapArea {
+                    name: "bootblock".to_string(),
+                    offset: 0,
+                    size: 0x80,
+                    flags: fmap::FMapFlags::empty(),
+                },
+                fmap::FMapArea {
+                    name: "normal".to_string(),
+                    offset: 0x100,
+                    size: 0x100,
+                    flags: fmap::FMapFlags::Preserve,
+                },
+                fmap::FMapArea {
+                    name: "data".to_string(),
+                    offset: 0x300,
+                    size: 0x100,
+                    flags: fmap::FMapFlags::empty(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_summarize() -> Result<(), String> {
+        let summary = match summarize(&example_fmap_with_gap(), false) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("summarize() failed with error: {e}")),
+        };
+        assert_eq!(
+            summary,
+            Summary {
+                flash_size: 0x400,
+                covered_bytes: 0x280,
+                unused_bytes: 0x180,
+                areas: 4,
+                preserve_areas: 1,
+            }
+        );
+
+        // Area beyond flash is neither an overlap nor covered bytes.
+        let mut fmap = example_fmap_with_gap();
+        fmap.areas.push(fmap::FMapArea::new("BEYOND", 0x800, 0x100));
+        let summary = match summarize(&fmap, false) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("summarize() failed with error: {e}")),
+        };
+        assert_eq!(summary.covered_bytes, 0x280);
+        assert_eq!(summary.unused_bytes, 0x180);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_summary_parsable() -> Result<(), String> {
+        let summary = match summarize(&example_fmap_with_gap(), false) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("summarize() failed with error: {e}")),
+        };
+        let mut result = Vec::new();
+        if let Err(e) = dump_summary_parsable(&summary, &mut result) {
+            return Err(format!("dump_summary_parsable() failed with error: {e}"));
+        }
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "1024 640 384 4 1 62.50\n"
+        );
+
+        Ok(())
+    }
+}
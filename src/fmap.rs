@@ -1,7 +1,19 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
 use bitflags::bitflags;
-use std::io::{Read, Seek, SeekFrom};
-use std::mem;
-use thiserror;
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::convert::TryFrom;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+pub mod decode;
+#[cfg(feature = "std")]
+pub mod tree;
 
 /* FMAP structs. See http://code.google.com/p/flashmap/wiki/FmapSpec */
 bitflags! {
@@ -14,12 +26,51 @@ bitflags! {
     }
 }
 
+impl FMapFlags {
+    /// Uppercase names of set flags, e.g. ["STATIC", "COMPRESSED"].
+    pub fn names(&self) -> Vec<String> {
+        self.iter_names()
+            .map(|(name, _)| name.to_uppercase())
+            .collect()
+    }
+
+    /// Canonical string form of flags: uppercase names joined with `|`, e.g. `STATIC|RO`.
+    /// Empty string if no flag is set.
+    pub fn to_symbolic(&self) -> String {
+        self.names().join("|")
+    }
+
+    /// Parses list of flag names separated with `|` or `,`. Names are case-insensitive.
+    pub fn from_symbolic(s: &str) -> Result<FMapFlags, String> {
+        let mut flags = FMapFlags::empty();
+        for name in s.split(['|', ',']).map(str::trim).filter(|n| !n.is_empty()) {
+            match FMapFlags::all()
+                .iter_names()
+                .find(|(flag_name, _)| flag_name.eq_ignore_ascii_case(name))
+            {
+                Some((_, flag)) => flags |= flag,
+                None => {
+                    return Err(format!(
+                        "Unknown flag '{name}'. Expected one of: {}",
+                        FMapFlags::all().names().join(", ")
+                    ))
+                }
+            }
+        }
+        Ok(flags)
+    }
+}
+
 pub const SEARCH_STRIDE: usize = 4;
 pub const NAME_LEN: usize = 32;
 pub const SIGNATURE: &[u8; 8] = b"__FMAP__";
 pub const VERSION_MAJOR: u32 = 1;
 pub const HEADER_SIZE: usize = SIGNATURE.len() + 1 + 1 + 8 + 4 + NAME_LEN + 2;
+pub const AREA_SIZE: usize = 4 + 4 + NAME_LEN + 2;
 
+/// FlashMap area. Offset and size are stored as `u32`, as in on-disk format of FMAP 1.x.
+/// They are relative to the flash start, which is placed at 64-bit `FMap::base` address.
+/// Values combining both must be computed in 64 bits, e.g. with `FMapArea::address()`.
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct FMapArea {
     pub name: String,
@@ -28,58 +79,107 @@ pub struct FMapArea {
     pub flags: FMapFlags,
 }
 
-#[derive(Debug, Default)]
-#[repr(C, packed)]
-struct FMapAreaRaw {
-    offset: u32,
-    size: u32,
-    name: [u8; NAME_LEN],
-    flags: u16,
-}
-
-impl From<FMapAreaRaw> for FMapArea {
-    fn from(fmap_area_raw: FMapAreaRaw) -> FMapArea {
-        let fmap_name: String = if fmap_area_raw.name.contains(&0_u8) {
-            std::ffi::CStr::from_bytes_until_nul(&fmap_area_raw.name)
-                .unwrap()
-                .to_str()
-                .unwrap_or("")
-        } else {
-            std::str::from_utf8(&fmap_area_raw.name).unwrap_or("")
-        }
-        .to_string();
-
+impl FMapArea {
+    /// Area without flags. Flags can be set with `with_flags()`.
+    pub fn new(name: impl Into<String>, offset: u32, size: u32) -> FMapArea {
         FMapArea {
-            name: fmap_name,
-            offset: fmap_area_raw.offset,
-            size: fmap_area_raw.size,
-            flags: FMapFlags::from_bits(fmap_area_raw.flags).unwrap_or(FMapFlags::empty()),
+            name: name.into(),
+            offset,
+            size,
+            flags: FMapFlags::empty(),
         }
     }
+
+    /// Returns the area with `flags` replacing its flags.
+    pub fn with_flags(self, flags: FMapFlags) -> FMapArea {
+        FMapArea { flags, ..self }
+    }
+
+    /// Offset of the first byte after the area. Computed in 64 bits, so it never wraps.
+    pub fn end(&self) -> u64 {
+        self.offset as u64 + self.size as u64
+    }
+
+    /// Address of the area in flash placed at `base`. Wraps around the 64-bit address space.
+    pub fn address(&self, base: u64) -> u64 {
+        base.wrapping_add(self.offset as u64)
+    }
+
+    /// Address of the first byte after the area in flash placed at `base`. Wraps like
+    /// `address()`.
+    pub fn end_address(&self, base: u64) -> u64 {
+        base.wrapping_add(self.end())
+    }
+
+    /// Address of the last byte of the area in flash placed at `base`, i.e. its inclusive
+    /// end. Wraps like `address()`. `None` for area of zero size, which has no bytes.
+    pub fn last_address(&self, base: u64) -> Option<u64> {
+        (self.size != 0).then(|| self.end_address(base).wrapping_sub(1))
+    }
+
+    pub fn range(&self) -> Range<u64> {
+        self.offset as u64..self.end()
+    }
+
+    /// Checks whether byte at `offset` belongs to the area.
+    pub fn contains(&self, offset: u32) -> bool {
+        self.range().contains(&(offset as u64))
+    }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct FMap {
     pub name: String,
     pub version_major: u8,
     pub version_minor: u8,
     pub base: u64,
     pub size: u32,
+    /// Areas in FlashMap order. `FMap::areas()` is preferred for reading, as it does not
+    /// depend on how areas are stored.
     pub areas: Vec<FMapArea>,
 }
 
-#[derive(Debug, Default)]
-#[repr(C, packed)]
-struct FMapRaw {
-    signature: [u8; SIGNATURE.len()],
-    version_major: u8,
-    version_minor: u8,
-    base: u64,
-    size: u32,
-    name: [u8; NAME_LEN],
-    nareas: u16,
+/// Read-only view of areas of FMap, returned by `FMap::areas()`.
+#[derive(Debug, Clone, Copy)]
+pub struct FMapAreas<'a>(&'a [FMapArea]);
+
+impl<'a> FMapAreas<'a> {
+    /// Areas in FlashMap order.
+    pub fn iter(&self) -> core::slice::Iter<'a, FMapArea> {
+        self.0.iter()
+    }
+
+    /// Areas sorted in the same order as by `FMap::sorted_areas()`.
+    pub fn by_offset(&self) -> Vec<&'a FMapArea> {
+        let mut areas = self.0.iter().collect::<Vec<_>>();
+        areas.sort_by(|a, b| area_order(a, b));
+        areas
+    }
+
+    /// First area named `name`.
+    pub fn find(&self, name: &str) -> Option<&'a FMapArea> {
+        self.0.iter().find(|ar| ar.name == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for FMapAreas<'a> {
+    type Item = &'a FMapArea;
+    type IntoIter = core::slice::Iter<'a, FMapArea>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
 }
 
+#[cfg(feature = "std")]
 #[derive(thiserror::Error, Debug)]
 pub enum FMapError {
     #[error("flash map not found")]
@@ -93,72 +193,262 @@ pub enum FMapError {
         #[from]
         source: std::io::Error,
     },
+    #[error("flash map area not found: {}", .0)]
+    AreaNotFound(String),
+    #[error("flash map area out of bounds: {}", .0)]
+    AreaOutOfBounds(String),
+    #[error("relocation by {} moves flash map out of address range", .0)]
+    InvalidRelocation(i64),
+    #[error("flash map area {} overlaps area {}", .0, .1)]
+    AreaOverlap(String, String),
+    #[error("flash map area {} has unknown flag bits: {:#06x}", .0, .1)]
+    UnknownFlags(String, u16),
+    #[error("{}", .0.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "))]
+    Multiple(Vec<FMapError>),
 }
 
-impl From<FMapRaw> for FMap {
-    fn from(fmap_raw: FMapRaw) -> FMap {
-        let fmap_name: String = if fmap_raw.name.contains(&0_u8) {
-            std::ffi::CStr::from_bytes_until_nul(&fmap_raw.name)
-                .unwrap()
-                .to_str()
-                .unwrap_or("")
-        } else {
-            std::str::from_utf8(&fmap_raw.name).unwrap_or("")
+/// Problem found in FMap layout.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutIssue {
+    /// Areas overlap partially, i.e. neither of them contains the other one.
+    Overlap {
+        a: FMapArea,
+        b: FMapArea,
+    },
+    /// Area stretches beyond the flash size.
+    OutOfBounds {
+        area: FMapArea,
+    },
+    ZeroSize {
+        area: FMapArea,
+    },
+    /// More than one area shares the same name.
+    DuplicateName {
+        name: String,
+    },
+    /// Space within flash or area with sub-areas, that is not covered by any of the sub-areas.
+    Gap {
+        start: u64,
+        size: u64,
+    },
+}
+
+/// Options altering FMap parsing.
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    /// Reject FMaps with minor version newer than given. Any minor version is accepted if not set.
+    pub max_version_minor: Option<u8>,
+    /// Reject areas with flag bits outside `FMapFlags::all()`. Unknown bits are dropped
+    /// otherwise, leaving no flags set.
+    pub strict_flags: bool,
+}
+
+/// Options altering FMap search.
+#[derive(Debug, Default, Clone)]
+pub struct SearchOptions {
+    /// Ignore FMaps placed before this offset.
+    pub start: usize,
+    /// Scan at most this many bytes from the beginning of data. Whole data is scanned if not set.
+    pub max_scan: Option<u64>,
+    /// Check only offsets aligned to this power of two. `SEARCH_STRIDE` is used if not set.
+    pub min_align: Option<usize>,
+}
+
+/// Statistics of FMap search.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Number of bytes read while looking for FMap signature.
+    pub bytes_scanned: u64,
+    /// Number of offsets checked for FMap signature.
+    pub candidates: usize,
+    /// Alignment of the search pass which found FMap. Zero if FMap was found
+    /// at the beginning of data.
+    pub alignment: usize,
+}
+
+#[cfg(feature = "std")]
+impl FMapError {
+    /// Stable name of the error variant for machine-readable reports.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FMapError::NotFound => "NotFound",
+            FMapError::CorruptedHeader => "CorruptedHeader",
+            FMapError::IncorrectVersion(_, _) => "IncorrectVersion",
+            FMapError::IOError { .. } => "IOError",
+            FMapError::AreaNotFound(_) => "AreaNotFound",
+            FMapError::AreaOutOfBounds(_) => "AreaOutOfBounds",
+            FMapError::InvalidRelocation(_) => "InvalidRelocation",
+            FMapError::AreaOverlap(_, _) => "AreaOverlap",
+            FMapError::UnknownFlags(_, _) => "UnknownFlags",
+            FMapError::Multiple(_) => "Multiple",
         }
-        .to_string();
+    }
 
-        FMap {
-            name: fmap_name,
-            version_major: fmap_raw.version_major,
-            version_minor: fmap_raw.version_minor,
-            base: fmap_raw.base,
-            size: fmap_raw.size,
-            areas: Vec::new(),
+    /// Combines `errors` into single error. Returns `Ok` if there are no errors.
+    pub fn collect(mut errors: Vec<FMapError>) -> Result<(), FMapError> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(FMapError::Multiple(errors)),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<decode::DecodeError> for FMapError {
+    fn from(error: decode::DecodeError) -> FMapError {
+        match error {
+            decode::DecodeError::TooShort => FMapError::from(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Not enough data to fit FMap",
+            )),
+            decode::DecodeError::IncorrectVersion(major, minor) => {
+                FMapError::IncorrectVersion(major, minor)
+            }
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl FMap {
     pub fn parse_fmap(reader: &mut (impl Read + Seek)) -> Result<FMap, FMapError> {
-        let mut buffer = [0_u8; mem::size_of::<FMapRaw>()];
-        if let Err(e) = reader.read_exact(&mut buffer) {
-            return Err(FMapError::from(e));
-        }
+        Self::parse_fmap_with_opts(reader, &ParseOptions::default())
+    }
 
-        let fmap_raw: FMapRaw = unsafe { mem::transmute(buffer) };
+    pub fn parse_fmap_with_opts(
+        reader: &mut (impl Read + Seek),
+        opts: &ParseOptions,
+    ) -> Result<FMap, FMapError> {
+        Self::parse_areas_with(reader, opts, |_| ())
+    }
 
-        if fmap_raw.version_major != VERSION_MAJOR as u8 {
-            return Err(FMapError::IncorrectVersion(
-                fmap_raw.version_major,
-                fmap_raw.version_minor,
-            ));
-        }
+    /// Parses FMap like `parse_fmap`, calling `cb` for each area as soon as it is read.
+    pub fn parse_fmap_with<F: FnMut(&FMapArea)>(
+        reader: &mut (impl Read + Seek),
+        cb: F,
+    ) -> Result<FMap, FMapError> {
+        Self::parse_areas_with(reader, &ParseOptions::default(), cb)
+    }
 
-        let fmap_nareas = fmap_raw.nareas;
-        let mut fmap = FMap::from(fmap_raw);
+    fn parse_areas_with<F: FnMut(&FMapArea)>(
+        reader: &mut (impl Read + Seek),
+        opts: &ParseOptions,
+        mut cb: F,
+    ) -> Result<FMap, FMapError> {
+        let mut buffer = [0_u8; HEADER_SIZE];
+        reader.read_exact(&mut buffer)?;
+        let (mut fmap, nareas) = decode::decode_header(&buffer, opts.max_version_minor)?;
 
         // Read areas
-        for _ in 0..fmap_nareas {
-            let mut buffer = [0_u8; mem::size_of::<FMapAreaRaw>()];
-            if let Err(e) = reader.read_exact(&mut buffer) {
-                return Err(FMapError::from(e));
+        for _ in 0..nareas {
+            let mut buffer = [0_u8; AREA_SIZE];
+            reader.read_exact(&mut buffer)?;
+            let area = decode::decode_area(&buffer)?;
+            if opts.strict_flags {
+                let bits = u16::from_le_bytes([buffer[AREA_SIZE - 2], buffer[AREA_SIZE - 1]]);
+                if FMapFlags::from_bits(bits).is_none() {
+                    return Err(FMapError::UnknownFlags(area.name, bits));
+                }
             }
-
-            let fmap_area_raw: FMapAreaRaw = unsafe { mem::transmute(buffer) };
-            fmap.areas.push(FMapArea::from(fmap_area_raw));
+            cb(&area);
+            fmap.areas.push(area);
         }
 
         Ok(fmap)
     }
 
+    /// Parses FMap placed at the beginning of in-memory `data`.
+    pub fn parse_bytes(data: &[u8]) -> Result<FMap, FMapError> {
+        Ok(decode::parse_from_slice(data, None)?)
+    }
+
+    /// Searches in-memory image `data` for FMap. Returns FMap and its offset, like `find_fmap`.
+    pub fn from_bytes(data: &[u8]) -> Result<(FMap, usize), FMapError> {
+        Self::find_fmap(&mut io::Cursor::new(data))
+    }
+
+    /// Serializes FMap in on-disk format of FMAP 1.x. Names longer than NAME_LEN bytes
+    /// and more than u16::MAX areas are rejected.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        let nareas = u16::try_from(self.areas.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Too many areas: {}", self.areas.len()),
+            )
+        })?;
+        writer.write_all(SIGNATURE)?;
+        writer.write_all(&[self.version_major, self.version_minor])?;
+        writer.write_all(&self.base.to_le_bytes())?;
+        writer.write_all(&self.size.to_le_bytes())?;
+        writer.write_all(&name_bytes(&self.name)?)?;
+        writer.write_all(&nareas.to_le_bytes())?;
+        for area in self.areas.iter() {
+            writer.write_all(&area.offset.to_le_bytes())?;
+            writer.write_all(&area.size.to_le_bytes())?;
+            writer.write_all(&name_bytes(&area.name)?)?;
+            writer.write_all(&area.flags.bits().to_le_bytes())?;
+        }
+        Ok(())
+    }
+
     fn is_fmap(reader: &mut impl Read) -> Result<bool, std::io::Error> {
         let mut signature_buffer = [0; SIGNATURE.len()];
         reader.read_exact(&mut signature_buffer)?;
         Ok(signature_buffer == *SIGNATURE)
     }
 
+    /// Parses FMap placed at `offset` without searching for it. Fails with `NotFound` if there
+    /// is no FMap signature at that offset.
+    pub fn parse_fmap_at(
+        reader: &mut (impl Read + Seek),
+        offset: usize,
+    ) -> Result<FMap, FMapError> {
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        match Self::is_fmap(reader) {
+            Ok(true) => (),
+            Ok(false) => return Err(FMapError::NotFound),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(FMapError::NotFound),
+            Err(e) => return Err(FMapError::from(e)),
+        }
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        Self::parse_fmap(reader)
+    }
+
     /// Returns FMap and offset of that fmap on success.
     pub fn find_fmap(reader: &mut (impl Read + Seek)) -> Result<(FMap, usize), FMapError> {
+        Self::find_fmap_from(reader, 0)
+    }
+
+    /// Same as `find_fmap`, but ignores FMaps placed before `start` offset, e.g. to skip
+    /// a region containing bogus FMap signature.
+    pub fn find_fmap_from(
+        reader: &mut (impl Read + Seek),
+        start: usize,
+    ) -> Result<(FMap, usize), FMapError> {
+        Self::find_fmap_with_opts(
+            reader,
+            &SearchOptions {
+                start,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Same as `find_fmap`, but search is altered by `opts`.
+    pub fn find_fmap_with_opts(
+        reader: &mut (impl Read + Seek),
+        opts: &SearchOptions,
+    ) -> Result<(FMap, usize), FMapError> {
+        Self::find_fmap_with_stats(reader, opts).map(|(fmap, offset, _)| (fmap, offset))
+    }
+
+    /// Same as `find_fmap_with_opts`, but also returns statistics of the search.
+    pub fn find_fmap_with_stats(
+        reader: &mut (impl Read + Seek),
+        opts: &SearchOptions,
+    ) -> Result<(FMap, usize, SearchStats), FMapError> {
+        let mut stats = SearchStats::default();
+        let start = opts.start;
         let data_size = reader.seek(SeekFrom::End(0))?;
 
         if HEADER_SIZE as u64 >= data_size {
@@ -167,34 +457,73 @@ impl FMap {
                 "Not enough data to fit FMap",
             )));
         }
+        let stride = opts.min_align.unwrap_or(SEARCH_STRIDE);
+        if !stride.is_power_of_two() || stride as u64 > data_size {
+            return Err(FMapError::from(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Alignment {stride:#x} is not a power of two within data size"),
+            )));
+        }
+        // FMap header has to fit in scanned part of data.
+        let scan_size = opts.max_scan.map_or(data_size, |max| max.min(data_size));
+        if (HEADER_SIZE as u64) > scan_size {
+            return Err(FMapError::NotFound);
+        }
+
+        // Signature may be followed by garbage, e.g. in strings or code referring to FMap.
+        // Such candidates are skipped and the first error is reported only if no valid FMap
+        // is found.
+        let mut parse_error = None;
 
         // Quick check at the beginning for directly passed FMap.
-        reader.seek(SeekFrom::Start(0))?;
-        match Self::is_fmap(reader) {
-            Ok(true) => {
-                reader.seek(SeekFrom::Start(0))?;
-                let fmap = Self::parse_fmap(reader)?;
-                return Ok((fmap, 0));
+        if start == 0 {
+            reader.seek(SeekFrom::Start(0))?;
+            stats.candidates += 1;
+            stats.bytes_scanned += SIGNATURE.len() as u64;
+            match Self::is_fmap(reader) {
+                Ok(true) => {
+                    reader.seek(SeekFrom::Start(0))?;
+                    match Self::parse_fmap(reader) {
+                        Ok(fmap) => return Ok((fmap, 0, stats)),
+                        Err(e) => parse_error = parse_error.or(Some(e)),
+                    }
+                }
+                Err(e) => return Err(FMapError::from(e)),
+                _ => (),
             }
-            Err(e) => return Err(FMapError::from(e)),
-            _ => (),
         }
 
-        let limit = data_size as usize - HEADER_SIZE;
+        let limit = scan_size as usize - HEADER_SIZE;
 
         // Search from largest alignments to find FMap instead of strings.
-        let align_log = ((limit - 1) as f64).log2();
-        let mut align = 2usize.pow(align_log as u32);
+        // Alignment starts at power of two not smaller than `stride` and is halved down
+        // to `stride`, so the last pass visits every `stride`-aligned offset up to
+        // `limit`, regardless of its value.
+        let mut align = stride;
+        while align * 2 <= limit {
+            align *= 2;
+        }
 
-        while align >= SEARCH_STRIDE {
-            let mut offset = align;
+        while align >= stride {
+            // First multiple of alignment not before `start`. Offset 0 was checked already.
+            let mut offset = match start.div_ceil(align) {
+                0 => align,
+                n => n * align,
+            };
             while offset <= limit {
                 reader.seek(SeekFrom::Start(offset as u64))?;
+                stats.candidates += 1;
+                stats.bytes_scanned += SIGNATURE.len() as u64;
                 match Self::is_fmap(reader) {
                     Ok(true) => {
                         reader.seek(SeekFrom::Start(offset as u64))?;
-                        let fmap = Self::parse_fmap(reader)?;
-                        return Ok((fmap, offset));
+                        match Self::parse_fmap(reader) {
+                            Ok(fmap) => {
+                                stats.alignment = align;
+                                return Ok((fmap, offset, stats));
+                            }
+                            Err(e) => parse_error = parse_error.or(Some(e)),
+                        }
                     }
                     Err(e) => return Err(FMapError::from(e)),
                     _ => (),
@@ -205,11 +534,294 @@ impl FMap {
             align /= 2;
         }
 
-        Err(FMapError::NotFound)
+        Err(parse_error.unwrap_or(FMapError::NotFound))
+    }
+}
+
+impl FMap {
+    /// Shifts `base` and offsets of all areas by `delta`. FMap is left unchanged and error is
+    /// returned if any of the values would leave its range.
+    #[cfg(feature = "std")]
+    pub fn relocate(&mut self, delta: i64) -> Result<(), FMapError> {
+        let base = self
+            .base
+            .checked_add_signed(delta)
+            .ok_or(FMapError::InvalidRelocation(delta))?;
+        let offsets = self
+            .areas
+            .iter()
+            .map(|ar| {
+                i64::from(ar.offset)
+                    .checked_add(delta)
+                    .and_then(|offset| u32::try_from(offset).ok())
+                    .ok_or(FMapError::InvalidRelocation(delta))
+            })
+            .collect::<Result<Vec<u32>, FMapError>>()?;
+
+        self.base = base;
+        for (area, offset) in self.areas.iter_mut().zip(offsets) {
+            area.offset = offset;
+        }
+        Ok(())
+    }
+
+    /// View of areas independent of their storage.
+    pub fn areas(&self) -> FMapAreas<'_> {
+        FMapAreas(&self.areas)
+    }
+
+    /// Number of bytes covered by areas. Nested areas are counted once, as bytes of the
+    /// innermost ones. Areas with layout issues other than duplicated name are not counted,
+    /// so covered and gap bytes sum up to flash size, unless there are no areas to count.
+    pub fn covered_bytes(&self) -> u64 {
+        self.nesting().1
+    }
+
+    /// Number of bytes of flash not covered by any area.
+    pub fn gap_bytes(&self) -> u64 {
+        self.nesting().0.iter().map(|(_, size)| size).sum()
+    }
+
+    pub fn area_names(&self) -> Vec<&str> {
+        self.areas.iter().map(|ar| ar.name.as_str()).collect()
+    }
+
+    /// Areas whose flags include all of the given `flags`.
+    pub fn areas_with_flag(&self, flags: FMapFlags) -> Vec<&FMapArea> {
+        self.areas
+            .iter()
+            .filter(|ar| ar.flags.contains(flags))
+            .collect()
+    }
+
+    /// Areas sorted ascending by offset, then descending by size and by name, so that
+    /// containing areas come before the contained ones, as in human-readable dump.
+    pub fn sorted_areas(&self) -> Vec<&FMapArea> {
+        self.areas().by_offset()
+    }
+
+    /// Inserts `area` before the first area following it in `sorted_areas` order, so sorted
+    /// areas stay sorted. Area stretching beyond flash or partially overlapping another one
+    /// is rejected, leaving FMap unchanged.
+    #[cfg(feature = "std")]
+    pub fn insert_area(&mut self, area: FMapArea) -> Result<(), FMapError> {
+        let index = self
+            .areas
+            .iter()
+            .position(|a| area_order(a, &area) == Ordering::Greater)
+            .unwrap_or(self.areas.len());
+        self.areas.insert(index, area);
+
+        let area = &self.areas[index];
+        let error = self.validate().into_iter().find_map(|issue| match issue {
+            LayoutIssue::OutOfBounds { area: a } if a == *area => {
+                Some(FMapError::AreaOutOfBounds(a.name))
+            }
+            LayoutIssue::Overlap { a, b } if a == *area => {
+                Some(FMapError::AreaOverlap(a.name, b.name))
+            }
+            LayoutIssue::Overlap { a, b } if b == *area => {
+                Some(FMapError::AreaOverlap(b.name, a.name))
+            }
+            _ => None,
+        });
+        match error {
+            Some(e) => {
+                self.areas.remove(index);
+                Err(e)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Removes the first area named `name` and returns it.
+    pub fn remove_area(&mut self, name: &str) -> Option<FMapArea> {
+        let index = self.areas.iter().position(|a| a.name == name)?;
+        Some(self.areas.remove(index))
+    }
+
+    /// Names shared by more than one area, in order of first occurrence.
+    pub fn duplicate_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for (i, area) in self.areas.iter().enumerate() {
+            if !names.contains(&area.name.as_str())
+                && self.areas.iter().skip(i + 1).any(|a| a.name == area.name)
+            {
+                names.push(&area.name);
+            }
+        }
+        names
+    }
+
+    /// Area describing FMap found at `fmap_offset`, i.e. area starting at that offset.
+    /// If there are several such areas, the one named `FMAP` or else the smallest one is returned.
+    pub fn self_area(&self, fmap_offset: usize) -> Option<&FMapArea> {
+        self.areas
+            .iter()
+            .filter(|ar| ar.offset as usize == fmap_offset)
+            .min_by_key(|ar| (ar.name != "FMAP", ar.size))
     }
 
     pub fn get(&self, area_name: &str) -> Option<&FMapArea> {
-        self.areas.iter().find(|&ar| ar.name == *area_name)
+        self.areas().find(area_name)
+    }
+
+    /// Returns the first area starting at `offset`. Several areas may start at the same
+    /// offset if one of them contains the others.
+    pub fn get_by_offset(&self, offset: u32) -> Option<&FMapArea> {
+        self.areas().iter().find(|ar| ar.offset == offset)
+    }
+
+    /// Returns problems with placement of single area.
+    pub fn area_issues(&self, area: &FMapArea) -> Vec<LayoutIssue> {
+        let mut issues = Vec::new();
+        if area.size == 0 {
+            issues.push(LayoutIssue::ZeroSize { area: area.clone() });
+        }
+        if area.end() > self.size as u64 {
+            issues.push(LayoutIssue::OutOfBounds { area: area.clone() });
+        }
+        issues
+    }
+
+    /// Audits layout of areas. Areas with issues other than duplicated name are not taken into
+    /// account when looking for gaps.
+    pub fn validate(&self) -> Vec<LayoutIssue> {
+        let mut issues = Vec::new();
+
+        for area in self.areas.iter() {
+            issues.append(&mut self.area_issues(area));
+        }
+
+        for (i, a) in self.areas.iter().enumerate() {
+            for b in self.areas.iter().skip(i + 1) {
+                if overlaps(a.range(), b.range()) {
+                    issues.push(LayoutIssue::Overlap {
+                        a: a.clone(),
+                        b: b.clone(),
+                    });
+                }
+            }
+        }
+
+        issues.extend(
+            self.duplicate_names()
+                .into_iter()
+                .map(|name| LayoutIssue::DuplicateName {
+                    name: name.to_string(),
+                }),
+        );
+
+        let (mut gaps, _) = self.nesting();
+        gaps.sort_unstable();
+        issues.extend(
+            gaps.into_iter()
+                .map(|(start, size)| LayoutIssue::Gap { start, size }),
+        );
+
+        issues
+    }
+
+    /// Nests areas without layout issues other than duplicated name in the flash. Returns
+    /// gaps as (offset, size) pairs within flash or areas with sub-areas, and number of bytes
+    /// covered by the innermost areas.
+    fn nesting(&self) -> (Vec<(u64, u64)>, u64) {
+        // Valid areas sorted ascending by offset and descending by size.
+        // Of overlapping areas only the first one is kept.
+        let sorted = self
+            .sorted_areas()
+            .into_iter()
+            .filter(|a| self.area_issues(a).is_empty());
+        let mut kept: Vec<&FMapArea> = Vec::new();
+        for area in sorted.into_iter() {
+            if !kept.iter().any(|k| overlaps(k.range(), area.range())) {
+                kept.push(area);
+            }
+        }
+        let mut valid = kept
+            .iter()
+            .map(|a| (a.offset as u64, a.end()))
+            .collect::<Vec<_>>();
+        valid.dedup();
+
+        // Stack of (start, end, end of last child, has children) of areas containing current
+        // one. Root is the flash itself, which is not counted as covered.
+        let mut gaps = Vec::new();
+        let mut covered = 0;
+        let mut finish = |gaps: &mut Vec<(u64, u64)>,
+                          (start, end, cursor, has_children): (u64, u64, u64, bool),
+                          is_root: bool| {
+            if has_children && cursor < end {
+                gaps.push((cursor, end - cursor));
+            } else if !has_children && !is_root {
+                covered += end - start;
+            }
+        };
+        let mut stack = vec![(0, self.size as u64, 0, false)];
+        for (start, end) in valid.into_iter() {
+            while stack.len() > 1 && stack.last().unwrap().1 <= start {
+                finish(&mut gaps, stack.pop().unwrap(), false);
+            }
+            let parent = stack.last_mut().unwrap();
+            if parent.2 < start {
+                gaps.push((parent.2, start - parent.2));
+            }
+            parent.2 = end;
+            parent.3 = true;
+            stack.push((start, end, start, false));
+        }
+        while let Some(node) = stack.pop() {
+            finish(&mut gaps, node, stack.is_empty());
+        }
+        (gaps, covered)
+    }
+}
+
+/// Checks whether ranges overlap partially, i.e. neither of them contains the other one.
+pub(crate) fn overlaps(a: Range<u64>, b: Range<u64>) -> bool {
+    (a.start < b.start && b.start < a.end && a.end < b.end)
+        || (b.start < a.start && a.start < b.end && b.end < a.end)
+}
+
+/// Orders areas ascending by offset, then descending by size and ascending by name.
+fn area_order(a: &FMapArea, b: &FMapArea) -> Ordering {
+    (a.offset, b.size, &a.name).cmp(&(b.offset, a.size, &b.name))
+}
+
+/// Encodes `name` as NUL-padded field of NAME_LEN bytes.
+#[cfg(feature = "std")]
+fn name_bytes(name: &str) -> io::Result<[u8; NAME_LEN]> {
+    let mut bytes = [0u8; NAME_LEN];
+    if name.len() > NAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Name '{name}' is longer than {NAME_LEN} bytes"),
+        ));
+    }
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    Ok(bytes)
+}
+
+/// Formats header and areas the same way as original futility `dump_fmap` does.
+impl fmt::Display for FMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "fmap_signature:  __FMAP__")?; // Original futility has no colon here
+        writeln!(
+            f,
+            "fmap_version:    {}.{}",
+            self.version_major, self.version_minor
+        )?;
+        writeln!(f, "fmap_base:       {:#x}", self.base)?;
+        writeln!(f, "fmap_size:       {0:#x} ({0})", self.size)?;
+        writeln!(f, "fmap_name:       {}", self.name)?;
+        writeln!(f, "fmap_nareas:     {}", self.areas.len())?;
+        for (i, area) in self.areas.iter().enumerate() {
+            writeln!(f, "area:            {}", i + 1)?;
+            writeln!(f, "area_offset:     {:#x}", area.offset)?;
+            writeln!(f, "area_size:       {0:#x} ({0})", area.size)?;
+            writeln!(f, "area_name:       {}", area.name)?;
+        }
+        Ok(())
     }
 }
 
@@ -222,6 +834,104 @@ mod tests {
 
     const EXAMPLE_FMAP_BIN_DATA_OFFSET: usize = 0x200;
 
+    #[test]
+    fn test_areas_view() {
+        let fmap = fmap_with_areas(vec![
+            area("RW", 0x200, 0x200),
+            area("RO", 0, 0x200),
+            area("RO_VPD", 0, 0x100),
+        ]);
+        let areas = fmap.areas();
+        assert_eq!(areas.len(), 3);
+        assert!(!areas.is_empty());
+        assert_eq!(
+            areas.iter().map(|ar| ar.name.as_str()).collect::<Vec<_>>(),
+            ["RW", "RO", "RO_VPD"]
+        );
+        assert_eq!(
+            areas
+                .by_offset()
+                .into_iter()
+                .map(|ar| ar.name.as_str())
+                .collect::<Vec<_>>(),
+            ["RO", "RO_VPD", "RW"]
+        );
+        assert_eq!(areas.find("RO_VPD").map(|ar| ar.size), Some(0x100));
+        assert!(areas.find("RW_VPD").is_none());
+        assert_eq!(areas.into_iter().count(), 3);
+    }
+
+    #[test]
+    fn test_area_builder() {
+        let area = FMapArea::new("RO_VPD", 0x100, 0x80).with_flags(FMapFlags::Preserve);
+        assert_eq!(
+            area,
+            FMapArea {
+                name: "RO_VPD".to_string(),
+                offset: 0x100,
+                size: 0x80,
+                flags: FMapFlags::Preserve,
+            }
+        );
+        assert_eq!(area.with_flags(FMapFlags::RO).flags, FMapFlags::RO);
+    }
+
+    #[test]
+    fn test_area_range_helpers() {
+        let area = FMapArea::new("A", 0x100, 0x80);
+        assert_eq!(area.end(), 0x180);
+        assert_eq!(area.range(), 0x100..0x180);
+        assert!(!area.contains(0xff));
+        assert!(area.contains(0x100));
+        assert!(area.contains(0x17f));
+        assert!(!area.contains(0x180));
+
+        // Area ending past u32::MAX does not wrap around.
+        let area = FMapArea::new("TOP", u32::MAX - 0xf, 0x20);
+        assert_eq!(area.end(), u32::MAX as u64 + 0x11);
+        assert!(area.contains(u32::MAX));
+        assert!(!area.contains(0));
+        assert!(!area.contains(0xf));
+        assert_eq!(area.address(0x1_0000_0000), 0x1_ffff_fff0);
+
+        // Addresses near the top of the 64-bit address space wrap around.
+        let base = 0xffff_ffff_ffff_f800;
+        let area = FMapArea::new("HIGH", 0x700, 0x100);
+        assert_eq!(area.address(base), 0xffff_ffff_ffff_ff00);
+        assert_eq!(area.end_address(base), 0);
+        assert_eq!(area.last_address(base), Some(u64::MAX));
+        assert_eq!(FMapArea::new("EMPTY", 0, 0).last_address(0), None);
+    }
+
+    #[test]
+    fn test_flags_symbolic() {
+        assert_eq!(FMapFlags::empty().to_symbolic(), "");
+        assert!(FMapFlags::empty().names().is_empty());
+        assert_eq!(
+            (FMapFlags::Static | FMapFlags::RO).names(),
+            vec!["STATIC", "RO"]
+        );
+        assert_eq!(FMapFlags::from_symbolic(""), Ok(FMapFlags::empty()));
+        assert_eq!(
+            FMapFlags::all().to_symbolic(),
+            "STATIC|COMPRESSED|RO|PRESERVE"
+        );
+        assert_eq!(
+            FMapFlags::from_symbolic("static|Compressed,ro | PRESERVE"),
+            Ok(FMapFlags::all())
+        );
+        for flags in [
+            FMapFlags::Static,
+            FMapFlags::Compressed,
+            FMapFlags::RO,
+            FMapFlags::Preserve,
+            FMapFlags::Static | FMapFlags::Preserve,
+        ] {
+            assert_eq!(FMapFlags::from_symbolic(&flags.to_symbolic()), Ok(flags));
+        }
+        assert!(FMapFlags::from_symbolic("STATIC|WRITABLE").is_err());
+    }
+
     #[test]
     fn test_is_fmap() -> Result<(), String> {
         let mut reader_ok = Cursor::new(&SIGNATURE[..]);
@@ -362,6 +1072,253 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_from_bytes() -> Result<(), String> {
+        let mut d = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/example_fmap.bin");
+        let data = match std::fs::read(&d) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("Failed to read {d}. Error: {e}")),
+        };
+
+        let (fmap, offset) = match FMap::from_bytes(&data) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("FMap::from_bytes() failed with error: {e}")),
+        };
+        assert_eq!(offset, EXAMPLE_FMAP_BIN_DATA_OFFSET);
+        assert_eq!(
+            fmap.area_names(),
+            vec!["bootblock", "normal", "fallback", "data"]
+        );
+
+        match FMap::parse_bytes(&data[offset..]) {
+            Ok(parsed) => assert_eq!(parsed, fmap),
+            Err(e) => return Err(format!("FMap::parse_bytes() failed with error: {e}")),
+        }
+        if FMap::parse_bytes(&data[offset + 1..]).is_ok() {
+            return Err("FMap::parse_bytes() expected to fail on misplaced header".into());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fmap_with() -> Result<(), String> {
+        let mut d = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/example_fmap.bin");
+        let data = match std::fs::read(&d) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("Failed to read {d}. Error: {e}")),
+        };
+
+        let mut names = Vec::new();
+        let fmap = match FMap::parse_fmap_with(
+            &mut Cursor::new(&data[EXAMPLE_FMAP_BIN_DATA_OFFSET..]),
+            |area| names.push(area.name.clone()),
+        ) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("FMap::parse_fmap_with() failed with error: {e}")),
+        };
+        assert_eq!(names.len(), fmap.areas.len());
+        assert_eq!(names, fmap.area_names());
+        Ok(())
+    }
+
+    fn header_bytes(size: u32) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(SIGNATURE);
+        header.extend_from_slice(&[VERSION_MAJOR as u8, 0]);
+        header.extend_from_slice(&0u64.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&[0u8; NAME_LEN]);
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn test_find_fmap_at_any_stride_aligned_offset() -> Result<(), String> {
+        for (data_size, offset) in [
+            (0x2000, 0x1234),
+            (0x1234 + HEADER_SIZE, 0x1234),
+            (0x1fff, 0x1fc4),
+            // Limit smaller than double stride.
+            (HEADER_SIZE + SEARCH_STRIDE, SEARCH_STRIDE),
+        ] {
+            let mut data = vec![0xffu8; data_size];
+            data[offset..offset + HEADER_SIZE].copy_from_slice(&header_bytes(data_size as u32));
+            match FMap::find_fmap(&mut Cursor::new(&data)) {
+                Ok((_, found)) => assert_eq!(found, offset),
+                Err(e) => {
+                    return Err(format!(
+                        "FMap at {offset:#x} in {data_size:#x} bytes not found. Error: {e}"
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_fmap_from() -> Result<(), String> {
+        let mut data = vec![0xffu8; 0x2000];
+        // Bogus FMap is placed at more aligned offset, so it is found first by default.
+        data[0x1000..0x1000 + HEADER_SIZE].copy_from_slice(&header_bytes(0x100));
+        data[0x1234..0x1234 + HEADER_SIZE].copy_from_slice(&header_bytes(0x2000));
+
+        for (start, expected) in [(0, 0x1000), (0x1001, 0x1234), (0x1234, 0x1234)] {
+            match FMap::find_fmap_from(&mut Cursor::new(&data), start) {
+                Ok((fmap, offset)) => {
+                    assert_eq!(offset, expected);
+                    assert_eq!(fmap.size, if offset == 0x1000 { 0x100 } else { 0x2000 });
+                }
+                Err(e) => return Err(format!("FMap not found from {start:#x}. Error: {e}")),
+            }
+        }
+        match FMap::find_fmap_from(&mut Cursor::new(&data), 0x1235) {
+            Err(FMapError::NotFound) => Ok(()),
+            Ok((_, offset)) => Err(format!("Unexpected FMap found at {offset:#x}")),
+            Err(e) => Err(format!("Unexpected error: {e}")),
+        }
+    }
+
+    #[test]
+    fn test_find_fmap_max_scan() -> Result<(), String> {
+        let mut data = vec![0xffu8; 0x2000];
+        data[0x1234..0x1234 + HEADER_SIZE].copy_from_slice(&header_bytes(0x2000));
+
+        for (max_scan, found) in [
+            (None, true),
+            (Some(0x10000), true),
+            (Some(0x1234 + HEADER_SIZE as u64), true),
+            (Some(0x1234 + HEADER_SIZE as u64 - 1), false),
+            (Some(0), false),
+        ] {
+            let opts = SearchOptions {
+                max_scan,
+                ..Default::default()
+            };
+            match FMap::find_fmap_with_opts(&mut Cursor::new(&data), &opts) {
+                Ok((_, offset)) if found => assert_eq!(offset, 0x1234),
+                Err(FMapError::NotFound) if !found => (),
+                Ok((_, offset)) => return Err(format!("Unexpected FMap found at {offset:#x}")),
+                Err(e) => return Err(format!("Unexpected error for {max_scan:?}: {e}")),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_fmap_min_align() -> Result<(), String> {
+        let mut data = vec![0xffu8; 0x2000];
+        data[0x1234..0x1234 + HEADER_SIZE].copy_from_slice(&header_bytes(0x2000));
+
+        for (min_align, found) in [(None, true), (Some(4), true), (Some(8), false)] {
+            let opts = SearchOptions {
+                min_align,
+                ..Default::default()
+            };
+            match FMap::find_fmap_with_opts(&mut Cursor::new(&data), &opts) {
+                Ok((_, offset)) if found => assert_eq!(offset, 0x1234),
+                Err(FMapError::NotFound) if !found => (),
+                Ok((_, offset)) => return Err(format!("Unexpected FMap found at {offset:#x}")),
+                Err(e) => return Err(format!("Unexpected error for {min_align:?}: {e}")),
+            }
+        }
+        for min_align in [3, 0x4000] {
+            let opts = SearchOptions {
+                min_align: Some(min_align),
+                ..Default::default()
+            };
+            if let Ok((_, offset)) = FMap::find_fmap_with_opts(&mut Cursor::new(&data), &opts) {
+                return Err(format!(
+                    "Unexpected FMap found at {offset:#x} for {min_align:#x}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_fmap_with_stats() -> Result<(), String> {
+        let mut data = vec![0xffu8; 0x2000];
+        data[0x400..0x400 + HEADER_SIZE].copy_from_slice(&header_bytes(0x2000));
+
+        // Offset 0, then 0x1000, 0x800, 0x1000, 0x1800 and finally 0x400 are checked.
+        match FMap::find_fmap_with_stats(&mut Cursor::new(&data), &SearchOptions::default()) {
+            Ok((_, offset, stats)) => {
+                assert_eq!(offset, 0x400);
+                assert_eq!(
+                    stats,
+                    SearchStats {
+                        bytes_scanned: 6 * SIGNATURE.len() as u64,
+                        candidates: 6,
+                        alignment: 0x400,
+                    }
+                );
+            }
+            Err(e) => return Err(format!("FMap not found. Error: {e}")),
+        }
+
+        data[0..HEADER_SIZE].copy_from_slice(&header_bytes(0x2000));
+        match FMap::find_fmap_with_stats(&mut Cursor::new(&data), &SearchOptions::default()) {
+            Ok((_, 0, stats)) => assert_eq!(stats.alignment, 0),
+            Ok((_, offset, _)) => return Err(format!("Unexpected FMap found at {offset:#x}")),
+            Err(e) => return Err(format!("FMap not found. Error: {e}")),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fmap_at() -> Result<(), String> {
+        let mut data = vec![0u8; 0x2000];
+        // Decoy would be found first by search.
+        data[0x1000..0x1000 + SIGNATURE.len()].copy_from_slice(SIGNATURE);
+        data[0x1234..0x1234 + HEADER_SIZE].copy_from_slice(&header_bytes(0x2000));
+
+        match FMap::parse_fmap_at(&mut Cursor::new(&data), 0x1234) {
+            Ok(fmap) => assert_eq!(fmap.size, 0x2000),
+            Err(e) => return Err(format!("FMap not parsed. Error: {e}")),
+        }
+        for offset in [0x1230, 0x1ffc, 0x3000] {
+            match FMap::parse_fmap_at(&mut Cursor::new(&data), offset) {
+                Err(FMapError::NotFound) => (),
+                Ok(_) => return Err(format!("Unexpected FMap parsed at {offset:#x}")),
+                Err(e) => return Err(format!("Unexpected error at {offset:#x}: {e}")),
+            }
+        }
+        match FMap::parse_fmap_at(&mut Cursor::new(&data), 0x1000) {
+            Err(FMapError::IncorrectVersion(0, 0)) => Ok(()),
+            Ok(_) => Err("Decoy FMap unexpectedly parsed".to_string()),
+            Err(e) => Err(format!("Unexpected error: {e}")),
+        }
+    }
+
+    #[test]
+    fn test_find_fmap_skips_decoy_signature() -> Result<(), String> {
+        let mut data = vec![0u8; 0x2000];
+        // Signature followed by zeros is found first, but has incorrect version.
+        data[0x1000..0x1000 + SIGNATURE.len()].copy_from_slice(SIGNATURE);
+        // Signature with impossible number of areas.
+        data[0x800..0x800 + HEADER_SIZE].copy_from_slice(&header_bytes(0x2000));
+        data[0x800 + HEADER_SIZE - 2..0x800 + HEADER_SIZE].copy_from_slice(&u16::MAX.to_le_bytes());
+        data[0x1234..0x1234 + HEADER_SIZE].copy_from_slice(&header_bytes(0x2000));
+
+        match FMap::find_fmap(&mut Cursor::new(&data)) {
+            Ok((fmap, offset)) => {
+                assert_eq!(offset, 0x1234);
+                assert_eq!(fmap.size, 0x2000);
+            }
+            Err(e) => return Err(format!("FMap not found. Error: {e}")),
+        }
+
+        // Error of the first decoy is reported if there is no valid FMap.
+        data[0x1234..0x1234 + SIGNATURE.len()].fill(0);
+        match FMap::find_fmap(&mut Cursor::new(&data)) {
+            Err(FMapError::IncorrectVersion(0, 0)) => Ok(()),
+            Ok((_, offset)) => Err(format!("Unexpected FMap found at {offset:#x}")),
+            Err(e) => Err(format!("Unexpected error: {e}")),
+        }
+    }
+
     #[test]
     fn test_find_fmap_incorrect_version() -> Result<(), String> {
         let mut d = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -387,6 +1344,84 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_fmap_max_version_minor() -> Result<(), String> {
+        let mut d = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/example_fmap.bin");
+
+        let mut fmap_data = match std::fs::read(&d) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(format!(
+                    "Failed to open test resource file `{}'. Error: {e}",
+                    d
+                ))
+            }
+        };
+        fmap_data[EXAMPLE_FMAP_BIN_DATA_OFFSET + SIGNATURE.len() + 1] = 1;
+        let header = &fmap_data[EXAMPLE_FMAP_BIN_DATA_OFFSET..];
+
+        // Any minor version accepted by default.
+        if let Err(e) = FMap::parse_fmap(&mut Cursor::new(header)) {
+            return Err(format!("Unexpected error: {e}"));
+        }
+
+        let opts = ParseOptions {
+            max_version_minor: Some(1),
+            ..Default::default()
+        };
+        if let Err(e) = FMap::parse_fmap_with_opts(&mut Cursor::new(header), &opts) {
+            return Err(format!("Unexpected error: {e}"));
+        }
+
+        let opts = ParseOptions {
+            max_version_minor: Some(0),
+            ..Default::default()
+        };
+        match FMap::parse_fmap_with_opts(&mut Cursor::new(header), &opts) {
+            Ok(_) => Err("FMap::parse_fmap_with_opts expected to fail but succeded".into()),
+            Err(FMapError::IncorrectVersion(1, 1)) => Ok(()),
+            Err(e) => Err(format!("Unexpected error: {e}")),
+        }
+    }
+
+    #[test]
+    fn test_parse_fmap_strict_flags() -> Result<(), String> {
+        let fmap = FMap {
+            name: "example".to_string(),
+            version_major: 1,
+            version_minor: 1,
+            base: 0,
+            size: 0x100,
+            areas: vec![
+                FMapArea::new("RO", 0, 0x80).with_flags(FMapFlags::RO),
+                FMapArea::new("RW", 0x80, 0x80),
+            ],
+        };
+        let mut data = Vec::new();
+        if let Err(e) = fmap.write(&mut data) {
+            return Err(format!("FMap::write() failed with error: {e}"));
+        }
+        // Set bit 4 in flags of the second area, next to the known PRESERVE bit.
+        data[HEADER_SIZE + 2 * AREA_SIZE - 2] = 0x10;
+
+        // Unknown bits are dropped by default.
+        match FMap::parse_fmap(&mut Cursor::new(&data)) {
+            Ok(parsed) => assert_eq!(parsed.areas[1].flags, FMapFlags::empty()),
+            Err(e) => return Err(format!("Unexpected error: {e}")),
+        }
+
+        let opts = ParseOptions {
+            strict_flags: true,
+            ..Default::default()
+        };
+        match FMap::parse_fmap_with_opts(&mut Cursor::new(&data), &opts) {
+            Ok(_) => Err("FMap::parse_fmap_with_opts expected to fail but succeded".into()),
+            Err(FMapError::UnknownFlags(name, 0x10)) if name == "RW" => Ok(()),
+            Err(e) => Err(format!("Unexpected error: {e}")),
+        }
+    }
+
     #[test]
     fn test_fmap_get() -> Result<(), String> {
         let fmap = FMap {
@@ -414,4 +1449,351 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_fmap_get_by_offset() {
+        let fmap = fmap_with_areas(vec![
+            area("RW", 0, 0x200),
+            area("FW_MAIN", 0, 0x100),
+            area("FW_MAIN", 0x200, 0x100),
+        ]);
+        assert_eq!(fmap.get_by_offset(0), Some(&fmap.areas[0]));
+        assert_eq!(fmap.get_by_offset(0x200), Some(&fmap.areas[2]));
+        assert_eq!(fmap.get_by_offset(0x100), None);
+    }
+
+    fn area(name: &str, offset: u32, size: u32) -> FMapArea {
+        FMapArea::new(name, offset, size)
+    }
+
+    fn fmap_with_areas(areas: Vec<FMapArea>) -> FMap {
+        FMap {
+            name: "example".to_string(),
+            version_major: 1,
+            version_minor: 0,
+            base: 0,
+            size: 0x400,
+            areas,
+        }
+    }
+
+    #[test]
+    fn test_relocate() {
+        let mut fmap = fmap_with_areas(vec![area("A", 0, 0x200), area("B", 0x200, 0x200)]);
+        fmap.base = 0x1000;
+        assert!(fmap.relocate(0x100).is_ok());
+        assert_eq!(fmap.base, 0x1100);
+        assert_eq!(fmap.areas[0].offset, 0x100);
+        assert_eq!(fmap.areas[1].offset, 0x300);
+        assert!(fmap.relocate(-0x100).is_ok());
+        assert_eq!(fmap.base, 0x1000);
+        assert_eq!(fmap.areas[0].offset, 0);
+
+        // Failed relocation does not modify FMap.
+        for delta in [-1, i64::from(u32::MAX) - 0x1ff, i64::MIN, i64::MAX] {
+            assert!(matches!(
+                fmap.relocate(delta),
+                Err(FMapError::InvalidRelocation(d)) if d == delta
+            ));
+            assert_eq!(fmap.base, 0x1000);
+            assert_eq!(fmap.areas[0].offset, 0);
+            assert_eq!(fmap.areas[1].offset, 0x200);
+        }
+
+        // Base wraps below zero.
+        let mut fmap = fmap_with_areas(vec![area("A", 0x2000, 0x200)]);
+        assert!(fmap.relocate(-0x1000).is_err());
+        fmap.base = u64::MAX - 0x10;
+        assert!(fmap.relocate(0x20).is_err());
+    }
+
+    #[test]
+    fn test_insert_and_remove_area() {
+        let mut fmap = fmap_with_areas(vec![area("RO", 0, 0x200), area("RW", 0x200, 0x200)]);
+
+        assert!(fmap.insert_area(area("GBB", 0x100, 0x80)).is_ok());
+        assert!(fmap.insert_area(area("RO_FRID", 0, 0x40)).is_ok());
+        assert_eq!(fmap.area_names(), ["RO", "RO_FRID", "GBB", "RW"]);
+
+        let before = fmap.clone();
+        assert!(matches!(
+            fmap.insert_area(area("BAD", 0x1c0, 0x80)),
+            Err(FMapError::AreaOverlap(a, b)) if a == "BAD" && b == "RO"
+        ));
+        assert!(matches!(
+            fmap.insert_area(area("BIG", 0x300, 0x200)),
+            Err(FMapError::AreaOutOfBounds(a)) if a == "BIG"
+        ));
+        assert_eq!(fmap, before);
+
+        assert_eq!(fmap.remove_area("GBB"), Some(area("GBB", 0x100, 0x80)));
+        assert_eq!(fmap.remove_area("GBB"), None);
+        assert_eq!(fmap.area_names(), ["RO", "RO_FRID", "RW"]);
+    }
+
+    #[test]
+    fn test_collect_errors() {
+        assert!(FMapError::collect(vec![]).is_ok());
+        assert!(matches!(
+            FMapError::collect(vec![FMapError::AreaNotFound("A".to_string())]),
+            Err(FMapError::AreaNotFound(name)) if name == "A"
+        ));
+        let e = FMapError::collect(vec![
+            FMapError::AreaNotFound("A".to_string()),
+            FMapError::AreaOutOfBounds("B".to_string()),
+        ])
+        .unwrap_err();
+        assert!(matches!(&e, FMapError::Multiple(v) if v.len() == 2));
+        assert_eq!(
+            e.to_string(),
+            "flash map area not found: A; flash map area out of bounds: B"
+        );
+    }
+
+    #[test]
+    fn test_area_names_and_display() {
+        let fmap = fmap_with_areas(vec![area("RO", 0, 0x200), area("RW", 0x200, 0x200)]);
+        assert_eq!(fmap.area_names(), vec!["RO", "RW"]);
+        let expected = "\
+fmap_signature:  __FMAP__
+fmap_version:    1.0
+fmap_base:       0x0
+fmap_size:       0x400 (1024)
+fmap_name:       example
+fmap_nareas:     2
+area:            1
+area_offset:     0x0
+area_size:       0x200 (512)
+area_name:       RO
+area:            2
+area_offset:     0x200
+area_size:       0x200 (512)
+area_name:       RW
+";
+        assert_eq!(fmap.to_string(), expected);
+    }
+
+    #[test]
+    fn test_validate_correct_layout() {
+        let fmap = fmap_with_areas(vec![
+            area("RO", 0, 0x200),
+            area("bootblock", 0, 0x200),
+            area("RW", 0x200, 0x200),
+        ]);
+        assert_eq!(fmap.validate(), vec![]);
+    }
+
+    #[test]
+    fn test_validate_overlap() {
+        let fmap = fmap_with_areas(vec![area("A", 0, 0x300), area("B", 0x200, 0x200)]);
+        assert_eq!(
+            fmap.validate(),
+            vec![
+                LayoutIssue::Overlap {
+                    a: area("A", 0, 0x300),
+                    b: area("B", 0x200, 0x200),
+                },
+                // Only the first of overlapping areas is considered when looking for gaps.
+                LayoutIssue::Gap {
+                    start: 0x300,
+                    size: 0x100
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_covered_and_gap_bytes() {
+        // RO contains RO_VPD and a gap, which is not counted as covered.
+        let fmap = fmap_with_areas(vec![
+            area("RO", 0, 0x200),
+            area("RO_VPD", 0, 0x100),
+            area("RW", 0x300, 0x80),
+        ]);
+        assert_eq!(fmap.covered_bytes(), 0x180);
+        assert_eq!(fmap.gap_bytes(), 0x280);
+        assert_eq!(fmap.covered_bytes() + fmap.gap_bytes(), fmap.size as u64);
+
+        // Zero-size area does not make its parent a non-leaf.
+        let fmap = fmap_with_areas(vec![area("ALL", 0, 0x400), area("EMPTY", 0x100, 0)]);
+        assert_eq!(fmap.covered_bytes(), 0x400);
+        assert_eq!(fmap.gap_bytes(), 0);
+
+        // Area beyond flash is neither covered nor leaves a gap.
+        let fmap = fmap_with_areas(vec![area("RO", 0, 0x200), area("BEYOND", 0x800, 0x100)]);
+        assert_eq!(fmap.covered_bytes(), 0x200);
+        assert_eq!(fmap.gap_bytes(), 0x200);
+        assert_eq!(fmap.covered_bytes() + fmap.gap_bytes(), fmap.size as u64);
+
+        assert_eq!(fmap_with_areas(vec![]).covered_bytes(), 0);
+    }
+
+    #[test]
+    fn test_validate_out_of_bounds() {
+        let fmap = fmap_with_areas(vec![area("A", 0, 0x400), area("B", 0x400, 0x10)]);
+        assert_eq!(
+            fmap.validate(),
+            vec![LayoutIssue::OutOfBounds {
+                area: area("B", 0x400, 0x10),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_zero_size() {
+        let fmap = fmap_with_areas(vec![area("A", 0, 0x400), area("B", 0x100, 0)]);
+        assert_eq!(
+            fmap.validate(),
+            vec![LayoutIssue::ZeroSize {
+                area: area("B", 0x100, 0),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_duplicate_name() {
+        let fmap = fmap_with_areas(vec![
+            area("A", 0, 0x200),
+            area("A", 0x200, 0x100),
+            area("A", 0x300, 0x100),
+        ]);
+        assert_eq!(
+            fmap.validate(),
+            vec![LayoutIssue::DuplicateName {
+                name: "A".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_names() {
+        let fmap = fmap_with_areas(vec![
+            area("B", 0, 0x100),
+            area("A", 0x100, 0x100),
+            area("C", 0x200, 0x100),
+            area("A", 0x300, 0x80),
+            area("B", 0x380, 0x80),
+        ]);
+        assert_eq!(fmap.duplicate_names(), vec!["B", "A"]);
+        assert_eq!(fmap.get("A"), Some(&fmap.areas[1]));
+        assert!(fmap_with_areas(vec![area("A", 0, 0x400)])
+            .duplicate_names()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_gap() {
+        let fmap = fmap_with_areas(vec![
+            area("RO", 0, 0x200),
+            area("bootblock", 0x10, 0x80),
+            area("fallback", 0x100, 0x80),
+            area("RW", 0x300, 0x80),
+        ]);
+        assert_eq!(
+            fmap.validate(),
+            vec![
+                LayoutIssue::Gap {
+                    start: 0,
+                    size: 0x10
+                },
+                LayoutIssue::Gap {
+                    start: 0x90,
+                    size: 0x70
+                },
+                LayoutIssue::Gap {
+                    start: 0x180,
+                    size: 0x80
+                },
+                LayoutIssue::Gap {
+                    start: 0x200,
+                    size: 0x100
+                },
+                LayoutIssue::Gap {
+                    start: 0x380,
+                    size: 0x80
+                },
+            ]
+        );
+    }
+
+    /// Minimal xorshift generator, so property tests are reproducible without extra crates.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn name(&mut self) -> String {
+            const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ_0123456789";
+            let len = self.below(NAME_LEN as u64 + 1);
+            (0..len)
+                .map(|_| CHARS[self.below(CHARS.len() as u64) as usize] as char)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_write_parse_round_trip() -> Result<(), String> {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            let fmap = FMap {
+                name: rng.name(),
+                version_major: VERSION_MAJOR as u8,
+                version_minor: rng.next() as u8,
+                base: rng.next(),
+                size: rng.next() as u32,
+                areas: (0..rng.below(16))
+                    .map(|_| FMapArea {
+                        name: rng.name(),
+                        offset: rng.next() as u32,
+                        size: rng.next() as u32,
+                        flags: FMapFlags::from_bits_truncate(rng.next() as u16),
+                    })
+                    .collect(),
+            };
+            let mut data = Vec::new();
+            if let Err(e) = fmap.write(&mut data) {
+                return Err(format!("Failed to serialize {fmap:?}. Error: {e}"));
+            }
+            assert_eq!(data.len(), HEADER_SIZE + fmap.areas.len() * AREA_SIZE);
+            match FMap::parse_fmap(&mut Cursor::new(&data)) {
+                Ok(parsed) => assert_eq!(parsed, fmap),
+                Err(e) => return Err(format!("Failed to parse {fmap:?}. Error: {e}")),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_rejects_long_name() {
+        let mut fmap = fmap_with_areas(vec![area(&"A".repeat(NAME_LEN + 1), 0, 0x10)]);
+        assert!(fmap.write(&mut Vec::new()).is_err());
+        fmap.areas.clear();
+        fmap.name = "N".repeat(NAME_LEN + 1);
+        assert!(fmap.write(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_random_data_does_not_panic() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..500 {
+            let mut data: Vec<u8> = (0..rng.below(0x400)).map(|_| rng.next() as u8).collect();
+            // Plant signature and valid version now and then to get past the header checks.
+            if data.len() >= HEADER_SIZE && rng.below(2) == 0 {
+                let offset = rng.below((data.len() - HEADER_SIZE) as u64 + 1) as usize;
+                data[offset..offset + SIGNATURE.len()].copy_from_slice(SIGNATURE);
+                data[offset + SIGNATURE.len()] = VERSION_MAJOR as u8;
+            }
+            let _ = FMap::parse_fmap(&mut Cursor::new(&data));
+            let _ = FMap::find_fmap(&mut Cursor::new(&data));
+        }
+    }
 }
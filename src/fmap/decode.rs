@@ -0,0 +1,153 @@
+//! Decoding of FMAP 1.x on-disk structures from bytes. Uses only `core` and `alloc`,
+//! so it does not depend on the `std` feature.
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::mem;
+
+use super::{
+    FMap, FMapArea, FMapFlags, AREA_SIZE, HEADER_SIZE, NAME_LEN, SIGNATURE, VERSION_MAJOR,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Data ends before the end of FMap header or areas.
+    TooShort,
+    /// Major version other than supported one or minor version above requested maximum.
+    IncorrectVersion(u8, u8),
+}
+
+#[derive(Debug, Default)]
+#[repr(C, packed)]
+struct FMapAreaRaw {
+    offset: u32,
+    size: u32,
+    name: [u8; NAME_LEN],
+    flags: u16,
+}
+
+#[derive(Debug, Default)]
+#[repr(C, packed)]
+struct FMapRaw {
+    signature: [u8; SIGNATURE.len()],
+    version_major: u8,
+    version_minor: u8,
+    base: u64,
+    size: u32,
+    name: [u8; NAME_LEN],
+    nareas: u16,
+}
+
+/// String stored in NUL-terminated or NUL-padded field, e.g. area name. Whole `data` is used
+/// if there is no NUL. Strings which are not valid UTF-8 are decoded as empty.
+pub fn c_string(data: &[u8]) -> String {
+    let len = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    core::str::from_utf8(&data[..len]).unwrap_or("").to_string()
+}
+
+impl From<FMapAreaRaw> for FMapArea {
+    fn from(fmap_area_raw: FMapAreaRaw) -> FMapArea {
+        FMapArea {
+            name: c_string(&fmap_area_raw.name),
+            offset: fmap_area_raw.offset,
+            size: fmap_area_raw.size,
+            flags: FMapFlags::from_bits(fmap_area_raw.flags).unwrap_or(FMapFlags::empty()),
+        }
+    }
+}
+
+impl From<FMapRaw> for FMap {
+    fn from(fmap_raw: FMapRaw) -> FMap {
+        FMap {
+            name: c_string(&fmap_raw.name),
+            version_major: fmap_raw.version_major,
+            version_minor: fmap_raw.version_minor,
+            base: fmap_raw.base,
+            size: fmap_raw.size,
+            areas: Vec::new(),
+        }
+    }
+}
+
+/// Decodes FMap header at the beginning of `data`. Returns FMap without areas and number
+/// of areas following the header. Signature is not checked.
+pub fn decode_header(
+    data: &[u8],
+    max_version_minor: Option<u8>,
+) -> Result<(FMap, u16), DecodeError> {
+    let mut buffer = [0_u8; mem::size_of::<FMapRaw>()];
+    buffer.copy_from_slice(data.get(..HEADER_SIZE).ok_or(DecodeError::TooShort)?);
+    let fmap_raw: FMapRaw = unsafe { mem::transmute(buffer) };
+
+    if fmap_raw.version_major != VERSION_MAJOR as u8
+        || max_version_minor.is_some_and(|max| fmap_raw.version_minor > max)
+    {
+        return Err(DecodeError::IncorrectVersion(
+            fmap_raw.version_major,
+            fmap_raw.version_minor,
+        ));
+    }
+
+    let nareas = fmap_raw.nareas;
+    Ok((FMap::from(fmap_raw), nareas))
+}
+
+/// Decodes single area entry at the beginning of `data`.
+pub fn decode_area(data: &[u8]) -> Result<FMapArea, DecodeError> {
+    let mut buffer = [0_u8; mem::size_of::<FMapAreaRaw>()];
+    buffer.copy_from_slice(data.get(..AREA_SIZE).ok_or(DecodeError::TooShort)?);
+    let fmap_area_raw: FMapAreaRaw = unsafe { mem::transmute(buffer) };
+    Ok(FMapArea::from(fmap_area_raw))
+}
+
+/// Decodes FMap with all its areas placed at the beginning of `data`.
+pub fn parse_from_slice(data: &[u8], max_version_minor: Option<u8>) -> Result<FMap, DecodeError> {
+    let (mut fmap, nareas) = decode_header(data, max_version_minor)?;
+    let areas = data.get(HEADER_SIZE..).ok_or(DecodeError::TooShort)?;
+    for i in 0..nareas as usize {
+        let entry = areas.get(i * AREA_SIZE..).ok_or(DecodeError::TooShort)?;
+        fmap.areas.push(decode_area(entry)?);
+    }
+    Ok(fmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_from_slice() {
+        let mut d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/example_fmap.bin");
+        let data = std::fs::read(d).unwrap();
+
+        let fmap = parse_from_slice(&data[0x200..], None).unwrap();
+        assert_eq!(fmap.version_major, 1);
+        assert_eq!(fmap.size, 0x400);
+        assert_eq!(
+            fmap.area_names(),
+            ["bootblock", "normal", "fallback", "data"]
+        );
+        assert_eq!(fmap.areas[1].offset, 0x80);
+        assert_eq!(
+            fmap.areas[1].flags,
+            FMapFlags::Static | FMapFlags::Compressed
+        );
+
+        // Area entries cut in the middle.
+        let end = 0x200 + HEADER_SIZE + 3 * AREA_SIZE + 4;
+        assert_eq!(
+            parse_from_slice(&data[0x200..end], None),
+            Err(DecodeError::TooShort)
+        );
+        assert_eq!(
+            parse_from_slice(&data[0x200..0x210], None),
+            Err(DecodeError::TooShort)
+        );
+        let mut data = data;
+        data[0x209] = 1;
+        assert_eq!(
+            parse_from_slice(&data[0x200..], Some(0)),
+            Err(DecodeError::IncorrectVersion(1, 1))
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Nesting of FlashMap areas, e.g. for human-readable dump and space accounting.
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::FMap;
+
+/// Area placed in the tree. Offsets are relative to the flash start, so `end()` of areas
+/// with 32-bit offsets and sizes always fits in 64 bits. Flash base is added when printing.
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+    pub aliases: Vec<String>,
+    pub parent: Option<Rc<RefCell<Node>>>,
+    pub children: Vec<Rc<RefCell<Node>>>,
+}
+
+pub type NodeRef = Rc<RefCell<Node>>;
+
+impl Node {
+    pub fn is_duplicate(&self, node: &Node) -> bool {
+        self.offset == node.offset && self.size == node.size
+    }
+
+    pub fn end(&self) -> u64 {
+        self.offset + self.size
+    }
+
+    pub fn overlaps(&self, node: &Node) -> bool {
+        super::overlaps(self.offset..self.end(), node.offset..node.end())
+    }
+
+    pub fn fits_in(&self, node: &Node) -> bool {
+        self.offset >= node.offset && self.end() <= node.end()
+    }
+
+    pub fn parents_number(&self) -> usize {
+        match &self.parent {
+            None => 0,
+            Some(p) => p.borrow().parents_number() + 1,
+        }
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.is_duplicate(other)
+    }
+}
+
+/// Builds tree of areas nested in the synthetic `-entire flash-` root node. With `dedup`,
+/// areas having the same offset and size as an earlier one become its aliases instead of
+/// separate sibling nodes. Areas overlapping already placed ones are skipped.
+/// Returned nodes are sorted by offset and the first one is the root.
+pub fn build_tree(fmap: &FMap, dedup: bool) -> Vec<NodeRef> {
+    // Convert into nodes.
+    let mut nodes = fmap
+        .areas
+        .iter()
+        .map(|ar| {
+            Rc::new(RefCell::new(Node {
+                name: ar.name.clone(),
+                offset: ar.offset as u64,
+                size: ar.size as u64,
+                aliases: vec![],
+                parent: None,
+                children: vec![],
+            }))
+        })
+        .collect::<Vec<_>>();
+    // Synthetic root is kept apart from real areas, so it is never sorted after or aliased
+    // by an area with the same geometry or name.
+    let root = Rc::new(RefCell::new(Node {
+        name: String::from("-entire flash-"),
+        offset: 0,
+        size: fmap.size as u64,
+        aliases: vec![],
+        parent: None,
+        children: vec![],
+    }));
+
+    // Sort ascending by offset and descending by size to push larger areas first.
+    nodes.sort_unstable_by_key(|a| {
+        let v = a.borrow();
+        (v.offset, u64::MAX - v.size, v.name.clone())
+    });
+
+    // Remove duplicates and skip overlaps
+    let mut deduplicated = vec![root];
+    'dedup_outer: for node in nodes.iter() {
+        for (i, d) in deduplicated.iter().enumerate() {
+            let node = node.borrow();
+            let mut d = d.borrow_mut();
+            if dedup && i != 0 && node.is_duplicate(&d) {
+                d.aliases.push(node.name.clone());
+                continue 'dedup_outer;
+            } else if node.overlaps(&d) {
+                continue 'dedup_outer;
+            }
+        }
+        // Add first occurrence of entry.
+        deduplicated.push(node.clone());
+    }
+    drop(nodes);
+
+    // Skip first as it is the root node.
+    for i in 1..deduplicated.len() {
+        let mut node_a = deduplicated[i].borrow_mut();
+        for k in (0..i).rev() {
+            let mut node_b = deduplicated[k].borrow_mut();
+            // Areas of the same geometry are siblings, unless one is the root.
+            if k != 0 && node_a.is_duplicate(&node_b) {
+                continue;
+            }
+            if node_a.fits_in(&node_b) {
+                node_a.parent = Some(deduplicated[k].clone());
+                node_b.children.push(deduplicated[i].clone());
+                break;
+            }
+        }
+    }
+
+    deduplicated
+}
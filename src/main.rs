@@ -1,12 +1,10 @@
-use clap::{Command, CommandFactory, Parser, Subcommand};
+use clap::{ArgAction, Command, CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::{generate, Generator, Shell};
-use log::error;
+use futility_rs::cmd;
+use log::{error, Level, Log, Metadata, Record};
 use std::io;
 use std::process::exit;
 
-pub mod cmd;
-pub mod fmap;
-
 #[derive(Parser)]
 #[command(version, about, long_about = None, arg_required_else_help = true)]
 pub struct Cli {
@@ -14,11 +12,40 @@ pub struct Cli {
     #[arg(long = "generate", value_enum)]
     generator: Option<Shell>,
 
+    /// Print JSON description of lines and columns of given dump-fmap output format.
+    #[arg(long, value_enum, value_name = "FORMAT", hide = true)]
+    dump_schema: Option<cmd::dump_fmap::SchemaFormat>,
+
+    /// Format of the error report printed on failure.
+    #[arg(long, value_enum, global = true, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+
+    /// Increase logging verbosity. Can be repeated. RUST_LOG takes precedence.
+    #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Log errors only. RUST_LOG takes precedence.
+    #[arg(short, long, action, global = true)]
+    quiet: bool,
+
+    /// Suppress advisory warnings, e.g. about gaps in FlashMap. Errors are still logged.
+    #[arg(long, action, global = true)]
+    no_warnings: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum ErrorFormat {
+    /// Log error message.
+    Text,
+    /// Print JSON object with error message and kind to stderr.
+    Json,
+}
+
 #[derive(Subcommand)]
+#[allow(clippy::enum_variant_names)]
 enum Commands {
     #[command(alias("dump_fmap"), disable_help_flag = true)]
     /// Dump FlashMap (FMAP) layout or sections.
@@ -27,17 +54,80 @@ enum Commands {
     #[command()]
     ExtractFmap(cmd::extract_fmap::ExtractFmapArgs),
 
+    #[command()]
+    /// Print firmware IDs stored in RO_FRID and RW_FWID* areas.
+    Fwid(cmd::fwid::FwidArgs),
+
     #[command(alias("load_fmap"))]
     LoadFmap(cmd::load_fmap::LoadFmapArgs),
+
+    #[command()]
+    /// Print metadata of single FlashMap (FMAP) area.
+    Info(cmd::info::InfoArgs),
+
+    #[command()]
+    /// Modify flags or offsets of FlashMap (FMAP) areas without touching area data.
+    Modify(cmd::modify::ModifyArgs),
+
+    #[command()]
+    /// Check whether image contains FlashMap (FMAP). Result is reported via exit code.
+    Probe(cmd::probe::ProbeArgs),
+
+    #[command()]
+    /// Extract FlashMap (FMAP) sections, run command on them and load them back.
+    Repack(cmd::repack::RepackArgs),
+
+    #[command()]
+    /// Print FlashMap (FMAP) space utilization summary.
+    Summary(cmd::summary::SummaryArgs),
 }
 
 fn print_completions<G: Generator>(gen: G, cmd: &mut Command) {
     generate(gen, cmd, cmd.get_name().to_string(), &mut io::stdout());
 }
 
+fn log_level(verbose: u8, quiet: bool) -> &'static str {
+    match (quiet, verbose) {
+        (true, _) => "error",
+        (false, 0) => "warn",
+        (false, 1) => "info",
+        (false, 2) => "debug",
+        (false, _) => "trace",
+    }
+}
+
+/// Logger dropping warnings. Logs are written to stderr, so stdout carries only command output.
+struct NoWarningsLogger(env_logger::Logger);
+
+impl Log for NoWarningsLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() != Level::Warn && self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.0.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
 fn main() {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
     let cli = Cli::parse();
+    let mut builder = env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or(log_level(cli.verbose, cli.quiet)),
+    );
+    if cli.no_warnings {
+        let logger = builder.build();
+        log::set_max_level(logger.filter());
+        log::set_boxed_logger(Box::new(NoWarningsLogger(logger)))
+            .expect("logger should be initialized only once");
+    } else {
+        builder.init();
+    }
 
     if let Some(generator) = cli.generator {
         let mut cmd = Cli::command();
@@ -45,6 +135,10 @@ fn main() {
         print_completions(generator, &mut cmd);
         exit(0);
     }
+    if let Some(format) = cli.dump_schema {
+        println!("{:#}", cmd::dump_fmap::output_schema(format));
+        exit(0);
+    }
     let command = cli
         .command
         .as_ref()
@@ -52,11 +146,26 @@ fn main() {
     let result = match command {
         Commands::DumpFmap(args) => cmd::dump_fmap::run_command(args),
         Commands::ExtractFmap(args) => cmd::extract_fmap::run_command(args),
+        Commands::Fwid(args) => cmd::fwid::run_command(args),
         Commands::LoadFmap(args) => cmd::load_fmap::run_command(args),
+        Commands::Info(args) => cmd::info::run_command(args),
+        Commands::Modify(args) => cmd::modify::run_command(args),
+        Commands::Probe(args) => cmd::probe::run_command(args),
+        Commands::Repack(args) => cmd::repack::run_command(args),
+        Commands::Summary(args) => cmd::summary::run_command(args),
     };
 
     if let Err(e) = result {
-        error!("{}", e);
-        exit(-1);
+        let (_, code) = cmd::error::error_kind(e.as_ref());
+        match cli.error_format {
+            ErrorFormat::Text => {
+                error!("{}", e);
+                exit(code);
+            }
+            ErrorFormat::Json => {
+                eprintln!("{}", cmd::error::error_json(e.as_ref()));
+                exit(code);
+            }
+        }
     }
 }